@@ -0,0 +1,259 @@
+use std::fmt;
+
+use crate::error::CalcError;
+use crate::token::{Operator, Token};
+
+/// An expression tree, built from the reverse polish notation the shunting yard produces.
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum Expr {
+  Number(f64),
+  Constant(String),
+  Variable(String),
+  Text(String),
+  Unit(String),
+  Unary(Operator, Box<Expr>),
+  Binary(Operator, Box<Expr>, Box<Expr>),
+  Call(String, Vec<Expr>),
+  List(Vec<Expr>),
+  /// The item of a list at a zero-based index, like `v[1]`.
+  Index(Box<Expr>, Box<Expr>),
+  /// Assign to a variable, combining with its current value first for compound assignments.
+  Assign(String, Option<Operator>, Box<Expr>),
+}
+
+impl Expr {
+  /// Fold a reverse polish notation token vector into a tree.
+  pub(crate) fn from_rpn(tokens: Vec<Token>) -> Result<Expr, CalcError> {
+    let mut stack: Vec<Expr> = Vec::new();
+
+    for token in tokens {
+      let expr = match token {
+        Token::Number(n) => Expr::Number(n),
+        Token::Constant(name) => Expr::Constant(name),
+        Token::Variable(name) => Expr::Variable(name),
+        Token::Text(text) => Expr::Text(text),
+        Token::Unit(unit) => Expr::Unit(unit),
+        Token::Call(name, count) => {
+          let start = stack
+            .len()
+            .checked_sub(count)
+            .ok_or(CalcError::InvalidExpression)?;
+          Expr::Call(name, stack.split_off(start))
+        }
+        Token::List(count) => {
+          let start = stack
+            .len()
+            .checked_sub(count)
+            .ok_or(CalcError::InvalidExpression)?;
+          Expr::List(stack.split_off(start))
+        }
+        Token::Index => {
+          let index = stack.pop().ok_or(CalcError::InvalidExpression)?;
+          let list = stack.pop().ok_or(CalcError::InvalidExpression)?;
+          Expr::Index(Box::new(list), Box::new(index))
+        }
+        Token::Operator(operator) if operator.arity() == 1 => {
+          let operand = stack.pop().ok_or(CalcError::InvalidExpression)?;
+          Expr::Unary(operator, Box::new(operand))
+        }
+        Token::Operator(operator) => {
+          let right = stack.pop().ok_or(CalcError::InvalidExpression)?;
+          let left = stack.pop().ok_or(CalcError::InvalidExpression)?;
+          Expr::Binary(operator, Box::new(left), Box::new(right))
+        }
+        Token::Assign(operator) => {
+          let value = stack.pop().ok_or(CalcError::InvalidExpression)?;
+          match stack.pop().ok_or(CalcError::InvalidExpression)? {
+            // Whether a constant may be assigned to is up to the evaluator.
+            Expr::Variable(name) | Expr::Constant(name) => {
+              Expr::Assign(name, operator, Box::new(value))
+            }
+            _ => return Err(CalcError::InvalidAssignment),
+          }
+        }
+        Token::Function(_) | Token::Parenthesis(_) | Token::Bracket(..) | Token::Separator => {
+          return Err(CalcError::InvalidExpression)
+        }
+      };
+      stack.push(expr);
+    }
+
+    let expr = stack.pop().ok_or(CalcError::InvalidExpression)?;
+    if !stack.is_empty() {
+      return Err(CalcError::InvalidExpression);
+    }
+    Ok(expr)
+  }
+
+  /// The tree as nested JSON objects with a `type` and the node's parts, like
+  /// `{"type":"binary","operator":"+","left":...,"right":...}`.
+  pub(crate) fn to_json(&self) -> String {
+    let list = |items: &[Expr]| {
+      let items: Vec<String> = items.iter().map(Expr::to_json).collect();
+      format!("[{}]", items.join(","))
+    };
+    match self {
+      Expr::Number(number) => format!("{{\"type\":\"number\",\"value\":{}}}", number),
+      Expr::Constant(name) => format!("{{\"type\":\"constant\",\"name\":\"{}\"}}", name),
+      Expr::Variable(name) => format!("{{\"type\":\"variable\",\"name\":\"{}\"}}", name),
+      Expr::Unit(unit) => format!("{{\"type\":\"unit\",\"name\":\"{}\"}}", unit),
+      Expr::Text(text) => format!("{{\"type\":\"text\",\"value\":{:?}}}", text),
+      Expr::Unary(operator, operand) => format!(
+        "{{\"type\":\"unary\",\"operator\":\"{}\",\"operand\":{}}}",
+        operator.symbol(),
+        operand.to_json()
+      ),
+      Expr::Binary(operator, left, right) => format!(
+        "{{\"type\":\"binary\",\"operator\":\"{}\",\"left\":{},\"right\":{}}}",
+        operator.symbol(),
+        left.to_json(),
+        right.to_json()
+      ),
+      Expr::Call(name, arguments) => format!(
+        "{{\"type\":\"call\",\"name\":\"{}\",\"arguments\":{}}}",
+        name,
+        list(arguments)
+      ),
+      Expr::List(items) => format!("{{\"type\":\"list\",\"items\":{}}}", list(items)),
+      Expr::Index(list, index) => format!(
+        "{{\"type\":\"index\",\"list\":{},\"index\":{}}}",
+        list.to_json(),
+        index.to_json()
+      ),
+      Expr::Assign(name, operator, value) => format!(
+        "{{\"type\":\"assignment\",\"name\":\"{}\",\"operator\":\"{}=\",\"value\":{}}}",
+        name,
+        operator.map_or("", |operator| operator.symbol()),
+        value.to_json()
+      ),
+    }
+  }
+
+  /// Collect the names of all variables the expression reads or assigns, in order of appearance.
+  pub(crate) fn variables(&self) -> Vec<&str> {
+    let mut names = Vec::new();
+    self.collect_variables(&mut names);
+    names
+  }
+
+  fn collect_variables<'a>(&'a self, names: &mut Vec<&'a str>) {
+    match self {
+      Expr::Number(_) | Expr::Constant(_) | Expr::Text(_) | Expr::Unit(_) => {}
+      Expr::Variable(name) => {
+        if !names.contains(&name.as_str()) {
+          names.push(name);
+        }
+      }
+      Expr::Unary(_, operand) => operand.collect_variables(names),
+      Expr::Binary(_, left, right) | Expr::Index(left, right) => {
+        left.collect_variables(names);
+        right.collect_variables(names);
+      }
+      Expr::Call(_, items) | Expr::List(items) => {
+        items.iter().for_each(|item| item.collect_variables(names))
+      }
+      Expr::Assign(name, _, value) => {
+        if !names.contains(&name.as_str()) {
+          names.push(name);
+        }
+        value.collect_variables(names);
+      }
+    }
+  }
+}
+
+/// How tightly an expression holds together when it appears as an operand, for deciding where
+/// parentheses are needed.
+fn binding(expr: &Expr) -> i32 {
+  match expr {
+    // A negative number, like one a step of simplification left, reads like its negation.
+    Expr::Number(number) if number.is_sign_negative() => Operator::Negate.precedence(),
+    Expr::Number(_)
+    | Expr::Constant(_)
+    | Expr::Variable(_)
+    | Expr::Text(_)
+    | Expr::Unit(_)
+    | Expr::Call(..)
+    | Expr::List(_)
+    | Expr::Index(..) => 4,
+    Expr::Unary(operator, _) | Expr::Binary(operator, _, _) => operator.precedence(),
+    Expr::Assign(..) => i32::MIN,
+  }
+}
+
+/// Write expressions separated by commas.
+fn items(f: &mut fmt::Formatter, items: &[Expr]) -> fmt::Result {
+  for (i, item) in items.iter().enumerate() {
+    if i > 0 {
+      write!(f, ", ")?;
+    }
+    write!(f, "{}", item)?;
+  }
+  Ok(())
+}
+
+/// Write an operand, in parentheses if it would otherwise be read differently.
+fn operand(f: &mut fmt::Formatter, expr: &Expr, parenthesise: bool) -> fmt::Result {
+  if parenthesise {
+    write!(f, "({})", expr)
+  } else {
+    write!(f, "{}", expr)
+  }
+}
+
+/// The canonical form of the expression: one space around binary operators and `=`, explicit
+/// `*`, and only the parentheses the structure of the tree needs.
+impl fmt::Display for Expr {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Expr::Number(number) => write!(f, "{}", number),
+      Expr::Constant(name) | Expr::Variable(name) | Expr::Unit(name) => write!(f, "{}", name),
+      Expr::Text(text) => write!(f, "\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"")),
+      Expr::Unary(operator, value) => {
+        write!(f, "{}", operator.symbol())?;
+        operand(f, value, binding(value) < operator.precedence())
+      }
+      Expr::Binary(operator, left, right) => {
+        let precedence = operator.precedence();
+        let left_associative = operator.associativity().is_left();
+        operand(
+          f,
+          left,
+          binding(left) < precedence || (binding(left) == precedence && !left_associative),
+        )?;
+        write!(f, " {} ", operator.symbol())?;
+        operand(
+          f,
+          right,
+          binding(right) < precedence || (binding(right) == precedence && left_associative),
+        )
+      }
+      Expr::Call(name, arguments) => {
+        write!(f, "{}(", name)?;
+        items(f, arguments)?;
+        write!(f, ")")
+      }
+      Expr::List(list) => {
+        write!(f, "[")?;
+        items(f, list)?;
+        write!(f, "]")
+      }
+      Expr::Index(list, index) => {
+        // Only these are followed by an index rather than a list when read back.
+        let indexable = matches!(
+          **list,
+          Expr::Variable(_) | Expr::Call(..) | Expr::List(_) | Expr::Index(..)
+        );
+        operand(f, list, !indexable)?;
+        write!(f, "[{}]", index)
+      }
+      Expr::Assign(name, operator, value) => write!(
+        f,
+        "{} {}= {}",
+        name,
+        operator.map_or("", |operator| operator.symbol()),
+        value
+      ),
+    }
+  }
+}
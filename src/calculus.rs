@@ -0,0 +1,289 @@
+use crate::ast::Expr;
+use crate::error::CalcError;
+use crate::evaluator::Evaluator;
+use crate::functions::Arity;
+use crate::value::Value;
+
+/// Functions of the form `name(body, variable, point, ...)` that evaluate `body` with `variable`
+/// bound to numbers around the points, like `avgrate(x^2, x, 1, 3)`.
+pub(crate) const CALCULUS: &[(&str, Arity)] = &[
+  ("avgrate", Arity::Exactly(4)),
+  ("deriv", Arity::Between(3, 4)),
+  ("integrate", Arity::Exactly(5)),
+];
+
+/// The step `deriv` takes to either side of the point unless given one. The error of a central
+/// difference shrinks with the square of the step, but the rounding error of subtracting two
+/// nearly equal results grows as the step shrinks; around `1e-5` the two balance for values of
+/// moderate size, leaving roughly ten correct digits.
+pub(crate) const DEFAULT_DERIVATIVE_STEP: f64 = 1e-5;
+
+impl Evaluator {
+  /// Evaluate a call to one of the [`CALCULUS`] functions.
+  pub(crate) fn calculus(&mut self, name: &str, arguments: &[Expr]) -> Result<Value, CalcError> {
+    if name == "integrate" {
+      self.allow_loop(name)?;
+    }
+    let (body, variable, points) = calculus_arguments(name, arguments)?;
+    let points = points
+      .iter()
+      .map(|point| self.eval(point).map(|value| value.to_f64()))
+      .collect::<Result<Vec<_>, _>>()?;
+    let result = match (name, points.as_slice()) {
+      ("avgrate", &[a, b]) => self.average_rate(body, variable, a, b)?,
+      ("deriv", &[point]) => self.derivative(body, variable, point, DEFAULT_DERIVATIVE_STEP)?,
+      ("deriv", &[point, step]) => self.derivative(body, variable, point, step)?,
+      ("integrate", &[a, b, n]) => self.integral(body, variable, a, b, n)?,
+      _ => unreachable!("'{}' isn't a calculus function", name),
+    };
+    Ok(self.number(result))
+  }
+
+  /// `(f(b) - f(a)) / (b - a)`, the slope of the line through `body` at `a` and `b`.
+  fn average_rate(
+    &mut self,
+    body: &Expr,
+    variable: &str,
+    a: f64,
+    b: f64,
+  ) -> Result<f64, CalcError> {
+    if a == b {
+      return Err(CalcError::DivisionByZero);
+    }
+    let [fa, fb] = self.at_points(body, variable, [a, b])?;
+    Ok((fb - fa) / (b - a))
+  }
+
+  /// `(f(point + step) - f(point - step)) / (2 step)`, the central difference approximating the
+  /// derivative of `body` at `point`.
+  fn derivative(
+    &mut self,
+    body: &Expr,
+    variable: &str,
+    point: f64,
+    step: f64,
+  ) -> Result<f64, CalcError> {
+    if step <= 0.0 || !step.is_finite() {
+      return Err(CalcError::InvalidRange(format!(
+        "the step must be positive, not {}",
+        step
+      )));
+    }
+    let [below, above] = self.at_points(body, variable, [point - step, point + step])?;
+    Ok((above - below) / (2.0 * step))
+  }
+
+  /// The integral of `body` from `a` to `b` by the trapezoidal rule with `n` subintervals of equal
+  /// width. Its error shrinks with the square of the width.
+  fn integral(
+    &mut self,
+    body: &Expr,
+    variable: &str,
+    a: f64,
+    b: f64,
+    n: f64,
+  ) -> Result<f64, CalcError> {
+    if n < 1.0 || n.fract() != 0.0 || !n.is_finite() {
+      return Err(CalcError::InvalidRange(format!(
+        "the number of subintervals must be a positive integer, not {}",
+        n
+      )));
+    }
+    if !a.is_finite() || !b.is_finite() {
+      return Err(CalcError::InvalidRange(
+        "the bounds must be finite".to_string(),
+      ));
+    }
+    if a > b {
+      return Err(CalcError::InvalidRange(format!(
+        "the start {} is greater than the end {}",
+        a, b
+      )));
+    }
+    let width = (b - a) / n;
+    let intervals = n as u64;
+    self.scoped(variable, |evaluator| {
+      let mut sum = 0.0;
+      for i in 0..=intervals {
+        evaluator.check_deadline()?;
+        // Multiplying instead of adding up the widths keeps rounding errors from accumulating.
+        let x = if i == intervals {
+          b
+        } else {
+          a + i as f64 * width
+        };
+        evaluator.bind(variable, evaluator.number(x));
+        let y = evaluator.eval(body)?.to_f64();
+        sum += if i == 0 || i == intervals { y / 2.0 } else { y };
+      }
+      Ok(sum * width)
+    })
+  }
+
+  /// Evaluate `body` with `variable` bound to each point in turn, leaving the variable as it was.
+  fn at_points<const N: usize>(
+    &mut self,
+    body: &Expr,
+    variable: &str,
+    points: [f64; N],
+  ) -> Result<[f64; N], CalcError> {
+    self.scoped(variable, |evaluator| {
+      let mut results = [0.0; N];
+      for (result, point) in results.iter_mut().zip(points) {
+        evaluator.bind(variable, evaluator.number(point));
+        *result = evaluator.eval(body)?.to_f64();
+      }
+      Ok(results)
+    })
+  }
+}
+
+/// The arity of one of the [`CALCULUS`] functions.
+pub(crate) fn calculus_arity(name: &str) -> Option<Arity> {
+  CALCULUS
+    .iter()
+    .find(|(calculus, _)| *calculus == name)
+    .map(|&(_, arity)| arity)
+}
+
+/// Split the arguments of one of the [`CALCULUS`] functions into the body, the variable, and the
+/// points.
+pub(crate) fn calculus_arguments<'a>(
+  name: &str,
+  arguments: &'a [Expr],
+) -> Result<(&'a Expr, &'a str, &'a [Expr]), CalcError> {
+  let arity = calculus_arity(name).expect("a calculus function");
+  arity.check(name, arguments.len())?;
+  let [body, Expr::Variable(variable), points @ ..] = arguments else {
+    return Err(CalcError::Unsupported(format!(
+      "the second argument of '{}' must be a variable",
+      name
+    )));
+  };
+  Ok((body, variable, points))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::testing::{assert_close, Tolerance::Absolute};
+  use crate::{evaluate, CalcError, Config, Evaluator};
+
+  #[test]
+  fn test_average_rate() {
+    assert_eq!(evaluate("avgrate(x^2, x, 1, 3)"), Ok(4.0));
+    assert_eq!(evaluate("avgrate(3t + 1, t, -2, 5)"), Ok(3.0));
+    assert_eq!(evaluate("avgrate(x^3, x, 2, 0)"), Ok(4.0));
+    let rate = evaluate("avgrate(sin(x), x, 0, pi/2)").unwrap();
+    assert!((rate - 2.0 / std::f64::consts::PI).abs() < 1e-12);
+
+    let mut evaluator = Evaluator::new();
+    evaluator.evaluate("x = 10").unwrap();
+    assert_eq!(evaluator.evaluate("avgrate(x^2, x, 0, x)"), Ok(10.0));
+    assert_eq!(evaluator.evaluate("x"), Ok(10.0));
+    assert_eq!(
+      evaluator.validate("avgrate(x * y, x, 0, 1)"),
+      Err(CalcError::UnknownVariable("y".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_derivative() {
+    let close = |expression, expected| assert_close(expression, expected, Absolute(1e-8));
+    close("deriv(x^2, x, 3)", 6.0);
+    close("deriv(x^3 - 2x, x, -1)", 1.0);
+    close("deriv(sin(x), x, 0)", 1.0);
+    close("deriv(exp(t), t, 1)", std::f64::consts::E);
+    close("deriv(ln(x), x, 4)", 0.25);
+    close("deriv(x^2, x, 3, 0.5)", 6.0);
+    assert_eq!(evaluate("deriv(x^3, x, 1, 1)"), Ok(4.0));
+
+    let mut evaluator = Evaluator::new();
+    evaluator.evaluate("x = 2").unwrap();
+    assert!((evaluator.evaluate("deriv(x^2, x, x)").unwrap() - 4.0).abs() < 1e-8);
+    assert_eq!(evaluator.evaluate("x"), Ok(2.0));
+  }
+
+  #[test]
+  fn test_derivative_errors() {
+    for step in ["0", "-0.1"] {
+      assert!(matches!(
+        evaluate(&format!("deriv(x^2, x, 1, {})", step)),
+        Err(CalcError::InvalidRange(_))
+      ));
+    }
+    let mismatch = |expected, found| {
+      Err(CalcError::ArityMismatch {
+        function: "deriv".to_string(),
+        expected,
+        found,
+      })
+    };
+    assert_eq!(evaluate("deriv(x^2, x)"), mismatch(3, 2));
+    assert_eq!(evaluate("deriv(x^2, x, 1, 2, 3)"), mismatch(4, 5));
+    assert!(matches!(
+      evaluate("deriv(x^2, 1, 3)"),
+      Err(CalcError::Unsupported(_))
+    ));
+  }
+
+  #[test]
+  fn test_integral() {
+    let close =
+      |expression, expected, tolerance| assert_close(expression, expected, Absolute(tolerance));
+    close("integrate(x^2, x, 0, 3, 1000)", 9.0, 1e-4);
+    close("integrate(sin(x), x, 0, pi, 1000)", 2.0, 1e-5);
+    close("integrate(1/t, t, 1, e, 1000)", 1.0, 1e-6);
+    close("integrate(exp(x), x, -1, 1, 2000)", 2.0 * 1f64.sinh(), 1e-6);
+    assert_eq!(evaluate("integrate(2x + 1, x, 0, 2, 1)"), Ok(6.0));
+    assert_eq!(evaluate("integrate(x^2, x, 1, 1, 10)"), Ok(0.0));
+
+    let mut evaluator = Evaluator::new();
+    evaluator.evaluate("x = 4").unwrap();
+    assert_eq!(evaluator.evaluate("integrate(1, x, 0, x, 8)"), Ok(4.0));
+    assert_eq!(evaluator.evaluate("x"), Ok(4.0));
+    assert_eq!(
+      evaluator.validate("integrate(x * y, x, 0, 1, 10)"),
+      Err(CalcError::UnknownVariable("y".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_integral_errors() {
+    for invalid in [
+      "integrate(x, x, 1, 0, 10)",
+      "integrate(x, x, 0, 1, 0)",
+      "integrate(x, x, 0, 1, 2.5)",
+      "integrate(x, x, 0, 1/0, 10)",
+    ] {
+      assert!(matches!(evaluate(invalid), Err(CalcError::InvalidRange(_))));
+    }
+    let mut sandbox = Evaluator::with_config(Config {
+      sandbox: true,
+      ..Config::default()
+    });
+    assert!(matches!(
+      sandbox.evaluate("integrate(x, x, 0, 1, 10)"),
+      Err(CalcError::FeatureDisabled(_))
+    ));
+  }
+
+  #[test]
+  fn test_average_rate_errors() {
+    assert_eq!(
+      evaluate("avgrate(x^2, x, 2, 2)"),
+      Err(CalcError::DivisionByZero)
+    );
+    assert_eq!(
+      evaluate("avgrate(x^2, x, 1)"),
+      Err(CalcError::ArityMismatch {
+        function: "avgrate".to_string(),
+        expected: 4,
+        found: 3
+      })
+    );
+    assert!(matches!(
+      evaluate("avgrate(x^2, 2, 1, 3)"),
+      Err(CalcError::Unsupported(_))
+    ));
+  }
+}
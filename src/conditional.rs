@@ -0,0 +1,70 @@
+use crate::ast::Expr;
+use crate::error::CalcError;
+use crate::evaluator::Evaluator;
+use crate::value::Value;
+
+/// `if(condition, then, otherwise)` evaluates only the branch the condition picks, so
+/// `if(x == 0, 0, 1 // x)` never divides by zero. Any condition that isn't `0` or `false` holds.
+pub(crate) const CONDITIONAL: &str = "if";
+
+/// The condition and the two branches.
+pub(crate) const CONDITIONAL_ARITY: usize = 3;
+
+impl Evaluator {
+  /// Evaluate a call to [`CONDITIONAL`].
+  pub(crate) fn conditional(&mut self, arguments: &[Expr]) -> Result<Value, CalcError> {
+    let [condition, then, otherwise] = arguments else {
+      return Err(CalcError::ArityMismatch {
+        function: CONDITIONAL.to_string(),
+        expected: CONDITIONAL_ARITY,
+        found: arguments.len(),
+      });
+    };
+    let holds = match self.eval(condition)? {
+      Value::List(_) | Value::Text(_) => {
+        return Err(CalcError::Unsupported(
+          "the condition of 'if' must be a number".to_string(),
+        ))
+      }
+      condition => condition.to_f64() != 0.0,
+    };
+    self.eval(if holds { then } else { otherwise })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{evaluate, evaluate_typed, CalcError, Value};
+
+  #[test]
+  fn test_conditional() {
+    assert_eq!(evaluate("if(1 < 2, 10, 20)"), Ok(10.0));
+    assert_eq!(evaluate("if(1 > 2, 10, 20)"), Ok(20.0));
+    assert_eq!(evaluate("if(0, 1, 2) + if(-3, 1, 2)"), Ok(3.0));
+    assert_eq!(evaluate("if(2 > 1, if(3 > 4, 1, 2), 3)"), Ok(2.0));
+    assert_eq!(evaluate_typed("if(1, 2 > 1, 0)"), Ok(Value::Boolean(true)));
+  }
+
+  #[test]
+  fn test_only_the_chosen_branch_is_evaluated() {
+    assert_eq!(evaluate("if(1, 2, 7 // 0)"), Ok(2.0));
+    assert_eq!(evaluate("if(0, 7 // 0, 3)"), Ok(3.0));
+    assert_eq!(evaluate("if(1, 7 // 0, 3)"), Err(CalcError::DivisionByZero));
+  }
+
+  #[test]
+  fn test_conditional_errors() {
+    assert_eq!(
+      evaluate("if(1, 2)"),
+      Err(CalcError::ArityMismatch {
+        function: "if".to_string(),
+        expected: 3,
+        found: 2
+      })
+    );
+    assert!(matches!(
+      evaluate("if([1], 2, 3)"),
+      Err(CalcError::Unsupported(_))
+    ));
+  }
+}
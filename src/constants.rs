@@ -0,0 +1,33 @@
+use std::f64::consts::{E, PI, SQRT_2};
+
+/// Constants that can be used by name in any expression. Their names can't be assigned to, so a
+/// variable can never hide one.
+pub(crate) const BUILTIN_CONSTANTS: &[(&str, f64)] = &[
+  ("pi", PI),
+  ("e", E),
+  // The golden ratio, (1 + sqrt(5)) / 2.
+  ("phi", 1.618_033_988_749_895),
+  ("sqrt2", SQRT_2),
+  // The Euler-Mascheroni constant.
+  ("gamma_c", 0.577_215_664_901_532_9),
+];
+
+/// Physical constants in SI units. Their short names would often clash with variables, so they are
+/// only available after [`Evaluator::register_physics_constants`](crate::Evaluator::register_physics_constants).
+pub(crate) const PHYSICS_CONSTANTS: &[(&str, f64)] = &[
+  // Speed of light in vacuum, m/s.
+  ("c", 299_792_458.0),
+  // Avogadro constant, 1/mol.
+  ("N_A", 6.022_140_76e23),
+  // Planck constant, J s.
+  ("h", 6.626_070_15e-34),
+  // Standard acceleration of gravity, m/s^2.
+  ("g", 9.806_65),
+];
+
+pub(crate) fn builtin_constant(name: &str) -> Option<f64> {
+  BUILTIN_CONSTANTS
+    .iter()
+    .find(|(constant, _)| *constant == name)
+    .map(|&(_, value)| value)
+}
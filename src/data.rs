@@ -0,0 +1,187 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::error::CalcError;
+
+/// Read constants from a JSON object of names and numbers, like `{"rate": 0.05, "years": 10}`.
+/// Anything other than a single flat object of numbers is an error, and so is a name that
+/// expressions couldn't refer to.
+pub fn parse_constants(json: &str) -> Result<Vec<(String, f64)>, CalcError> {
+  let mut reader = Reader {
+    chars: json.chars().peekable(),
+    line: 1,
+  };
+  let mut constants = Vec::new();
+  reader.expect('{')?;
+  if reader.peek() == Some('}') {
+    reader.next();
+  } else {
+    loop {
+      let name = reader.string()?;
+      if !is_identifier(&name) {
+        return Err(reader.invalid(&format!("'{}' isn't a valid constant name", name)));
+      }
+      reader.expect(':')?;
+      let value = reader.number(&name)?;
+      constants.push((name, value));
+      match reader.next() {
+        Some(',') => continue,
+        Some('}') => break,
+        _ => return Err(reader.invalid("expected ',' or '}'")),
+      }
+    }
+  }
+  if reader.peek().is_some() {
+    return Err(reader.invalid("expected the end after the object"));
+  }
+  Ok(constants)
+}
+
+/// Whether `name` can be written in an expression to refer to a constant.
+fn is_identifier(name: &str) -> bool {
+  let mut chars = name.chars();
+  chars
+    .next()
+    .is_some_and(|first| first.is_alphabetic() || first == '_')
+    && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Walks through the JSON, counting lines for error messages.
+struct Reader<'a> {
+  chars: Peekable<Chars<'a>>,
+  line: usize,
+}
+
+impl Reader<'_> {
+  fn invalid(&self, reason: &str) -> CalcError {
+    CalcError::InvalidData {
+      line: self.line,
+      reason: reason.to_string(),
+    }
+  }
+
+  /// The next character that isn't whitespace, without consuming it.
+  fn peek(&mut self) -> Option<char> {
+    while let Some(&c) = self.chars.peek() {
+      if !c.is_whitespace() {
+        return Some(c);
+      }
+      if c == '\n' {
+        self.line += 1;
+      }
+      self.chars.next();
+    }
+    None
+  }
+
+  fn next(&mut self) -> Option<char> {
+    self.peek()?;
+    self.chars.next()
+  }
+
+  fn expect(&mut self, expected: char) -> Result<(), CalcError> {
+    match self.next() {
+      Some(c) if c == expected => Ok(()),
+      _ => Err(self.invalid(&format!("expected '{}'", expected))),
+    }
+  }
+
+  fn string(&mut self) -> Result<String, CalcError> {
+    self.expect('"')?;
+    let mut string = String::new();
+    loop {
+      match self.chars.next() {
+        Some('"') => return Ok(string),
+        Some('\\') => {
+          let escaped = match self.chars.next() {
+            Some(c @ ('"' | '\\' | '/')) => c,
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('u') => {
+              let digits: String = self.chars.by_ref().take(4).collect();
+              u32::from_str_radix(&digits, 16)
+                .ok()
+                .filter(|_| digits.len() == 4)
+                .and_then(char::from_u32)
+                .ok_or_else(|| self.invalid("invalid '\\u' escape"))?
+            }
+            _ => return Err(self.invalid("invalid escape in a string")),
+          };
+          string.push(escaped);
+        }
+        Some('\n') | None => return Err(self.invalid("unterminated string")),
+        Some(c) => string.push(c),
+      }
+    }
+  }
+
+  /// The value of constant `name`, which has to be a number.
+  fn number(&mut self, name: &str) -> Result<f64, CalcError> {
+    self.peek();
+    let mut number = String::new();
+    while let Some(&c) = self.chars.peek() {
+      if !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+        break;
+      }
+      number.push(c);
+      self.chars.next();
+    }
+    match number.parse::<f64>() {
+      Ok(value) if value.is_finite() => Ok(value),
+      _ => Err(self.invalid(&format!("the value of '{}' must be a number", name))),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{parse_constants, CalcError, Evaluator};
+
+  #[test]
+  fn test_parse_constants() {
+    let constants =
+      parse_constants("{\n  \"rate\": 0.05,\n  \"years\": 10,\n  \"k_\\u00e9\": -1e3\n}\n");
+    assert_eq!(
+      constants,
+      Ok(vec![
+        ("rate".to_string(), 0.05),
+        ("years".to_string(), 10.0),
+        ("k_é".to_string(), -1000.0)
+      ])
+    );
+    assert_eq!(parse_constants(" {} "), Ok(vec![]));
+
+    let mut evaluator = Evaluator::new();
+    for (name, value) in constants.unwrap() {
+      evaluator.register_const(&name, value);
+    }
+    assert_eq!(evaluator.evaluate("years * rate"), Ok(0.5));
+  }
+
+  #[test]
+  fn test_invalid_constants() {
+    let invalid = |json| match parse_constants(json) {
+      Err(CalcError::InvalidData { line, reason }) => (line, reason),
+      other => panic!("expected an error, got {:?}", other),
+    };
+    assert_eq!(
+      invalid("{\"a\": 1,\n\"b\": \"two\"}"),
+      (2, "the value of 'b' must be a number".to_string())
+    );
+    assert_eq!(
+      invalid("{\"a\": [1]}"),
+      (1, "the value of 'a' must be a number".to_string())
+    );
+    assert_eq!(invalid("[1]").1, "expected '{'");
+    assert_eq!(invalid("{\"a\": 1").1, "expected ',' or '}'");
+    assert_eq!(
+      invalid("{\"a\": 1} x").1,
+      "expected the end after the object"
+    );
+    assert_eq!(invalid("{\"2x\": 1}").1, "'2x' isn't a valid constant name");
+    assert_eq!(invalid("{\"a").1, "unterminated string");
+  }
+}
@@ -0,0 +1,223 @@
+use crate::ast::Expr;
+use crate::error::CalcError;
+use crate::evaluator::Evaluator;
+use crate::value::Value;
+
+/// How deeply calls to defined functions may nest, which only a function calling itself reaches.
+const MAX_CALL_DEPTH: usize = 64;
+
+/// A function defined like `f(x) = x^2`, evaluated with its parameters bound to the arguments of
+/// each call.
+#[derive(Clone, Debug)]
+pub(crate) struct Definition {
+  pub(crate) parameters: Vec<String>,
+  pub(crate) body: Expr,
+}
+
+impl Evaluator {
+  /// Define a function like `abs2(x) = if(x < 0, -x, x)`. Its parameters mean the arguments in the
+  /// body, which can read other variables and call any function, the new one included. Defined
+  /// functions take precedence over all other functions of the same name.
+  pub fn define(&mut self, definition: &str) -> Result<(), CalcError> {
+    self.allow("Defining functions")?;
+    let (name, parameters, body) =
+      split_definition(definition).ok_or(CalcError::InvalidAssignment)?;
+    for (i, parameter) in parameters.iter().enumerate() {
+      self.check_assignable(parameter)?;
+      if parameters[..i].contains(parameter) {
+        return Err(CalcError::Unsupported(format!(
+          "'{}' has two parameters named '{}'",
+          name, parameter
+        )));
+      }
+    }
+    self.check_assignable(name)?;
+    let definition = Definition {
+      parameters: parameters
+        .iter()
+        .map(|parameter| parameter.to_string())
+        .collect(),
+      body: Expr::Number(0.0),
+    };
+    // Known while the body is parsed, so that it can call itself.
+    let previous = self.definitions.insert(name.to_string(), definition);
+    match self.parse(body) {
+      Ok(body) => {
+        self.definitions.get_mut(name).unwrap().body = body;
+        Ok(())
+      }
+      Err(error) => {
+        match previous {
+          Some(previous) => self.definitions.insert(name.to_string(), previous),
+          None => self.definitions.remove(name),
+        };
+        Err(error)
+      }
+    }
+  }
+
+  /// Evaluate a call to a function from [`Evaluator::define`].
+  pub(crate) fn call_definition(
+    &mut self,
+    name: &str,
+    arguments: &[Expr],
+  ) -> Result<Value, CalcError> {
+    let definition = self.definitions[name].clone();
+    if arguments.len() != definition.parameters.len() {
+      return Err(CalcError::ArityMismatch {
+        function: name.to_string(),
+        expected: definition.parameters.len(),
+        found: arguments.len(),
+      });
+    }
+    if self.call_depth == MAX_CALL_DEPTH {
+      return Err(CalcError::Unsupported(format!(
+        "'{}' calls itself more than {} times deep",
+        name, MAX_CALL_DEPTH
+      )));
+    }
+    self.check_deadline()?;
+    let mut bindings = Vec::with_capacity(arguments.len());
+    for (parameter, argument) in definition.parameters.iter().zip(arguments) {
+      bindings.push((parameter.as_str(), self.eval(argument)?));
+    }
+    self.call_depth += 1;
+    let result = self.bind_all(&bindings, &definition.body);
+    self.call_depth -= 1;
+    result
+  }
+
+  /// Evaluate `body` with every binding in place, restoring the variables they set aside after.
+  pub(crate) fn bind_all(
+    &mut self,
+    bindings: &[(&str, Value)],
+    body: &Expr,
+  ) -> Result<Value, CalcError> {
+    match bindings {
+      [] => self.eval(body),
+      [(name, value), rest @ ..] => self.scoped(name, |evaluator| {
+        evaluator.bind(name, value.clone());
+        evaluator.bind_all(rest, body)
+      }),
+    }
+  }
+}
+
+/// Split a definition like `f(x, y) = x*y` into the name, the parameters and the body, or give
+/// `None` if the statement isn't one.
+pub(crate) fn split_definition(statement: &str) -> Option<(&str, Vec<&str>, &str)> {
+  let (head, body) = statement.split_once('=')?;
+  if body.starts_with('=') {
+    return None;
+  }
+  let (name, parameters) = head.trim().strip_suffix(')')?.split_once('(')?;
+  let name = name.trim();
+  let parameters: Vec<&str> = parameters.split(',').map(str::trim).collect();
+  if !is_identifier(name) || !parameters.iter().all(|parameter| is_identifier(parameter)) {
+    return None;
+  }
+  Some((name, parameters, body))
+}
+
+fn is_identifier(name: &str) -> bool {
+  let mut chars = name.chars();
+  chars
+    .next()
+    .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+    && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{CalcError, Evaluator, Value};
+
+  #[test]
+  fn test_piecewise_function() {
+    let mut evaluator = Evaluator::new();
+    evaluator.define("abs2(x) = if(x < 0, -x, x)").unwrap();
+    assert_eq!(evaluator.evaluate("abs2(-3)"), Ok(3.0));
+    assert_eq!(evaluator.evaluate("abs2(0)"), Ok(0.0));
+    assert_eq!(evaluator.evaluate("abs2(2.5)"), Ok(2.5));
+    evaluator
+      .define("sign2(x) = if(x < 0, -1, if(x == 0, 0, 1))")
+      .unwrap();
+    assert_eq!(evaluator.evaluate("sign2(-7)"), Ok(-1.0));
+    assert_eq!(evaluator.evaluate("sign2(0)"), Ok(0.0));
+    assert_eq!(evaluator.evaluate("sign2(7)"), Ok(1.0));
+  }
+
+  #[test]
+  fn test_defined_functions() {
+    let mut evaluator = Evaluator::new();
+    evaluator.define("f(x, y) = x^2 + y").unwrap();
+    assert_eq!(evaluator.evaluate("f(3, 1) * 2"), Ok(20.0));
+    assert_eq!(evaluator.evaluate("2f(1, 1)"), Ok(4.0));
+    evaluator.evaluate("x = 10").unwrap();
+    assert_eq!(evaluator.evaluate("f(2, x)"), Ok(14.0));
+    assert_eq!(evaluator.variable("x"), Some(Value::Float(10.0)));
+    evaluator.define("sq(x) = x * x").unwrap();
+    assert_eq!(evaluator.evaluate("sq(sq(2))"), Ok(16.0));
+    evaluator.define("sq(x) = x ^ 3").unwrap();
+    assert_eq!(evaluator.evaluate("sq(2)"), Ok(8.0));
+    assert_eq!(evaluator.validate("sq(2) + f(1, 2)"), Ok(()));
+  }
+
+  #[test]
+  fn test_recursive_functions() {
+    let mut evaluator = Evaluator::new();
+    evaluator
+      .define("fact(n) = if(n <= 1, 1, n * fact(n - 1))")
+      .unwrap();
+    assert_eq!(evaluator.evaluate("fact(5)"), Ok(120.0));
+    evaluator.define("loop(n) = loop(n + 1)").unwrap();
+    assert!(matches!(
+      evaluator.evaluate("loop(1)"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert_eq!(evaluator.evaluate("fact(3)"), Ok(6.0));
+  }
+
+  #[test]
+  fn test_definitions_in_statements() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+      evaluator.evaluate_statements("half(x) = x / 2; half(9)"),
+      Ok(vec![Value::Float(4.5)])
+    );
+  }
+
+  #[test]
+  fn test_invalid_definitions() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+      evaluator.define("f(x) + 1"),
+      Err(CalcError::InvalidAssignment)
+    );
+    assert_eq!(
+      evaluator.define("f(x) == 1"),
+      Err(CalcError::InvalidAssignment)
+    );
+    assert_eq!(
+      evaluator.define("pi(x) = x"),
+      Err(CalcError::ConstantAssignment("pi".to_string()))
+    );
+    assert!(matches!(
+      evaluator.define("f(x, x) = x"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert!(evaluator.define("f(x) = (x").is_err());
+    assert_eq!(
+      evaluator.evaluate("f(1)"),
+      Err(CalcError::UnknownFunction("f".to_string()))
+    );
+    evaluator.define("f(x) = x").unwrap();
+    assert_eq!(
+      evaluator.evaluate("f(1, 2)"),
+      Err(CalcError::ArityMismatch {
+        function: "f".to_string(),
+        expected: 1,
+        found: 2
+      })
+    );
+  }
+}
@@ -0,0 +1,393 @@
+use std::fmt;
+use std::ops::Range;
+use std::time::Duration;
+
+/// Which operand of an operator is meant.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum OperandSide {
+  Left,
+  Right,
+}
+
+impl fmt::Display for OperandSide {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      OperandSide::Left => write!(f, "left"),
+      OperandSide::Right => write!(f, "right"),
+    }
+  }
+}
+
+/// Everything that can go wrong while turning an expression into a result.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CalcError {
+  NoExpression,
+  InputTooLong {
+    length: usize,
+    limit: usize,
+  },
+  InvalidNumber {
+    number: String,
+    /// Where the problem is, counting characters from 1.
+    column: usize,
+    /// The byte offsets of the whole number.
+    span: Range<usize>,
+  },
+  InvalidGrouping(String),
+  /// Text given to `parse` that isn't an integer in the base.
+  InvalidDigits {
+    digits: String,
+    base: u32,
+  },
+  /// A `/*` with no closing `*/` after it. The span runs from it to the end.
+  UnterminatedComment {
+    column: usize,
+    span: Range<usize>,
+  },
+  /// A `"` with no closing `"` after it. The span runs from it to the end.
+  UnterminatedText {
+    column: usize,
+    span: Range<usize>,
+  },
+  MismatchedParentheses,
+  InvalidExpression,
+  MissingOperand {
+    operator: String,
+    side: OperandSide,
+  },
+  DivisionByZero,
+  UnknownVariable(String),
+  UnknownFunction(String),
+  /// A function written like a variable before its argument, as in `sqrt 16`.
+  MissingFunctionParens {
+    function: String,
+    argument: String,
+  },
+  DomainError {
+    function: String,
+    argument: f64,
+  },
+  NonIntegerOperand {
+    function: String,
+    value: f64,
+  },
+  /// A function taking a fixed number of arguments, or a range of them, given another number.
+  ArityMismatch {
+    function: String,
+    expected: usize,
+    found: usize,
+  },
+  /// A function taking either of two numbers of arguments, like `sum`, given another number.
+  ArityChoiceMismatch {
+    function: String,
+    expected: (usize, usize),
+    found: usize,
+  },
+  /// A function taking any number of arguments from a minimum, like `mean`, given fewer.
+  NotEnoughArguments {
+    function: String,
+    minimum: usize,
+    found: usize,
+  },
+  MisplacedSeparator,
+  InvalidAssignment,
+  ConstantAssignment(String),
+  Unsupported(String),
+  NoUniqueSolution,
+  InvalidRange(String),
+  /// Evaluation ran past [`Config::timeout`](crate::Config::timeout).
+  Timeout(Duration),
+  /// An operator that needs operands in the same units, like `+`, was given different ones.
+  DimensionMismatch {
+    operator: String,
+    left: String,
+    right: String,
+  },
+  /// `convert` was asked for a rate that hasn't been set.
+  UnknownRate(String),
+  InvalidRates {
+    line: usize,
+    reason: String,
+  },
+  /// A constants file for [`parse_constants`](crate::parse_constants) isn't a JSON object of
+  /// numbers.
+  InvalidData {
+    line: usize,
+    reason: String,
+  },
+  /// An operation gave NaN from numbers, with [`NanPolicy::Error`](crate::NanPolicy::Error).
+  NotANumber(String),
+  /// Two integers that don't divide evenly, with
+  /// [`IntegerDivision::Strict`](crate::IntegerDivision::Strict).
+  InexactDivision(String),
+  /// An index into a list that is negative or not less than its length.
+  IndexOutOfBounds {
+    index: i64,
+    length: usize,
+  },
+  /// `modinv(a, modulus)` where `a` and the modulus have a common factor.
+  NoModularInverse {
+    a: i64,
+    modulus: i64,
+  },
+  /// Evaluation applied more operators than
+  /// [`Config::max_operations`](crate::Config::max_operations).
+  OperationLimitExceeded(usize),
+  /// A result whose absolute value is beyond
+  /// [`Config::max_magnitude`](crate::Config::max_magnitude).
+  MagnitudeExceeded {
+    value: f64,
+    limit: f64,
+  },
+  /// A feature [`Config::sandbox`](crate::Config::sandbox) turns off, like `sum` over a range.
+  FeatureDisabled(String),
+  /// An indeterminate form like `0 ^ 0`, with
+  /// [`ZeroPower::Error`](crate::ZeroPower::Error).
+  Indeterminate(String),
+  /// No expression was saved under this name with [`Evaluator::save`](crate::Evaluator::save).
+  UnknownSaved(String),
+}
+
+impl CalcError {
+  /// Whether the expression is malformed, as opposed to failing while it is evaluated.
+  pub fn is_syntax_error(&self) -> bool {
+    matches!(
+      self,
+      CalcError::NoExpression
+        | CalcError::InputTooLong { .. }
+        | CalcError::InvalidNumber { .. }
+        | CalcError::InvalidGrouping(_)
+        | CalcError::UnterminatedText { .. }
+        | CalcError::UnterminatedComment { .. }
+        | CalcError::MismatchedParentheses
+        | CalcError::InvalidExpression
+        | CalcError::MissingOperand { .. }
+        | CalcError::MisplacedSeparator
+        | CalcError::InvalidAssignment
+        | CalcError::MissingFunctionParens { .. }
+    )
+  }
+
+  /// A stable code for the kind of error, like `E_DIV_ZERO`, for callers to tell errors apart
+  /// without matching on their messages. Every syntax error is `E_PARSE`.
+  pub fn code(&self) -> &'static str {
+    match self {
+      CalcError::NoExpression
+      | CalcError::InputTooLong { .. }
+      | CalcError::InvalidNumber { .. }
+      | CalcError::InvalidGrouping(_)
+      | CalcError::UnterminatedText { .. }
+      | CalcError::UnterminatedComment { .. }
+      | CalcError::MismatchedParentheses
+      | CalcError::InvalidExpression
+      | CalcError::MissingOperand { .. }
+      | CalcError::MisplacedSeparator
+      | CalcError::InvalidAssignment
+      | CalcError::MissingFunctionParens { .. } => "E_PARSE",
+      CalcError::InvalidDigits { .. } => "E_INVALID_DIGITS",
+      CalcError::DivisionByZero => "E_DIV_ZERO",
+      CalcError::UnknownVariable(_) => "E_UNKNOWN_VARIABLE",
+      CalcError::UnknownFunction(_) => "E_UNKNOWN_FUNCTION",
+      CalcError::DomainError { .. } => "E_DOMAIN",
+      CalcError::NonIntegerOperand { .. } => "E_NON_INTEGER",
+      CalcError::ArityMismatch { .. } | CalcError::ArityChoiceMismatch { .. } => "E_ARITY",
+      CalcError::NotEnoughArguments { .. } => "E_NOT_ENOUGH_ARGUMENTS",
+      CalcError::ConstantAssignment(_) => "E_CONSTANT_ASSIGNMENT",
+      CalcError::Unsupported(_) => "E_UNSUPPORTED",
+      CalcError::NoUniqueSolution => "E_NO_UNIQUE_SOLUTION",
+      CalcError::InvalidRange(_) => "E_INVALID_RANGE",
+      CalcError::Timeout(_) => "E_TIMEOUT",
+      CalcError::DimensionMismatch { .. } => "E_DIMENSION_MISMATCH",
+      CalcError::UnknownRate(_) => "E_UNKNOWN_RATE",
+      CalcError::InvalidRates { .. } => "E_INVALID_RATES",
+      CalcError::InvalidData { .. } => "E_INVALID_DATA",
+      CalcError::NotANumber(_) => "E_NAN",
+      CalcError::InexactDivision(_) => "E_INEXACT_DIVISION",
+      CalcError::IndexOutOfBounds { .. } => "E_INDEX_OUT_OF_BOUNDS",
+      CalcError::NoModularInverse { .. } => "E_NO_MODULAR_INVERSE",
+      CalcError::OperationLimitExceeded(_) => "E_OPERATION_LIMIT",
+      CalcError::MagnitudeExceeded { .. } => "E_MAGNITUDE_EXCEEDED",
+      CalcError::FeatureDisabled(_) => "E_FEATURE_DISABLED",
+      CalcError::Indeterminate(_) => "E_INDETERMINATE",
+      CalcError::UnknownSaved(_) => "E_UNKNOWN_SAVED",
+    }
+  }
+
+  /// The 1-based column the error points at, for errors that know one.
+  pub fn column(&self) -> Option<usize> {
+    match self {
+      CalcError::InvalidNumber { column, .. }
+      | CalcError::UnterminatedText { column, .. }
+      | CalcError::UnterminatedComment { column, .. } => Some(*column),
+      _ => None,
+    }
+  }
+
+  /// The byte offsets of the text the error is about, for editors to underline, for errors that
+  /// know them. Like [`CalcError::column`], they point into the expression as it was
+  /// tokenised, after comments were blanked out and aliases expanded.
+  pub fn span(&self) -> Option<Range<usize>> {
+    match self {
+      CalcError::InvalidNumber { span, .. }
+      | CalcError::UnterminatedText { span, .. }
+      | CalcError::UnterminatedComment { span, .. } => Some(span.clone()),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for CalcError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      CalcError::NoExpression => write!(f, "No expression provided"),
+      CalcError::InputTooLong { length, limit } => write!(
+        f,
+        "The expression is {} characters long, more than the limit of {}",
+        length, limit
+      ),
+      CalcError::InvalidNumber { number, column, .. } => {
+        write!(f, "Invalid number '{}' at column {}", number, column)
+      }
+      CalcError::InvalidGrouping(number) => write!(
+        f,
+        "Invalid digit grouping in '{}', expected groups of three digits",
+        number
+      ),
+      CalcError::InvalidDigits { digits, base } => {
+        write!(f, "'{}' is not an integer in base {}", digits, base)
+      }
+      CalcError::UnterminatedComment { column, .. } => {
+        write!(f, "Unterminated comment starting at column {}", column)
+      }
+      CalcError::UnterminatedText { column, .. } => {
+        write!(f, "Unterminated text starting at column {}", column)
+      }
+      CalcError::MismatchedParentheses => write!(f, "Mismatched parentheses"),
+      CalcError::InvalidExpression => write!(f, "Invalid expression"),
+      CalcError::MissingOperand { operator, side } => {
+        write!(f, "Operator '{}' is missing its {} operand", operator, side)
+      }
+      CalcError::DivisionByZero => write!(f, "Division by zero"),
+      CalcError::UnknownVariable(name) => write!(f, "Unknown variable '{}'", name),
+      CalcError::UnknownFunction(name) => write!(f, "Unknown function '{}'", name),
+      CalcError::MissingFunctionParens { function, argument } => write!(
+        f,
+        "Function '{}' must be called with parentheses, e.g. {}({})",
+        function, function, argument
+      ),
+      CalcError::DomainError { function, argument } => {
+        write!(f, "'{}' is not defined for {}", function, argument)
+      }
+      CalcError::NonIntegerOperand { function, value } => {
+        write!(f, "'{}' needs an integer but was given {}", function, value)
+      }
+      CalcError::ArityMismatch {
+        function,
+        expected,
+        found,
+      } => write!(
+        f,
+        "'{}' takes {} argument(s) but was given {}",
+        function, expected, found
+      ),
+      CalcError::ArityChoiceMismatch {
+        function,
+        expected: (fewer, more),
+        found,
+      } => write!(
+        f,
+        "'{}' takes {} or {} argument(s) but was given {}",
+        function, fewer, more, found
+      ),
+      CalcError::NotEnoughArguments {
+        function,
+        minimum,
+        found,
+      } => write!(
+        f,
+        "'{}' takes at least {} argument(s) but was given {}",
+        function, minimum, found
+      ),
+      CalcError::MisplacedSeparator => write!(f, "',' can only separate function arguments"),
+      CalcError::InvalidAssignment => write!(f, "Only variables can be assigned to"),
+      CalcError::ConstantAssignment(name) => write!(f, "Cannot assign to constant '{}'", name),
+      CalcError::Unsupported(reason) => write!(f, "Unsupported: {}", reason),
+      CalcError::NoUniqueSolution => write!(f, "The equation has no unique solution"),
+      CalcError::InvalidRange(reason) => write!(f, "Invalid range: {}", reason),
+      CalcError::Timeout(limit) => write!(f, "Evaluation took longer than {:?}", limit),
+      CalcError::DimensionMismatch {
+        operator,
+        left,
+        right,
+      } => write!(
+        f,
+        "Can't apply '{}' to quantities in {} and {}",
+        operator, left, right
+      ),
+      CalcError::UnknownRate(pair) => write!(f, "No conversion rate for '{}'", pair),
+      CalcError::InvalidRates { line, reason } => {
+        write!(f, "Invalid rate table at line {}: {}", line, reason)
+      }
+      CalcError::InvalidData { line, reason } => {
+        write!(f, "Invalid data file at line {}: {}", line, reason)
+      }
+      CalcError::NotANumber(operation) => write!(f, "{} is not a number", operation),
+      CalcError::InexactDivision(division) => write!(f, "{} doesn't divide evenly", division),
+      CalcError::IndexOutOfBounds { index, length } => write!(
+        f,
+        "Index {} is out of bounds for a list of {} items",
+        index, length
+      ),
+      CalcError::NoModularInverse { a, modulus } => {
+        write!(f, "{} has no inverse modulo {}", a, modulus)
+      }
+      CalcError::OperationLimitExceeded(limit) => {
+        write!(f, "Evaluation exceeded the limit of {} operations", limit)
+      }
+      CalcError::MagnitudeExceeded { value, limit } => {
+        write!(f, "{} exceeds the maximum magnitude of {}", value, limit)
+      }
+      CalcError::FeatureDisabled(feature) => write!(f, "{} is disabled in sandbox mode", feature),
+      CalcError::Indeterminate(operation) => write!(f, "{} is indeterminate", operation),
+      CalcError::UnknownSaved(name) => write!(f, "No expression is saved as '{}'", name),
+    }
+  }
+}
+
+impl std::error::Error for CalcError {}
+
+#[cfg(test)]
+mod tests {
+  use crate::{evaluate, CalcError};
+
+  #[test]
+  fn test_syntax_errors() {
+    let error = evaluate("1.2.3 + 1").unwrap_err();
+    assert!(error.is_syntax_error());
+    assert_eq!(error.column(), Some(4));
+    assert_eq!(error.span(), Some(0..5));
+    assert!(evaluate("(1").unwrap_err().is_syntax_error());
+    assert_eq!(evaluate("(1").unwrap_err().column(), None);
+    assert_eq!(evaluate("(1").unwrap_err().span(), None);
+    assert!(!evaluate("1/0 + x").unwrap_err().is_syntax_error());
+    assert!(!CalcError::DivisionByZero.is_syntax_error());
+  }
+
+  #[test]
+  fn test_error_codes() {
+    let code = |expression| evaluate(expression).unwrap_err().code();
+    assert_eq!(code("7 // 0"), "E_DIV_ZERO");
+    assert_eq!(code("(1"), "E_PARSE");
+    assert_eq!(code("2 +"), "E_PARSE");
+    assert_eq!(code("1.2.3"), "E_PARSE");
+    assert_eq!(code("x + 1"), "E_UNKNOWN_VARIABLE");
+    assert_eq!(code("nope(1)"), "E_UNKNOWN_FUNCTION");
+    assert_eq!(code("sqrt(1, 2)"), "E_ARITY");
+    assert_eq!(code("factorial(-1)"), "E_DOMAIN");
+    assert_eq!(code("[1, 2][5]"), "E_INDEX_OUT_OF_BOUNDS");
+    for error in [CalcError::NoExpression, CalcError::MisplacedSeparator] {
+      assert!(error.is_syntax_error());
+      assert_eq!(error.code(), "E_PARSE");
+    }
+  }
+}
@@ -0,0 +1,2657 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::ast::Expr;
+use crate::calculus::{calculus_arity, CALCULUS};
+use crate::conditional::{CONDITIONAL, CONDITIONAL_ARITY};
+use crate::constants::{builtin_constant, BUILTIN_CONSTANTS, PHYSICS_CONSTANTS};
+use crate::define::{split_definition, Definition};
+use crate::error::CalcError;
+use crate::format::{round, Rounding};
+use crate::functions::{call_builtin, find_builtin, Arity, BUILTINS};
+use crate::parser::shunting_yard;
+use crate::rational::Rational;
+use crate::series::{series_arity, SERIES};
+use crate::table::{RANGE_EXTREMA, RANGE_EXTREMUM_ARITY};
+use crate::template::{TEMPLATE, TEMPLATE_MINIMUM_ARITY};
+use crate::text::{TEXT_FUNCTIONS, TEXT_FUNCTION_ARITY};
+use crate::token::{
+  expand_aliases, nesting_depth, split_statements, strip_comments, tokenise, tokens_to_json,
+  Operator, Syntax, Token,
+};
+use crate::units::Dimension;
+use crate::value::Value;
+
+/// How many recent results are kept for `ans`, `ans1`, `ans2` and so on.
+pub const HISTORY_LENGTH: usize = 10;
+
+/// Which kind of arithmetic the evaluator performs.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum NumberMode {
+  /// Plain floating point arithmetic.
+  #[default]
+  Float,
+  /// Exact fractions wherever the operation allows it, falling back to floats otherwise.
+  Fraction,
+}
+
+impl fmt::Display for NumberMode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      NumberMode::Float => write!(f, "float"),
+      NumberMode::Fraction => write!(f, "fraction"),
+    }
+  }
+}
+
+/// How angles given to and returned by trigonometric functions are measured.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum AngleUnit {
+  #[default]
+  Radians,
+  Degrees,
+  /// A quarter of a circle is 100 gradians.
+  Gradians,
+}
+
+impl AngleUnit {
+  pub(crate) fn to_radians(self, angle: f64) -> f64 {
+    match self {
+      AngleUnit::Radians => angle,
+      AngleUnit::Degrees => angle.to_radians(),
+      AngleUnit::Gradians => angle * std::f64::consts::PI / 200.0,
+    }
+  }
+
+  /// The size of a full circle in the unit.
+  pub(crate) fn full_turn(self) -> f64 {
+    match self {
+      AngleUnit::Radians => std::f64::consts::TAU,
+      AngleUnit::Degrees => 360.0,
+      AngleUnit::Gradians => 400.0,
+    }
+  }
+
+  pub(crate) fn radians_to_unit(self, angle: f64) -> f64 {
+    match self {
+      AngleUnit::Radians => angle,
+      AngleUnit::Degrees => angle.to_degrees(),
+      AngleUnit::Gradians => angle * 200.0 / std::f64::consts::PI,
+    }
+  }
+}
+
+/// The short name [`AngleUnit::from_str`] accepts.
+impl fmt::Display for AngleUnit {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      AngleUnit::Radians => write!(f, "rad"),
+      AngleUnit::Degrees => write!(f, "deg"),
+      AngleUnit::Gradians => write!(f, "grad"),
+    }
+  }
+}
+
+impl FromStr for AngleUnit {
+  type Err = String;
+
+  fn from_str(str: &str) -> Result<AngleUnit, String> {
+    match str {
+      "rad" | "radians" => Ok(AngleUnit::Radians),
+      "deg" | "degrees" => Ok(AngleUnit::Degrees),
+      "grad" | "gradians" => Ok(AngleUnit::Gradians),
+      _ => Err(format!(
+        "Unknown angle unit '{}', expected rad, deg or grad",
+        str
+      )),
+    }
+  }
+}
+
+/// What integer-only functions and operators do with operands that have a fractional part.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum IntegerPolicy {
+  /// Reject them with [`CalcError::NonIntegerOperand`].
+  #[default]
+  Strict,
+  /// Truncate them toward zero.
+  Truncate,
+}
+
+impl fmt::Display for IntegerPolicy {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      IntegerPolicy::Strict => write!(f, "strict"),
+      IntegerPolicy::Truncate => write!(f, "truncate"),
+    }
+  }
+}
+
+impl FromStr for IntegerPolicy {
+  type Err = String;
+
+  fn from_str(str: &str) -> Result<IntegerPolicy, String> {
+    match str {
+      "strict" => Ok(IntegerPolicy::Strict),
+      "truncate" => Ok(IntegerPolicy::Truncate),
+      _ => Err(format!(
+        "Unknown integer policy '{}', expected strict or truncate",
+        str
+      )),
+    }
+  }
+}
+
+/// What happens when an operation produces NaN from operands that are numbers, like `0 * inf`.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum NanPolicy {
+  /// Carry on with NaN, which then makes the result NaN too.
+  #[default]
+  Propagate,
+  /// Fail right there with [`CalcError::NotANumber`], naming the operation.
+  Error,
+}
+
+impl fmt::Display for NanPolicy {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      NanPolicy::Propagate => write!(f, "propagate"),
+      NanPolicy::Error => write!(f, "error"),
+    }
+  }
+}
+
+impl FromStr for NanPolicy {
+  type Err = String;
+
+  fn from_str(str: &str) -> Result<NanPolicy, String> {
+    match str {
+      "propagate" => Ok(NanPolicy::Propagate),
+      "error" => Ok(NanPolicy::Error),
+      _ => Err(format!(
+        "Unknown NaN policy '{}', expected propagate or error",
+        str
+      )),
+    }
+  }
+}
+
+/// What `/` does with two integers that don't divide evenly, like `7 / 2`.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum IntegerDivision {
+  /// Give the exact quotient, `3.5`, or `7/2` in [`NumberMode::Fraction`].
+  #[default]
+  Promote,
+  /// Fail with [`CalcError::InexactDivision`].
+  Strict,
+  /// Round the quotient down like `//`, to `3`.
+  Truncate,
+}
+
+impl fmt::Display for IntegerDivision {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      IntegerDivision::Promote => write!(f, "promote"),
+      IntegerDivision::Strict => write!(f, "strict-int"),
+      IntegerDivision::Truncate => write!(f, "truncate-int"),
+    }
+  }
+}
+
+impl FromStr for IntegerDivision {
+  type Err = String;
+
+  fn from_str(str: &str) -> Result<IntegerDivision, String> {
+    match str {
+      "promote" => Ok(IntegerDivision::Promote),
+      "strict-int" => Ok(IntegerDivision::Strict),
+      "truncate-int" => Ok(IntegerDivision::Truncate),
+      _ => Err(format!(
+        "Unknown integer division '{}', expected promote, strict-int or truncate-int",
+        str
+      )),
+    }
+  }
+}
+
+/// What arithmetic does with `true` and `false`, the results of comparisons, like
+/// `(3 > 2) + (1 > 5)`.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum BooleanArithmetic {
+  /// Count them as `1` and `0`, so the example is `1`.
+  #[default]
+  Coerce,
+  /// Fail with [`CalcError::Unsupported`], so a comparison can't be used as a number by mistake.
+  /// Comparing booleans with each other is still allowed.
+  Strict,
+}
+
+impl fmt::Display for BooleanArithmetic {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      BooleanArithmetic::Coerce => write!(f, "coerce"),
+      BooleanArithmetic::Strict => write!(f, "strict-bool"),
+    }
+  }
+}
+
+impl FromStr for BooleanArithmetic {
+  type Err = String;
+
+  fn from_str(str: &str) -> Result<BooleanArithmetic, String> {
+    match str {
+      "coerce" => Ok(BooleanArithmetic::Coerce),
+      "strict-bool" => Ok(BooleanArithmetic::Strict),
+      _ => Err(format!(
+        "Unknown boolean arithmetic '{}', expected coerce or strict-bool",
+        str
+      )),
+    }
+  }
+}
+
+/// What `0^0` is.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum ZeroPower {
+  /// `1`, the convention of most languages and of counting, as there is one empty product.
+  #[default]
+  One,
+  /// Fail with [`CalcError::Indeterminate`].
+  Error,
+  /// NaN, the way an indeterminate form is left undefined.
+  Nan,
+}
+
+impl fmt::Display for ZeroPower {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ZeroPower::One => write!(f, "one"),
+      ZeroPower::Error => write!(f, "error"),
+      ZeroPower::Nan => write!(f, "nan"),
+    }
+  }
+}
+
+impl FromStr for ZeroPower {
+  type Err = String;
+
+  fn from_str(str: &str) -> Result<ZeroPower, String> {
+    match str {
+      "one" => Ok(ZeroPower::One),
+      "error" => Ok(ZeroPower::Error),
+      "nan" => Ok(ZeroPower::Nan),
+      _ => Err(format!(
+        "Unknown zero power '{}', expected one, error or nan",
+        str
+      )),
+    }
+  }
+}
+
+/// Fixed-width integer arithmetic to emulate: integral results of operators are wrapped into the
+/// range of a `bits`-bit integer, so `255 + 1` is `0` for unsigned 8-bit integers.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Wrap {
+  bits: u32,
+  signed: bool,
+}
+
+impl Wrap {
+  /// `None` unless `bits` is between 1 and 64.
+  pub fn new(bits: u32, signed: bool) -> Option<Wrap> {
+    (1..=64).contains(&bits).then_some(Wrap { bits, signed })
+  }
+
+  pub fn bits(&self) -> u32 {
+    self.bits
+  }
+
+  pub fn signed(&self) -> bool {
+    self.signed
+  }
+
+  /// Wrap an integer into range, in two's complement when signed.
+  pub fn apply(&self, value: i128) -> i128 {
+    let modulus = 1i128 << self.bits;
+    let wrapped = value.rem_euclid(modulus);
+    if self.signed && wrapped >= modulus / 2 {
+      wrapped - modulus
+    } else {
+      wrapped
+    }
+  }
+}
+
+/// The default for [`Config::max_input_length`].
+pub const DEFAULT_MAX_INPUT_LENGTH: usize = 10_000;
+
+/// The longest expression [`Config::sandbox`] allows, whatever [`Config::max_input_length`] says.
+pub const SANDBOX_MAX_INPUT_LENGTH: usize = 1_000;
+
+/// The longest an evaluation may take with [`Config::sandbox`], whatever [`Config::timeout`] says.
+pub const SANDBOX_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The default for [`Config::epsilon`], enough to absorb the rounding error of a few operations.
+pub const DEFAULT_EPSILON: f64 = 1e-12;
+
+/// Settings that change how expressions are evaluated.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Config {
+  pub mode: NumberMode,
+  /// Let assignments change constants registered with [`Evaluator::register_const`].
+  /// Built-in constants like `pi` can never be reassigned.
+  pub allow_constant_assignment: bool,
+  /// The longest expression, in characters, that will be tokenised at all.
+  pub max_input_length: usize,
+  /// Read `,` between digits as a thousands separator that has to split the integer part of a
+  /// number into groups of three, like `1,234,567`. Elsewhere `,` still separates arguments.
+  pub digit_grouping: bool,
+  /// Close parentheses and brackets left open at the end of an expression instead of failing.
+  pub auto_close: bool,
+  /// The unit `sin`, `cos` and `tan` take their argument in.
+  pub angle_input_unit: AngleUnit,
+  /// The unit `asin`, `acos` and `atan` return their result in.
+  pub angle_output_unit: AngleUnit,
+  pub integer_policy: IntegerPolicy,
+  pub nan_policy: NanPolicy,
+  pub integer_division: IntegerDivision,
+  pub boolean_arithmetic: BooleanArithmetic,
+  pub zero_power: ZeroPower,
+  /// Fail with [`CalcError::DivisionByZero`] for zero to a negative power, like `0^-1`, instead of
+  /// giving infinity.
+  pub strict_zero_power: bool,
+  pub wrap: Option<Wrap>,
+  /// How far apart two inexact numbers may be for `==` to consider them equal, relative to the
+  /// larger of them once that is above 1. Exact numbers, and everything when this is `0`, compare
+  /// exactly.
+  pub epsilon: f64,
+  /// How long a single evaluation may take before it fails with [`CalcError::Timeout`]. Only loops
+  /// like `sum` and `iterate` check it, as everything else finishes in time proportional to the
+  /// length of the expression.
+  pub timeout: Option<Duration>,
+  /// How many operators a single evaluation may apply before it fails with
+  /// [`CalcError::OperationLimitExceeded`]. Loops count the operators of every round, so this
+  /// bounds them by the work they do rather than the time it takes.
+  pub max_operations: Option<usize>,
+  /// The largest absolute value any operator or function may give before the evaluation fails
+  /// with [`CalcError::MagnitudeExceeded`], which stops a runaway like a power tower early
+  /// instead of carrying an infinity to the end.
+  pub max_magnitude: Option<f64>,
+  /// Read `m`, `s` and `kg` as units, so `3 m / 2 s` is `1.5 m/s` and `1 m + 1 s` fails.
+  pub units: bool,
+  /// Round the result of every operator and function to this many decimals, half away from zero,
+  /// the way a calculation by hand rounds each step.
+  pub round_steps: Option<u32>,
+  /// Refuse what untrusted input could make expensive, with [`CalcError::FeatureDisabled`]: loops
+  /// like `sum` over a range and `iterate`, and defining functions, which can call themselves.
+  /// Also caps the input length and evaluation time at [`SANDBOX_MAX_INPUT_LENGTH`] and
+  /// [`SANDBOX_TIMEOUT`].
+  pub sandbox: bool,
+}
+
+impl Default for Config {
+  fn default() -> Config {
+    Config {
+      mode: NumberMode::default(),
+      allow_constant_assignment: false,
+      max_input_length: DEFAULT_MAX_INPUT_LENGTH,
+      digit_grouping: false,
+      auto_close: false,
+      angle_input_unit: AngleUnit::default(),
+      angle_output_unit: AngleUnit::default(),
+      integer_policy: IntegerPolicy::default(),
+      nan_policy: NanPolicy::default(),
+      integer_division: IntegerDivision::default(),
+      boolean_arithmetic: BooleanArithmetic::default(),
+      zero_power: ZeroPower::default(),
+      strict_zero_power: false,
+      wrap: None,
+      epsilon: DEFAULT_EPSILON,
+      timeout: None,
+      max_operations: None,
+      max_magnitude: None,
+      units: false,
+      round_steps: None,
+      sandbox: false,
+    }
+  }
+}
+
+/// Like `i8` for signed and `u8` for unsigned 8-bit integers.
+impl fmt::Display for Wrap {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}{}", if self.signed { 'i' } else { 'u' }, self.bits)
+  }
+}
+
+/// One `name = value` line per setting, with `none` for what is turned off.
+impl fmt::Display for Config {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn optional(value: Option<impl fmt::Display>) -> String {
+      value.map_or("none".to_string(), |value| value.to_string())
+    }
+    writeln!(f, "mode = {}", self.mode)?;
+    writeln!(
+      f,
+      "allow_constant_assignment = {}",
+      self.allow_constant_assignment
+    )?;
+    writeln!(f, "max_input_length = {}", self.max_input_length)?;
+    writeln!(f, "digit_grouping = {}", self.digit_grouping)?;
+    writeln!(f, "auto_close = {}", self.auto_close)?;
+    writeln!(f, "angle_input_unit = {}", self.angle_input_unit)?;
+    writeln!(f, "angle_output_unit = {}", self.angle_output_unit)?;
+    writeln!(f, "integer_policy = {}", self.integer_policy)?;
+    writeln!(f, "nan_policy = {}", self.nan_policy)?;
+    writeln!(f, "integer_division = {}", self.integer_division)?;
+    writeln!(f, "boolean_arithmetic = {}", self.boolean_arithmetic)?;
+    writeln!(f, "zero_power = {}", self.zero_power)?;
+    writeln!(f, "strict_zero_power = {}", self.strict_zero_power)?;
+    writeln!(f, "wrap = {}", optional(self.wrap))?;
+    writeln!(f, "epsilon = {}", self.epsilon)?;
+    writeln!(
+      f,
+      "timeout = {}",
+      optional(
+        self
+          .timeout
+          .map(|timeout| format!("{}ms", timeout.as_millis()))
+      )
+    )?;
+    writeln!(f, "max_operations = {}", optional(self.max_operations))?;
+    writeln!(f, "max_magnitude = {}", optional(self.max_magnitude))?;
+    writeln!(f, "units = {}", self.units)?;
+    writeln!(f, "round_steps = {}", optional(self.round_steps))?;
+    write!(f, "sandbox = {}", self.sandbox)
+  }
+}
+
+/// The first operation of an evaluation that produced an infinite or NaN result from finite
+/// operands, such as `1e308 * 10 = inf`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct NonFiniteStep {
+  pub operation: String,
+  pub result: f64,
+}
+
+impl fmt::Display for NonFiniteStep {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} = {}", self.operation, self.result)
+  }
+}
+
+type NativeFunction = dyn Fn(&[f64]) -> f64 + Send + Sync;
+
+/// A function or constant expressions can refer to, as listed by [`Evaluator::known_names`].
+#[derive(Clone, PartialEq, Debug)]
+pub enum KnownName {
+  Function { name: String, arity: Arity },
+  Constant { name: String, value: f64 },
+}
+
+impl fmt::Display for KnownName {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      KnownName::Function { name, arity } => write!(f, "{} ({})", name, arity),
+      KnownName::Constant { name, value } => write!(f, "{} = {}", name, value),
+    }
+  }
+}
+
+/// A function registered through [`Evaluator::register_fn`].
+#[derive(Clone)]
+struct CustomFunction {
+  arity: usize,
+  function: Arc<NativeFunction>,
+}
+
+impl fmt::Debug for CustomFunction {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("CustomFunction")
+      .field("arity", &self.arity)
+      .finish_non_exhaustive()
+  }
+}
+
+/// Evaluates expressions according to its [`Config`], remembering variables between calls.
+#[derive(Clone, Debug, Default)]
+pub struct Evaluator {
+  pub config: Config,
+  variables: HashMap<String, Value>,
+  functions: HashMap<String, CustomFunction>,
+  constants: HashMap<String, f64>,
+  first_non_finite: Option<NonFiniteStep>,
+  /// How many operators the current evaluation has applied.
+  operations: usize,
+  /// The most recent results, newest first.
+  history: VecDeque<Value>,
+  aliases: HashMap<String, String>,
+  /// The running total while adding up results like an adding machine.
+  total: Option<Value>,
+  /// When the current evaluation runs out of time.
+  deadline: Option<Instant>,
+  /// The rates `convert` multiplies by, by pair like `USD_EUR`.
+  pub(crate) rates: HashMap<String, f64>,
+  /// The functions defined with [`Evaluator::define`].
+  pub(crate) definitions: HashMap<String, Definition>,
+  /// The expressions saved with [`Evaluator::save`], by name.
+  pub(crate) saved: HashMap<String, String>,
+  /// How many calls to defined functions are in progress.
+  pub(crate) call_depth: usize,
+  /// What `debug` calls noted, until [`Evaluator::take_annotations`] takes it.
+  pub(crate) annotations: Vec<String>,
+}
+
+impl Evaluator {
+  pub fn new() -> Evaluator {
+    Evaluator::default()
+  }
+
+  pub fn with_config(config: Config) -> Evaluator {
+    Evaluator {
+      config,
+      ..Evaluator::default()
+    }
+  }
+
+  /// Forget all variables, registered functions and constants and past results, keeping only the
+  /// configuration.
+  pub fn reset(&mut self) {
+    *self = Evaluator::with_config(self.config.clone());
+  }
+
+  /// Look up the current value of a variable.
+  pub fn variable(&self, name: &str) -> Option<Value> {
+    self.variables.get(name).cloned()
+  }
+
+  /// Give a variable a value, as an assignment like `x = 5` would.
+  pub fn set_variable(&mut self, name: &str, value: Value) -> Result<(), CalcError> {
+    self.check_assignable(name)?;
+    self.bind(name, value);
+    Ok(())
+  }
+
+  /// The `n`th most recent result, counting from 1, if there is one.
+  pub fn recall(&self, n: usize) -> Option<Value> {
+    self.history.get(n.checked_sub(1)?).cloned()
+  }
+
+  /// Resolve `ans`, the latest result, and `ansN`, the `N`th latest one.
+  pub(crate) fn recall_name(&self, name: &str) -> Option<Value> {
+    let n = name.strip_prefix("ans")?;
+    if n.is_empty() {
+      return self.recall(1);
+    }
+    if n.starts_with('0') {
+      return None;
+    }
+    self.recall(n.parse().ok()?)
+  }
+
+  /// Make a function available to expressions under `name`, taking exactly `arity` arguments.
+  /// Registered functions take precedence over built-in ones of the same name.
+  pub fn register_fn(
+    &mut self,
+    name: &str,
+    arity: usize,
+    function: impl Fn(&[f64]) -> f64 + Send + Sync + 'static,
+  ) {
+    self.functions.insert(
+      name.to_string(),
+      CustomFunction {
+        arity,
+        function: Arc::new(function),
+      },
+    );
+  }
+
+  /// Make a constant available to expressions under `name`. Registered constants take precedence
+  /// over built-in ones of the same name.
+  pub fn register_const(&mut self, name: &str, value: f64) {
+    self.constants.insert(name.to_string(), value);
+  }
+
+  /// Make `name` a shorthand that is replaced by `expansion` before an expression is tokenised,
+  /// so with the alias `k` for `*1000`, `5k` is `5*1000`. Only whole identifiers are replaced,
+  /// so `name` has to be one for the alias to ever apply.
+  pub fn define_alias(&mut self, name: &str, expansion: &str) {
+    self.aliases.insert(name.to_string(), expansion.to_string());
+  }
+
+  /// Register the speed of light `c`, the Avogadro constant `N_A`, the Planck constant `h` and
+  /// standard gravity `g`, all in SI units.
+  pub fn register_physics_constants(&mut self) {
+    for &(name, value) in PHYSICS_CONSTANTS {
+      self.register_const(name, value);
+    }
+  }
+
+  /// Look up a registered or built-in constant.
+  pub fn constant(&self, name: &str) -> Option<f64> {
+    self
+      .constants
+      .get(name)
+      .copied()
+      .or_else(|| builtin_constant(name))
+  }
+
+  /// Every function and constant expressions can use, built-in or registered, sorted by name
+  /// with functions first. Registered names shadowing a built-in are listed once.
+  pub fn known_names(&self) -> Vec<KnownName> {
+    let mut functions: Vec<(String, Arity)> = BUILTINS
+      .iter()
+      .map(|builtin| (builtin.name, builtin.arity))
+      .chain(SERIES.iter().map(|&name| (name, series_arity(name))))
+      .chain(
+        TEXT_FUNCTIONS
+          .iter()
+          .map(|&name| (name, Arity::Exactly(TEXT_FUNCTION_ARITY))),
+      )
+      .chain(
+        RANGE_EXTREMA
+          .iter()
+          .map(|&name| (name, Arity::Exactly(RANGE_EXTREMUM_ARITY))),
+      )
+      .chain(CALCULUS.iter().copied())
+      .chain([
+        (CONDITIONAL, Arity::Exactly(CONDITIONAL_ARITY)),
+        (TEMPLATE, Arity::AtLeast(TEMPLATE_MINIMUM_ARITY)),
+      ])
+      .filter(|(name, _)| !self.is_custom(name))
+      .map(|(name, arity)| (name.to_string(), arity))
+      .chain(
+        self
+          .functions
+          .iter()
+          .filter(|(name, _)| !self.definitions.contains_key(*name))
+          .map(|(name, custom)| (name.clone(), Arity::Exactly(custom.arity))),
+      )
+      .chain(
+        self
+          .definitions
+          .iter()
+          .map(|(name, definition)| (name.clone(), Arity::Exactly(definition.parameters.len()))),
+      )
+      .collect();
+    functions.sort();
+
+    let mut constants: Vec<(String, f64)> = BUILTIN_CONSTANTS
+      .iter()
+      .filter(|(name, _)| !self.constants.contains_key(*name))
+      .map(|&(name, value)| (name.to_string(), value))
+      .chain(
+        self
+          .constants
+          .iter()
+          .map(|(name, &value)| (name.clone(), value)),
+      )
+      .collect();
+    constants.sort_by(|a, b| a.0.cmp(&b.0));
+
+    functions
+      .into_iter()
+      .map(|(name, arity)| KnownName::Function { name, arity })
+      .chain(
+        constants
+          .into_iter()
+          .map(|(name, value)| KnownName::Constant { name, value }),
+      )
+      .collect()
+  }
+
+  /// The operation that first stopped being finite during the last evaluation, if any did.
+  pub fn first_non_finite(&self) -> Option<&NonFiniteStep> {
+    self.first_non_finite.as_ref()
+  }
+
+  /// Evaluate an expression to a float.
+  pub fn evaluate(&mut self, expression: &str) -> Result<f64, CalcError> {
+    number_result(self.evaluate_typed(expression)?)
+  }
+
+  /// Evaluate an expression to a float, also returning how many operators were applied. Series
+  /// count the operators of every round.
+  pub fn evaluate_with_stats(&mut self, expression: &str) -> Result<(f64, usize), CalcError> {
+    let result = self.evaluate(expression)?;
+    Ok((result, self.operations))
+  }
+
+  /// Evaluate an expression, reporting what kind of number the result is.
+  pub fn evaluate_typed(&mut self, expression: &str) -> Result<Value, CalcError> {
+    let expr = self.parse(expression)?;
+    self.evaluate_expr(&expr)
+  }
+
+  /// Evaluate tokens already in reverse polish notation, as returned by [`crate::to_rpn`].
+  pub fn eval_rpn(&mut self, rpn: Vec<Token>) -> Result<f64, CalcError> {
+    let expr = Expr::from_rpn(rpn)?;
+    number_result(self.evaluate_expr(&expr)?)
+  }
+
+  /// Evaluate a line that may continue from the previous result: one that starts with a binary
+  /// operator, like `*2`, applies it to `ans`. A leading `-` still negates, so `-5` is just `-5`.
+  pub fn evaluate_continued(&mut self, expression: &str) -> Result<Value, CalcError> {
+    let mut tokens = self.tokenise(expression)?;
+    let continues =
+      matches!(tokens.first(), Some(Token::Operator(operator)) if operator.arity() == 2);
+    if continues && !self.history.is_empty() {
+      tokens.insert(0, Token::Variable("ans".to_string()));
+    }
+    let expr = self.parse_tokens(tokens)?;
+    self.evaluate_expr(&expr)
+  }
+
+  /// Evaluate `;` separated statements in order, like `x = 5; x*2`, each continuing from the
+  /// previous result as in [`Evaluator::evaluate_continued`]. A statement followed by `;` is
+  /// still evaluated but left out of the returned results, so that input gives just `[10]`, and so
+  /// is a definition like `f(x) = x^2`, which is passed to [`Evaluator::define`].
+  /// Evaluation stops at the first error, keeping the effects of the statements before it.
+  pub fn evaluate_statements(&mut self, input: &str) -> Result<Vec<Value>, CalcError> {
+    let segments = split_statements(input);
+    let mut statements = segments
+      .iter()
+      .enumerate()
+      .map(|(i, segment)| (segment.trim(), i + 1 < segments.len()))
+      .filter(|(statement, _)| !statement.is_empty())
+      .peekable();
+    if statements.peek().is_none() {
+      return Err(CalcError::NoExpression);
+    }
+    let mut results = Vec::new();
+    for (statement, suppressed) in statements {
+      if split_definition(statement).is_some() {
+        self.define(statement)?;
+        continue;
+      }
+      let result = self.evaluate_continued(statement)?;
+      if !suppressed {
+        results.push(result);
+      }
+    }
+    Ok(results)
+  }
+
+  /// Evaluate a parsed expression, remembering its result.
+  pub(crate) fn evaluate_expr(&mut self, expr: &Expr) -> Result<Value, CalcError> {
+    self.first_non_finite = None;
+    self.operations = 0;
+    self.start_clock();
+    let result = self.eval(expr)?.classify();
+    self.history.truncate(HISTORY_LENGTH - 1);
+    self.history.push_front(result.clone());
+    Ok(result)
+  }
+
+  /// Start a running total at zero, replacing any running one.
+  pub fn start_total(&mut self) {
+    self.total = Some(self.number(0.0).classify());
+  }
+
+  /// The running total, if one has been started.
+  pub fn total(&self) -> Option<&Value> {
+    self.total.as_ref()
+  }
+
+  /// Stop keeping a running total, returning its final value.
+  pub fn end_total(&mut self) -> Option<Value> {
+    self.total.take()
+  }
+
+  /// Evaluate an expression and add its result to the running total, starting one if needed.
+  /// Returns the new total. On an error the total doesn't change.
+  pub fn add_to_total(&mut self, expression: &str) -> Result<Value, CalcError> {
+    let result = self.evaluate_typed(expression)?;
+    let total = self.total.clone().unwrap_or_else(|| self.number(0.0));
+    let total = self.apply(Operator::Add, total, result)?.classify();
+    self.total = Some(total.clone());
+    Ok(total)
+  }
+
+  /// The tokens of an expression as a JSON array of `{"type": ..., "value": ...}` objects, for
+  /// tools that want to highlight expressions the same way they are parsed.
+  pub fn tokens_json(&self, expression: &str) -> Result<String, CalcError> {
+    Ok(tokens_to_json(&self.tokenise(expression)?))
+  }
+
+  /// The parsed tree of an expression as nested JSON objects, like
+  /// `{"type":"binary","operator":"+","left":{"type":"number","value":2},...}`, for tools that
+  /// want to see how it was parsed.
+  pub fn ast_json(&self, expression: &str) -> Result<String, CalcError> {
+    Ok(self.parse(expression)?.to_json())
+  }
+
+  /// Whether the last statement of an expression ends in an operator or `=` that is still waiting
+  /// for its right operand, like `5 +`, so that the next line of input could complete it.
+  pub fn is_unfinished(&self, expression: &str) -> bool {
+    let last_statement = split_statements(expression).pop().unwrap_or_default();
+    matches!(
+      self.tokenise(last_statement).as_deref(),
+      Ok([.., Token::Operator(_) | Token::Assign(_)])
+    )
+  }
+
+  /// How many tokens an expression consists of, counting numbers, names, operators, parentheses and
+  /// argument separators, including the multiplications implied by `2(3+4)`.
+  pub fn token_count(&self, expression: &str) -> Result<usize, CalcError> {
+    Ok(self.tokenise(expression)?.len())
+  }
+
+  /// How deeply the parentheses and brackets of an expression nest, so `((1+2))` is `2`. Fails
+  /// with [`CalcError::MismatchedParentheses`] if they don't pair up, unless
+  /// [`Config::auto_close`] closes them.
+  pub fn max_paren_depth(&self, expression: &str) -> Result<usize, CalcError> {
+    nesting_depth(&self.tokenise(expression)?)
+  }
+
+  /// Parse an expression and print it back in canonical form, e.g. `2 * (3 + 4)` for `2(3+4)`,
+  /// without evaluating it.
+  pub fn normalize(&self, expression: &str) -> Result<String, CalcError> {
+    Ok(self.parse(expression)?.to_string())
+  }
+
+  /// The steps of simplifying an expression by evaluating one operation on numbers at a time, in
+  /// the order evaluation would, each in canonical form: `2 + 3 * 4`, `2 + 12`, `14`. Whatever
+  /// depends on variables or constants is left as it is, and so are results that aren't plain
+  /// finite numbers.
+  pub fn simplify_steps(&mut self, expression: &str) -> Result<Vec<String>, CalcError> {
+    let mut expr = self.parse(expression)?;
+    self.operations = 0;
+    self.start_clock();
+    let mut steps = vec![expr.to_string()];
+    while self.fold_once(&mut expr)? {
+      steps.push(expr.to_string());
+    }
+    Ok(steps)
+  }
+
+  /// Replace the first operation whose operands are all numbers with its result, returning
+  /// whether there was one.
+  fn fold_once(&mut self, expr: &mut Expr) -> Result<bool, CalcError> {
+    let children: Vec<&mut Expr> = match expr {
+      Expr::Unary(_, operand) | Expr::Assign(_, _, operand) => vec![operand],
+      Expr::Binary(_, left, right) | Expr::Index(left, right) => vec![left, right],
+      Expr::Call(_, items) | Expr::List(items) => items.iter_mut().collect(),
+      _ => vec![],
+    };
+    for child in children {
+      if self.fold_once(child)? {
+        return Ok(true);
+      }
+    }
+    let is_number = |expr: &Expr| matches!(expr, Expr::Number(_));
+    let foldable = match expr {
+      Expr::Unary(_, operand) => is_number(operand),
+      Expr::Binary(_, left, right) => is_number(left) && is_number(right),
+      Expr::Call(_, arguments) => arguments.iter().all(is_number),
+      _ => false,
+    };
+    if !foldable {
+      return Ok(false);
+    }
+    *expr = match self.eval(expr)?.classify() {
+      Value::Integer(integer) => Expr::Number(integer as f64),
+      Value::Float(float) if float.is_finite() => Expr::Number(float),
+      _ => return Ok(false),
+    };
+    Ok(true)
+  }
+
+  /// Start timing an evaluation against [`Config::timeout`].
+  pub(crate) fn start_clock(&mut self) {
+    self.deadline = self.timeout().map(|timeout| Instant::now() + timeout);
+  }
+
+  /// Fail if the current evaluation has run out of time. Called on every round of a loop.
+  pub(crate) fn check_deadline(&self) -> Result<(), CalcError> {
+    match (self.deadline, self.timeout()) {
+      (Some(deadline), Some(timeout)) if Instant::now() > deadline => {
+        Err(CalcError::Timeout(timeout))
+      }
+      _ => Ok(()),
+    }
+  }
+
+  /// Count an operator of the current evaluation, failing once [`Config::max_operations`] of
+  /// them have been applied.
+  fn count_operation(&mut self) -> Result<(), CalcError> {
+    match self.config.max_operations {
+      Some(limit) if self.operations >= limit => Err(CalcError::OperationLimitExceeded(limit)),
+      _ => {
+        self.operations += 1;
+        Ok(())
+      }
+    }
+  }
+
+  /// How long an evaluation may take, at most [`SANDBOX_TIMEOUT`] in a sandbox.
+  fn timeout(&self) -> Option<Duration> {
+    match (self.config.sandbox, self.config.timeout) {
+      (true, timeout) => {
+        Some(timeout.map_or(SANDBOX_TIMEOUT, |timeout| timeout.min(SANDBOX_TIMEOUT)))
+      }
+      (false, timeout) => timeout,
+    }
+  }
+
+  /// The longest expression that will be tokenised, at most [`SANDBOX_MAX_INPUT_LENGTH`] in a
+  /// sandbox.
+  fn max_input_length(&self) -> usize {
+    if self.config.sandbox {
+      self.config.max_input_length.min(SANDBOX_MAX_INPUT_LENGTH)
+    } else {
+      self.config.max_input_length
+    }
+  }
+
+  /// Fail with [`CalcError::FeatureDisabled`] in a sandbox, for a feature it turns off.
+  pub(crate) fn allow(&self, feature: &str) -> Result<(), CalcError> {
+    if self.config.sandbox {
+      return Err(CalcError::FeatureDisabled(feature.to_string()));
+    }
+    Ok(())
+  }
+
+  /// Run `f` with the current binding of `name` set aside, restoring it afterwards whether or not
+  /// `f` succeeds.
+  pub(crate) fn scoped<T>(
+    &mut self,
+    name: &str,
+    f: impl FnOnce(&mut Evaluator) -> Result<T, CalcError>,
+  ) -> Result<T, CalcError> {
+    let previous = self.variables.remove(name);
+    let result = f(self);
+    match previous {
+      Some(value) => self.variables.insert(name.to_string(), value),
+      None => self.variables.remove(name),
+    };
+    result
+  }
+
+  /// Set a variable, usually one set aside with [`Evaluator::scoped`].
+  pub(crate) fn bind(&mut self, name: &str, value: Value) {
+    self.variables.insert(name.to_string(), value);
+  }
+
+  /// A number written in an expression, exact in fraction mode whenever it can be.
+  pub(crate) fn number(&self, n: f64) -> Value {
+    match self.config.mode {
+      NumberMode::Float => Value::Float(n),
+      NumberMode::Fraction => {
+        Rational::from_decimal(n).map_or(Value::Float(n), Value::from_rational)
+      }
+    }
+  }
+
+  /// Parse an expression into a tree without evaluating it.
+  pub(crate) fn parse(&self, expression: &str) -> Result<Expr, CalcError> {
+    self.parse_tokens(self.tokenise(expression)?)
+  }
+
+  fn parse_tokens(&self, tokens: Vec<Token>) -> Result<Expr, CalcError> {
+    if tokens.is_empty() {
+      return Err(CalcError::NoExpression);
+    }
+    Expr::from_rpn(shunting_yard(tokens)?)
+  }
+
+  /// The number of arguments the function `name` takes, if there is such a function.
+  /// Defined functions shadow registered ones, which shadow built-in ones, and series aren't
+  /// included.
+  pub(crate) fn arity(&self, name: &str) -> Option<Arity> {
+    if let Some(definition) = self.definitions.get(name) {
+      return Some(Arity::Exactly(definition.parameters.len()));
+    }
+    match self.functions.get(name) {
+      Some(custom) => Some(Arity::Exactly(custom.arity)),
+      None => find_builtin(name).map(|builtin| builtin.arity),
+    }
+  }
+
+  /// Whether `name` is a registered or defined function, which shadows any other of that name.
+  fn is_custom(&self, name: &str) -> bool {
+    self.functions.contains_key(name) || self.definitions.contains_key(name)
+  }
+
+  /// Whether `name` is a series like `sum` rather than a custom function shadowing it.
+  pub(crate) fn is_series(&self, name: &str) -> bool {
+    SERIES.contains(&name) && !self.is_custom(name)
+  }
+
+  /// Whether `name` is a function taking text like `convert` rather than a custom function
+  /// shadowing it.
+  pub(crate) fn is_text_function(&self, name: &str) -> bool {
+    TEXT_FUNCTIONS.contains(&name) && !self.is_custom(name)
+  }
+
+  /// Whether `name` is `rangemax` or `rangemin` rather than a custom function shadowing it.
+  pub(crate) fn is_range_extremum(&self, name: &str) -> bool {
+    RANGE_EXTREMA.contains(&name) && !self.is_custom(name)
+  }
+
+  /// Whether `name` is `if` rather than a custom function shadowing it.
+  pub(crate) fn is_conditional(&self, name: &str) -> bool {
+    name == CONDITIONAL && !self.is_custom(name)
+  }
+
+  /// Whether `name` is one of the [`CALCULUS`] functions rather than a custom function shadowing
+  /// it.
+  pub(crate) fn is_calculus(&self, name: &str) -> bool {
+    calculus_arity(name).is_some() && !self.is_custom(name)
+  }
+
+  /// Whether `name` is `apply` rather than a custom function shadowing it.
+  pub(crate) fn is_template(&self, name: &str) -> bool {
+    name == TEMPLATE && !self.is_custom(name)
+  }
+
+  /// Fail if `name` is a constant that may not be assigned to.
+  pub(crate) fn check_assignable(&self, name: &str) -> Result<(), CalcError> {
+    if self.constant(name).is_some()
+      && !(self.config.allow_constant_assignment && self.constants.contains_key(name))
+    {
+      return Err(CalcError::ConstantAssignment(name.to_string()));
+    }
+    Ok(())
+  }
+
+  /// Fail on an expression longer than [`Config::max_input_length`] allows.
+  pub(crate) fn check_length(&self, expression: &str) -> Result<(), CalcError> {
+    let length = expression.chars().count();
+    if length > self.max_input_length() {
+      return Err(CalcError::InputTooLong {
+        length,
+        limit: self.max_input_length(),
+      });
+    }
+    Ok(())
+  }
+
+  /// Tokenise an expression, recognising registered constants as well as built-in ones.
+  pub(crate) fn tokenise(&self, expression: &str) -> Result<Vec<Token>, CalcError> {
+    self.check_length(expression)?;
+    let stripped;
+    let expression = if expression.contains("/*") {
+      stripped = strip_comments(expression)?;
+      &stripped
+    } else {
+      expression
+    };
+    let expanded;
+    let expression = if self.aliases.is_empty() {
+      expression
+    } else {
+      expanded = expand_aliases(expression, &self.aliases);
+      &expanded
+    };
+    tokenise(
+      expression,
+      &Syntax {
+        is_constant: &|name| self.constant(name).is_some(),
+        is_function: &|name| {
+          (self.arity(name).is_some()
+            || self.is_series(name)
+            || self.is_text_function(name)
+            || self.is_range_extremum(name)
+            || self.is_conditional(name)
+            || self.is_template(name)
+            || self.is_calculus(name))
+            && self.variable(name).is_none()
+        },
+        digit_grouping: self.config.digit_grouping,
+        auto_close: self.config.auto_close,
+        units: self.config.units,
+      },
+    )
+  }
+
+  pub(crate) fn eval(&mut self, expr: &Expr) -> Result<Value, CalcError> {
+    match expr {
+      Expr::Number(n) => Ok(self.number(*n)),
+      Expr::Constant(name) => self
+        .constant(name)
+        .map(Value::Float)
+        .ok_or_else(|| CalcError::UnknownVariable(name.clone())),
+      Expr::Variable(name) => self
+        .variable(name)
+        .or_else(|| self.recall_name(name))
+        .ok_or_else(|| CalcError::UnknownVariable(name.clone())),
+      Expr::Unary(operator, operand) => {
+        let operand = self.eval(operand)?;
+        match operand {
+          Value::List(_) => return Err(list_operand(*operator)),
+          Value::Text(_) => return Err(text_operand(*operator)),
+          _ => {}
+        }
+        self.check_boolean_operands(operator.symbol(), [&operand])?;
+        self.count_operation()?;
+        let result = match operator {
+          Operator::BitNot => bit_not(operand)?,
+          _ => negate(operand),
+        };
+        let result = self.wrap(result);
+        self.check_magnitude(&result)?;
+        Ok(result)
+      }
+      Expr::Binary(operator, left, right) => {
+        let left = self.eval(left)?;
+        let right = self.eval(right)?;
+        self.apply(*operator, left, right)
+      }
+      Expr::Unit(unit) => Dimension::of(unit)
+        .map(|dimension| Value::Quantity(1.0, dimension))
+        .ok_or_else(|| CalcError::UnknownVariable(unit.clone())),
+      Expr::Text(text) => Err(CalcError::Unsupported(format!(
+        "text like \"{}\" can only be passed to functions that take it",
+        text
+      ))),
+      Expr::Call(name, arguments) if self.definitions.contains_key(name) => {
+        self.call_definition(name, arguments)
+      }
+      Expr::Call(name, arguments) if self.is_series(name) => self.series(name, arguments),
+      Expr::Call(name, arguments) if self.is_conditional(name) => self.conditional(arguments),
+      Expr::Call(name, arguments) if self.is_template(name) => self.template(arguments),
+      Expr::Call(name, arguments) if self.is_calculus(name) => self.calculus(name, arguments),
+      Expr::Call(name, arguments) if self.is_range_extremum(name) => {
+        self.range_extremum(name, arguments)
+      }
+      Expr::Call(name, arguments) if self.is_text_function(name) => {
+        self.text_function(name, arguments)
+      }
+      Expr::Call(name, arguments) => {
+        let variadic = matches!(self.arity(name), Some(Arity::AtLeast(_)));
+        let mut values = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+          match self.eval(argument)? {
+            Value::List(_) if !variadic => {
+              return Err(CalcError::Unsupported(format!(
+                "'{}' doesn't take a list",
+                name
+              )))
+            }
+            Value::Quantity(..) => {
+              return Err(CalcError::Unsupported(format!(
+                "'{}' doesn't take a quantity with units",
+                name
+              )))
+            }
+            Value::Text(_) => {
+              return Err(CalcError::Unsupported(format!(
+                "'{}' doesn't take text",
+                name
+              )))
+            }
+            value => {
+              let flattened = value.flatten();
+              self.check_boolean_operands(name, &flattened)?;
+              values.extend(flattened.iter().map(Value::to_f64))
+            }
+          }
+        }
+        self.call(name, &values)
+      }
+      Expr::List(items) => items
+        .iter()
+        .map(|item| self.eval(item))
+        .collect::<Result<_, _>>()
+        .map(Value::List),
+      Expr::Index(list, index) => {
+        let Value::List(items) = self.eval(list)? else {
+          return Err(CalcError::Unsupported(
+            "only a list can be indexed".to_string(),
+          ));
+        };
+        let index = match self.eval(index)?.classify() {
+          Value::Integer(index) => index,
+          other => {
+            return Err(CalcError::NonIntegerOperand {
+              function: "[]".to_string(),
+              value: other.to_f64(),
+            })
+          }
+        };
+        usize::try_from(index)
+          .ok()
+          .and_then(|i| items.get(i).cloned())
+          .ok_or(CalcError::IndexOutOfBounds {
+            index,
+            length: items.len(),
+          })
+      }
+      Expr::Assign(name, operator, value) => {
+        self.check_assignable(name)?;
+        let constant = self.constant(name);
+        let mut value = self.eval(value)?;
+        if let Some(operator) = operator {
+          let current = constant
+            .map(Value::Float)
+            .or_else(|| self.variable(name))
+            .ok_or_else(|| CalcError::UnknownVariable(name.clone()))?;
+          value = self.apply(*operator, current, value)?;
+        }
+        if constant.is_some() {
+          if let Value::List(_) | Value::Text(_) = value {
+            return Err(CalcError::Unsupported(format!(
+              "the constant '{}' can only be a number",
+              name
+            )));
+          }
+          self.constants.insert(name.clone(), value.to_f64());
+        } else {
+          self.variables.insert(name.clone(), value.clone());
+        }
+        Ok(value)
+      }
+    }
+  }
+
+  pub(crate) fn apply(
+    &mut self,
+    operator: Operator,
+    left: Value,
+    right: Value,
+  ) -> Result<Value, CalcError> {
+    if matches!(left, Value::List(_)) || matches!(right, Value::List(_)) {
+      return Err(list_operand(operator));
+    }
+    if matches!(left, Value::Text(_)) || matches!(right, Value::Text(_)) {
+      return Err(text_operand(operator));
+    }
+    if matches!(left, Value::Quantity(..)) || matches!(right, Value::Quantity(..)) {
+      return self.apply_quantities(operator, left, right);
+    }
+    if !operator.is_comparison() {
+      self.check_boolean_operands(operator.symbol(), [&left, &right])?;
+    }
+    self.count_operation()?;
+    let operator = self.integer_division(operator, &left, &right)?;
+    let result = if operator.is_comparison() {
+      Value::Boolean(compare(operator, &left, &right, self.config.epsilon))
+    } else if let Some(result) = self.zero_power(operator, &left, &right)? {
+      result
+    } else {
+      self.round_step(self.wrap(apply_operator(operator, &left, &right)?))
+    };
+    let operands = [left, right];
+    self.check_non_finite(&operands, &result, || {
+      format!(
+        "{} {} {}",
+        operands[0].clone().classify(),
+        operator.symbol(),
+        operands[1].clone().classify()
+      )
+    })?;
+    self.check_magnitude(&result)?;
+    Ok(result)
+  }
+
+  /// Apply [`Config::boolean_arithmetic`] to the operands of an operator or function, given by
+  /// `name`.
+  fn check_boolean_operands<'a>(
+    &self,
+    name: &str,
+    operands: impl IntoIterator<Item = &'a Value>,
+  ) -> Result<(), CalcError> {
+    let strict = self.config.boolean_arithmetic == BooleanArithmetic::Strict;
+    if strict
+      && operands
+        .into_iter()
+        .any(|operand| matches!(operand, Value::Boolean(_)))
+    {
+      return Err(CalcError::Unsupported(format!(
+        "'{}' can't be applied to true or false with strict-bool",
+        name
+      )));
+    }
+    Ok(())
+  }
+
+  /// Apply [`Config::zero_power`] and [`Config::strict_zero_power`] to a power of zero, giving the
+  /// result if it differs from the usual one.
+  fn zero_power(
+    &self,
+    operator: Operator,
+    base: &Value,
+    exponent: &Value,
+  ) -> Result<Option<Value>, CalcError> {
+    if operator != Operator::Power || base.to_f64() != 0.0 {
+      return Ok(None);
+    }
+    let exponent = exponent.to_f64();
+    match self.config.zero_power {
+      ZeroPower::Error if exponent == 0.0 => Err(CalcError::Indeterminate("0 ^ 0".to_string())),
+      ZeroPower::Nan if exponent == 0.0 => Ok(Some(Value::Float(f64::NAN))),
+      _ if exponent < 0.0 && self.config.strict_zero_power => Err(CalcError::DivisionByZero),
+      _ => Ok(None),
+    }
+  }
+
+  /// Apply [`Config::integer_division`] to a division of two integers that doesn't come out even,
+  /// giving the operator to apply instead.
+  fn integer_division(
+    &self,
+    operator: Operator,
+    left: &Value,
+    right: &Value,
+  ) -> Result<Operator, CalcError> {
+    let (dividend, divisor) = (left.to_f64(), right.to_f64());
+    let inexact = operator == Operator::Divide
+      && [dividend, divisor]
+        .iter()
+        .all(|n| n.is_finite() && n.fract() == 0.0)
+      && divisor != 0.0
+      && dividend % divisor != 0.0;
+    match self.config.integer_division {
+      IntegerDivision::Strict if inexact => Err(CalcError::InexactDivision(format!(
+        "{} / {}",
+        left.clone().classify(),
+        right.clone().classify()
+      ))),
+      IntegerDivision::Truncate if inexact => Ok(Operator::FloorDivide),
+      _ => Ok(operator),
+    }
+  }
+
+  fn call(&mut self, name: &str, arguments: &[f64]) -> Result<Value, CalcError> {
+    let result = match self.functions.get(name) {
+      Some(custom) if custom.arity != arguments.len() => {
+        return Err(CalcError::ArityMismatch {
+          function: name.to_string(),
+          expected: custom.arity,
+          found: arguments.len(),
+        })
+      }
+      Some(custom) => Value::Float((custom.function)(arguments)),
+      None => call_builtin(name, arguments, &self.config)?,
+    };
+    let result = self.round_step(result);
+    let operands: Vec<Value> = arguments.iter().copied().map(Value::from_f64).collect();
+    self.check_non_finite(&operands, &result, || {
+      let arguments: Vec<String> = operands.iter().map(Value::to_string).collect();
+      format!("{}({})", name, arguments.join(", "))
+    })?;
+    self.check_magnitude(&result)?;
+    Ok(result)
+  }
+
+  /// Wrap an integral result into the configured fixed-width range, if there is one.
+  fn wrap(&self, result: Value) -> Value {
+    let Some(wrap) = self.config.wrap else {
+      return result;
+    };
+    let value = result.to_f64();
+    if value.fract() != 0.0 || !value.is_finite() {
+      return result;
+    }
+    let wrapped = wrap.apply(value as i128) as f64;
+    match result {
+      Value::Float(_) => Value::Float(wrapped),
+      Value::Quantity(_, dimension) => Value::Quantity(wrapped, dimension),
+      _ => Value::from_f64(wrapped),
+    }
+  }
+
+  /// Round an intermediate result to [`Config::round_steps`] decimals, if set.
+  fn round_step(&self, result: Value) -> Value {
+    match (self.config.round_steps, &result) {
+      (Some(decimals), Value::Float(_) | Value::Rational(_)) => {
+        Value::Float(round(result.to_f64(), decimals, Rounding::HalfUp))
+      }
+      _ => result,
+    }
+  }
+
+  /// Fail on a number beyond [`Config::max_magnitude`]. Lists and text have no magnitude, and NaN
+  /// is left to [`Config::nan_policy`].
+  fn check_magnitude(&self, result: &Value) -> Result<(), CalcError> {
+    let value = result.to_f64();
+    match self.config.max_magnitude {
+      Some(limit) if value.abs() > limit => Err(CalcError::MagnitudeExceeded { value, limit }),
+      _ => Ok(()),
+    }
+  }
+
+  /// Remember the first operation that turned finite operands into a non-finite result, and fail
+  /// on one that turned numbers into NaN if [`Config::nan_policy`] says so.
+  fn check_non_finite(
+    &mut self,
+    operands: &[Value],
+    result: &Value,
+    describe: impl FnOnce() -> String,
+  ) -> Result<(), CalcError> {
+    let value = result.to_f64();
+    if matches!(result, Value::Text(_)) || value.is_finite() {
+      return Ok(());
+    }
+    let first = self.first_non_finite.is_none()
+      && operands.iter().all(|operand| operand.to_f64().is_finite());
+    let fails = self.config.nan_policy == NanPolicy::Error
+      && value.is_nan()
+      && operands.iter().all(|operand| !operand.to_f64().is_nan());
+    if !first && !fails {
+      return Ok(());
+    }
+    let operation = describe();
+    if fails {
+      return Err(CalcError::NotANumber(operation));
+    }
+    self.first_non_finite = Some(NonFiniteStep {
+      operation,
+      result: value,
+    });
+    Ok(())
+  }
+}
+
+/// The result of an evaluation as a float, which a list or text isn't.
+fn number_result(value: Value) -> Result<f64, CalcError> {
+  match value {
+    Value::List(_) => Err(CalcError::Unsupported(
+      "the result is a list, not a number".to_string(),
+    )),
+    Value::Text(_) => Err(CalcError::Unsupported(
+      "the result is text, not a number".to_string(),
+    )),
+    number => Ok(number.to_f64()),
+  }
+}
+
+/// Operators only apply to numbers.
+fn list_operand(operator: Operator) -> CalcError {
+  CalcError::Unsupported(format!(
+    "'{}' can't be applied to a list",
+    operator.symbol()
+  ))
+}
+
+fn text_operand(operator: Operator) -> CalcError {
+  CalcError::Unsupported(format!("'{}' can't be applied to text", operator.symbol()))
+}
+
+fn negate(operand: Value) -> Value {
+  if let Value::Quantity(magnitude, dimension) = operand {
+    return Value::Quantity(-magnitude, dimension);
+  }
+  match operand.to_rational().and_then(|exact| exact.checked_neg()) {
+    Some(exact) => Value::from_rational(exact),
+    None => Value::Float(-operand.to_f64()),
+  }
+}
+
+/// Flip every bit of an integer in two's complement, so `~5` is `-6`.
+fn bit_not(operand: Value) -> Result<Value, CalcError> {
+  match operand.classify() {
+    Value::Integer(integer) => Ok(Value::Integer(!integer)),
+    other => Err(CalcError::NonIntegerOperand {
+      function: Operator::BitNot.symbol().to_string(),
+      value: other.to_f64(),
+    }),
+  }
+}
+
+/// Apply an operator exactly when both operands are exact, and as floats otherwise.
+fn apply_operator(operator: Operator, left: &Value, right: &Value) -> Result<Value, CalcError> {
+  if let (Some(left), Some(right)) = (left.to_rational(), right.to_rational()) {
+    let exact = match operator {
+      Operator::Add => left.checked_add(right),
+      Operator::Subtract => left.checked_sub(right),
+      Operator::Multiply => left.checked_mul(right),
+      Operator::Divide if right.numerator() == 0 => return Err(CalcError::DivisionByZero),
+      Operator::Divide => left.checked_div(right),
+      Operator::FloorDivide if right.numerator() == 0 => return Err(CalcError::DivisionByZero),
+      Operator::FloorDivide => left
+        .checked_div(right)
+        .map(|quotient| Rational::from_integer(quotient.floor())),
+      Operator::Power if right.is_integer() => left.checked_pow(right.numerator()),
+      _ => None,
+    };
+    if let Some(exact) = exact {
+      return Ok(Value::from_rational(exact));
+    }
+  }
+
+  let (left, right) = (left.to_f64(), right.to_f64());
+  if operator == Operator::FloorDivide && right == 0.0 {
+    return Err(CalcError::DivisionByZero);
+  }
+  Ok(Value::Float(match operator {
+    Operator::Add => left + right,
+    Operator::Subtract => left - right,
+    Operator::Multiply => left * right,
+    Operator::Divide => left / right,
+    Operator::FloorDivide => (left / right).floor(),
+    Operator::Power => integer_power(left, right).unwrap_or_else(|| left.powf(right)),
+    Operator::Negate | Operator::BitNot => unreachable!("unary operators are applied by eval"),
+    _ => unreachable!("comparisons are applied by compare"),
+  }))
+}
+
+/// Apply a comparison, exactly between exact numbers and otherwise treating numbers within
+/// `epsilon` of each other as equal. Comparisons with NaN are false, except for `!=`.
+fn compare(operator: Operator, left: &Value, right: &Value, epsilon: f64) -> bool {
+  let ordering = match (left.to_rational(), right.to_rational()) {
+    (Some(left), Some(right)) => Some(left.cmp(&right)),
+    _ => {
+      let (left, right) = (left.to_f64(), right.to_f64());
+      if (left - right).abs() <= epsilon * left.abs().max(right.abs()).max(1.0) {
+        Some(Ordering::Equal)
+      } else {
+        left.partial_cmp(&right)
+      }
+    }
+  };
+  match operator {
+    Operator::Equal => ordering == Some(Ordering::Equal),
+    Operator::NotEqual => ordering != Some(Ordering::Equal),
+    Operator::Less => ordering == Some(Ordering::Less),
+    Operator::LessEqual => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+    Operator::Greater => ordering == Some(Ordering::Greater),
+    Operator::GreaterEqual => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+    _ => unreachable!("only comparisons are compared"),
+  }
+}
+
+/// Raise an integral float to a non-negative integral power by exact integer arithmetic, or `None`
+/// if either isn't integral or the result doesn't fit an `i64`.
+fn integer_power(base: f64, exponent: f64) -> Option<f64> {
+  let integral = |value: f64| value.fract() == 0.0 && value.abs() < i64::MAX as f64;
+  if !integral(base) || !integral(exponent) || exponent < 0.0 {
+    return None;
+  }
+  let exponent = u32::try_from(exponent as i64).ok()?;
+  Some((base as i64).checked_pow(exponent)? as f64)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fraction_evaluator() -> Evaluator {
+    Evaluator::with_config(Config {
+      mode: NumberMode::Fraction,
+      ..Config::default()
+    })
+  }
+
+  #[test]
+  fn test_float_mode_kinds() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.evaluate_typed("4/2"), Ok(Value::Integer(2)));
+    assert_eq!(evaluator.evaluate_typed("1/4"), Ok(Value::Float(0.25)));
+    assert_eq!(evaluator.evaluate_typed("10^30"), Ok(Value::Float(1e30)));
+  }
+
+  #[test]
+  fn test_fraction_mode_kinds() {
+    let mut evaluator = fraction_evaluator();
+    assert_eq!(evaluator.evaluate_typed("4/2"), Ok(Value::Integer(2)));
+    assert_eq!(
+      evaluator.evaluate_typed("1/3"),
+      Ok(Value::Rational(Rational::new(1, 3).unwrap()))
+    );
+    assert_eq!(
+      evaluator.evaluate_typed("0.1+0.2"),
+      Ok(Value::Rational(Rational::new(3, 10).unwrap()))
+    );
+    assert_eq!(
+      evaluator.evaluate_typed("(1/2)^3"),
+      Ok(Value::Rational(Rational::new(1, 8).unwrap()))
+    );
+    assert_eq!(evaluator.evaluate_typed("-7//2"), Ok(Value::Integer(-4)));
+    assert_eq!(
+      evaluator.evaluate_typed("(7/3)//(1/3)"),
+      Ok(Value::Integer(7))
+    );
+    assert_eq!(
+      evaluator.evaluate_typed("1//0"),
+      Err(CalcError::DivisionByZero)
+    );
+  }
+
+  #[test]
+  fn test_identifiers() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.evaluate("2*pi"), Ok(2.0 * std::f64::consts::PI));
+    assert_eq!(
+      evaluator.evaluate("x1+1"),
+      Err(CalcError::UnknownVariable("x1".to_string()))
+    );
+    assert_eq!(
+      evaluator.evaluate("f(1)"),
+      Err(CalcError::UnknownFunction("f".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_assignment() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.evaluate("x = 2+3"), Ok(5.0));
+    assert_eq!(evaluator.evaluate("x*2"), Ok(10.0));
+    assert_eq!(evaluator.evaluate("x = x+1"), Ok(6.0));
+    assert_eq!(evaluator.variable("x"), Some(Value::Float(6.0)));
+  }
+
+  #[test]
+  fn test_chained_assignment() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.evaluate("a = b = 5"), Ok(5.0));
+    assert_eq!(evaluator.variable("a"), Some(Value::Float(5.0)));
+    assert_eq!(evaluator.variable("b"), Some(Value::Float(5.0)));
+    assert_eq!(evaluator.evaluate("a = (b = 2) + 1"), Ok(3.0));
+    assert_eq!(evaluator.evaluate("a*b"), Ok(6.0));
+  }
+
+  #[test]
+  fn test_compound_assignment() {
+    let mut evaluator = Evaluator::new();
+    evaluator.evaluate("a = 10").unwrap();
+    assert_eq!(evaluator.evaluate("a += 2"), Ok(12.0));
+    assert_eq!(evaluator.evaluate("a -= 4"), Ok(8.0));
+    assert_eq!(evaluator.evaluate("a *= 3"), Ok(24.0));
+    assert_eq!(evaluator.evaluate("a /= 2*3"), Ok(4.0));
+    assert_eq!(evaluator.evaluate("b = a += 1"), Ok(5.0));
+    assert_eq!(evaluator.variable("b"), Some(Value::Float(5.0)));
+    assert_eq!(
+      evaluator.evaluate("c += 1"),
+      Err(CalcError::UnknownVariable("c".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_invalid_assignment_target() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+      evaluator.evaluate("2 = 3"),
+      Err(CalcError::InvalidAssignment)
+    );
+    assert_eq!(
+      evaluator.evaluate("pi = 3"),
+      Err(CalcError::ConstantAssignment("pi".to_string()))
+    );
+    assert_eq!(
+      evaluator.evaluate("2*a = 3"),
+      Err(CalcError::InvalidAssignment)
+    );
+    assert_eq!(
+      evaluator.evaluate("a+b += 3"),
+      Err(CalcError::InvalidAssignment)
+    );
+  }
+
+  #[test]
+  fn test_max_input_length() {
+    let mut evaluator = Evaluator::with_config(Config {
+      max_input_length: 5,
+      ..Config::default()
+    });
+    assert_eq!(evaluator.evaluate("1+2+3"), Ok(6.0));
+    assert_eq!(
+      evaluator.evaluate("1+2+3+4"),
+      Err(CalcError::InputTooLong {
+        length: 7,
+        limit: 5
+      })
+    );
+    assert!(evaluator.solve("x = 12").is_err());
+
+    let long = "1+".repeat(DEFAULT_MAX_INPUT_LENGTH / 2) + "1";
+    assert!(matches!(
+      Evaluator::new().evaluate(&long),
+      Err(CalcError::InputTooLong { .. })
+    ));
+  }
+
+  #[test]
+  fn test_operation_count() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.evaluate_with_stats("42"), Ok((42.0, 0)));
+    assert_eq!(evaluator.evaluate_with_stats("2+3*4"), Ok((14.0, 2)));
+    assert_eq!(evaluator.evaluate_with_stats("-(2+3)^2"), Ok((-25.0, 3)));
+    assert_eq!(evaluator.evaluate_with_stats("sqrt(4)"), Ok((2.0, 0)));
+    assert_eq!(evaluator.evaluate_with_stats("x = 1 + 1"), Ok((2.0, 1)));
+    assert_eq!(evaluator.evaluate_with_stats("x *= 3"), Ok((6.0, 1)));
+    // Three additions to the total plus three squarings.
+    assert_eq!(
+      evaluator.evaluate_with_stats("sum(i, 1, 3, i^2)"),
+      Ok((14.0, 6))
+    );
+  }
+
+  #[test]
+  fn test_max_magnitude() {
+    let mut evaluator = Evaluator::with_config(Config {
+      max_magnitude: Some(1000.0),
+      ..Config::default()
+    });
+    assert_eq!(evaluator.evaluate("2^3^2 - 500"), Ok(12.0));
+    assert_eq!(evaluator.evaluate("-1000"), Ok(-1000.0));
+    // The tower fails at 3^3^3 = 3^27, long before it would overflow.
+    assert_eq!(
+      evaluator.evaluate("3^3^3^3"),
+      Err(CalcError::MagnitudeExceeded {
+        value: 3f64.powi(27),
+        limit: 1000.0
+      })
+    );
+    assert_eq!(
+      evaluator.evaluate("2^20 / 2^20"),
+      Err(CalcError::MagnitudeExceeded {
+        value: 1048576.0,
+        limit: 1000.0
+      })
+    );
+    assert!(matches!(
+      evaluator.evaluate("-(40 * 40)"),
+      Err(CalcError::MagnitudeExceeded { .. })
+    ));
+    assert!(matches!(
+      evaluator.evaluate("exp(10)"),
+      Err(CalcError::MagnitudeExceeded { .. })
+    ));
+    assert_eq!(
+      evaluator.evaluate("1/0").unwrap_err().to_string(),
+      "inf exceeds the maximum magnitude of 1000"
+    );
+  }
+
+  #[test]
+  fn test_bitwise_not() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.evaluate_typed("~0"), Ok(Value::Integer(-1)));
+    assert_eq!(evaluator.evaluate("~5"), Ok(-6.0));
+    assert_eq!(evaluator.evaluate("~-6"), Ok(5.0));
+    assert_eq!(evaluator.evaluate("~~7"), Ok(7.0));
+    assert_eq!(evaluator.evaluate("~2^2"), Ok(-5.0));
+    assert_eq!(evaluator.evaluate("1 + ~1 * 2"), Ok(-3.0));
+    assert_eq!(evaluator.evaluate("~(2.5 * 2)"), Ok(-6.0));
+    assert_eq!(evaluator.normalize("~(1+2)"), Ok("~(1 + 2)".to_string()));
+    assert_eq!(
+      evaluator.evaluate("~1.5"),
+      Err(CalcError::NonIntegerOperand {
+        function: "~".to_string(),
+        value: 1.5
+      })
+    );
+    assert!(matches!(
+      evaluator.evaluate("~(1 > 0)"),
+      Err(CalcError::NonIntegerOperand { .. })
+    ));
+  }
+
+  #[test]
+  fn test_integer_powers() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.evaluate("10^2"), Ok(100.0));
+    assert_eq!(evaluator.evaluate("10^15"), Ok(1e15));
+    assert_eq!(evaluator.evaluate("2^62"), Ok(4611686018427387904.0));
+    assert_eq!(evaluator.evaluate("(-3)^3"), Ok(-27.0));
+    assert_eq!(evaluator.evaluate("7^0"), Ok(1.0));
+    assert_eq!(evaluator.evaluate("2^-1"), Ok(0.5));
+    assert_eq!(evaluator.evaluate("4^0.5"), Ok(2.0));
+    assert_eq!(evaluator.evaluate("10^400"), Ok(f64::INFINITY));
+  }
+
+  #[test]
+  fn test_reset() {
+    let mut evaluator = fraction_evaluator();
+    evaluator.evaluate("x = 1/3").unwrap();
+    evaluator.register_fn("double", 1, |args| args[0] * 2.0);
+    evaluator.register_const("golden", 1.618);
+    evaluator.evaluate("10^308*10").unwrap();
+    assert!(evaluator.first_non_finite().is_some());
+
+    evaluator.reset();
+    assert_eq!(
+      evaluator.evaluate("x"),
+      Err(CalcError::UnknownVariable("x".to_string()))
+    );
+    assert_eq!(
+      evaluator.evaluate("double(2)"),
+      Err(CalcError::UnknownFunction("double".to_string()))
+    );
+    assert!(evaluator.evaluate("golden").is_err());
+    assert!(evaluator.evaluate("ans").is_err());
+    assert_eq!(evaluator.first_non_finite(), None);
+    assert_eq!(evaluator.config.mode, NumberMode::Fraction);
+  }
+
+  #[test]
+  fn test_wrap() {
+    let wrapping = |bits, signed| {
+      Evaluator::with_config(Config {
+        wrap: Wrap::new(bits, signed),
+        ..Config::default()
+      })
+    };
+    let mut unsigned = wrapping(8, false);
+    assert_eq!(unsigned.evaluate("255+1"), Ok(0.0));
+    assert_eq!(unsigned.evaluate("200*2"), Ok(144.0));
+    assert_eq!(unsigned.evaluate("0-1"), Ok(255.0));
+    assert_eq!(unsigned.evaluate("-1"), Ok(255.0));
+    assert_eq!(unsigned.evaluate("1/2"), Ok(0.5));
+
+    let mut signed = wrapping(8, true);
+    assert_eq!(signed.evaluate("127+1"), Ok(-128.0));
+    assert_eq!(signed.evaluate("-128-1"), Ok(127.0));
+    assert_eq!(signed.evaluate("100+27"), Ok(127.0));
+    assert_eq!(signed.evaluate("2^8"), Ok(0.0));
+
+    assert_eq!(Wrap::new(0, false), None);
+    assert_eq!(Wrap::new(65, true), None);
+    assert_eq!(Wrap::new(64, false).unwrap().apply(-1), u64::MAX as i128);
+  }
+
+  #[test]
+  fn test_comparison_tolerance() {
+    assert_eq!(Evaluator::new().evaluate("0.1+0.2 == 0.3"), Ok(1.0));
+    assert_eq!(Evaluator::new().evaluate("0.1+0.2 != 0.3"), Ok(0.0));
+    assert_eq!(Evaluator::new().evaluate("1 == 1.001"), Ok(0.0));
+    assert_eq!(Evaluator::new().evaluate("10^20 == 10^20 + 1000"), Ok(1.0));
+
+    let mut exact = Evaluator::with_config(Config {
+      epsilon: 0.0,
+      ..Config::default()
+    });
+    assert_eq!(exact.evaluate("0.1+0.2 == 0.3"), Ok(0.0));
+    assert_eq!(exact.evaluate("0.1+0.2 != 0.3"), Ok(1.0));
+
+    // Fractions are exact, so there is nothing to tolerate.
+    assert_eq!(fraction_evaluator().evaluate("0.1+0.2 == 0.3"), Ok(1.0));
+    assert_eq!(
+      fraction_evaluator().evaluate("1/3 == 333333333333333/1000000000000000"),
+      Ok(0.0)
+    );
+  }
+
+  #[test]
+  fn test_auto_close() {
+    let mut lenient = Evaluator::with_config(Config {
+      auto_close: true,
+      ..Config::default()
+    });
+    for (open, closed) in [
+      ("2*(3+4", "2*(3+4)"),
+      ("((1+2)*(3", "((1+2)*(3))"),
+      ("sqrt(16", "sqrt(16)"),
+      ("sum([2, ⌊3.5", "sum([2, ⌊3.5⌋])"),
+      ("mean([1, 2", "mean([1, 2])"),
+      ("(1+2)", "(1+2)"),
+    ] {
+      assert_eq!(lenient.normalize(open), lenient.normalize(closed));
+      assert_eq!(lenient.evaluate(open), lenient.evaluate(closed));
+    }
+    assert_eq!(lenient.evaluate("2*(3+4"), Ok(14.0));
+    assert_eq!(
+      lenient.evaluate("1+2)"),
+      Err(CalcError::MismatchedParentheses)
+    );
+    assert_eq!(
+      Evaluator::new().evaluate("2*(3+4"),
+      Err(CalcError::MismatchedParentheses)
+    );
+  }
+
+  #[test]
+  fn test_boolean_results() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.evaluate_typed("3>2"), Ok(Value::Boolean(true)));
+    assert_eq!(evaluator.evaluate_typed("3<2"), Ok(Value::Boolean(false)));
+    assert_eq!(evaluator.evaluate_typed("2 <= 2"), Ok(Value::Boolean(true)));
+    assert_eq!(
+      evaluator.evaluate_typed("0.1+0.2 >= 0.3"),
+      Ok(Value::Boolean(true))
+    );
+    assert_eq!(
+      evaluator.evaluate_typed("0.1+0.2 > 0.3"),
+      Ok(Value::Boolean(false))
+    );
+    assert_eq!(
+      evaluator.evaluate_typed("0/0 >= 0/0"),
+      Ok(Value::Boolean(false))
+    );
+    assert_eq!(evaluator.evaluate_typed("(3>2) + 1"), Ok(Value::Integer(2)));
+    assert_eq!(evaluator.evaluate_typed("-(3>2)"), Ok(Value::Integer(-1)));
+    assert_eq!(evaluator.evaluate("1 < 2"), Ok(1.0));
+    assert_eq!(
+      fraction_evaluator().evaluate_typed("1/3 < 0.3333333333333333"),
+      Ok(Value::Boolean(false))
+    );
+  }
+
+  #[test]
+  fn test_running_total() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.total(), None);
+    evaluator.start_total();
+    assert_eq!(evaluator.total(), Some(&Value::Integer(0)));
+    assert_eq!(evaluator.add_to_total("10"), Ok(Value::Integer(10)));
+    assert_eq!(evaluator.add_to_total("2.5"), Ok(Value::Float(12.5)));
+    assert_eq!(evaluator.add_to_total("3*2"), Ok(Value::Float(18.5)));
+    assert!(evaluator.add_to_total("2+").is_err());
+    assert!(evaluator.add_to_total("[1]").is_err());
+    assert_eq!(evaluator.add_to_total("-0.5"), Ok(Value::Integer(18)));
+    assert_eq!(evaluator.evaluate("ans"), Ok(-0.5));
+    assert_eq!(evaluator.end_total(), Some(Value::Integer(18)));
+    assert_eq!(evaluator.total(), None);
+
+    assert_eq!(evaluator.add_to_total("4"), Ok(Value::Integer(4)));
+    evaluator.start_total();
+    assert_eq!(evaluator.total(), Some(&Value::Integer(0)));
+  }
+
+  #[test]
+  fn test_lists() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+      evaluator.evaluate_typed("[1, 2/4, [3]]"),
+      Ok(Value::List(vec![
+        Value::Integer(1),
+        Value::Float(0.5),
+        Value::List(vec![Value::Integer(3)])
+      ]))
+    );
+    assert_eq!(evaluator.evaluate_typed("[]"), Ok(Value::List(vec![])));
+    evaluator.evaluate_typed("v = [2, 4]").unwrap();
+    assert_eq!(evaluator.evaluate("mean(v) + 1"), Ok(4.0));
+    assert!(matches!(
+      evaluator.evaluate("[1, 2]"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert!(matches!(
+      evaluator.evaluate("v + 1"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert!(matches!(
+      evaluator.evaluate("-v"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert_eq!(
+      evaluator.evaluate("[1, 2"),
+      Err(CalcError::MismatchedParentheses)
+    );
+    assert_eq!(
+      evaluator.evaluate("(1]"),
+      Err(CalcError::MismatchedParentheses)
+    );
+  }
+
+  #[test]
+  fn test_list_indexing() {
+    let mut evaluator = Evaluator::new();
+    evaluator.evaluate_typed("v = [10, 20, [30, 40]]").unwrap();
+    assert_eq!(evaluator.evaluate("v[1]"), Ok(20.0));
+    assert_eq!(evaluator.evaluate("v[0] + v[1] * 2"), Ok(50.0));
+    assert_eq!(evaluator.evaluate("v[2][1]"), Ok(40.0));
+    assert_eq!(evaluator.evaluate("v[2 - 1]^2"), Ok(400.0));
+    assert_eq!(evaluator.evaluate("-v[0]"), Ok(-10.0));
+    assert_eq!(evaluator.evaluate("[5, 6][1]"), Ok(6.0));
+    // A list after a number is still multiplied by it.
+    assert!(matches!(
+      evaluator.evaluate("2[1]"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert_eq!(evaluator.parse("v[1]+1").unwrap().to_string(), "v[1] + 1");
+
+    assert_eq!(
+      evaluator.evaluate("v[3]"),
+      Err(CalcError::IndexOutOfBounds {
+        index: 3,
+        length: 3
+      })
+    );
+    assert_eq!(
+      evaluator.evaluate("v[-1]"),
+      Err(CalcError::IndexOutOfBounds {
+        index: -1,
+        length: 3
+      })
+    );
+    assert_eq!(
+      evaluator.evaluate("v[0.5]"),
+      Err(CalcError::NonIntegerOperand {
+        function: "[]".to_string(),
+        value: 0.5
+      })
+    );
+    assert!(matches!(
+      evaluator.evaluate("v[0, 1]"),
+      Err(CalcError::MisplacedSeparator)
+    ));
+    evaluator.evaluate("x = 3").unwrap();
+    assert!(matches!(
+      evaluator.evaluate("x[0]"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert_eq!(
+      evaluator.validate("w[0]"),
+      Err(CalcError::UnknownVariable("w".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_statements() {
+    use crate::error::OperandSide;
+
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+      evaluator.evaluate_statements("x = 5;  x*2"),
+      Ok(vec![Value::Integer(10)])
+    );
+    assert_eq!(
+      evaluator.evaluate_statements("x; x + 1; x + 2"),
+      Ok(vec![Value::Integer(7)])
+    );
+    assert_eq!(
+      evaluator.evaluate_statements("1; 2"),
+      Ok(vec![Value::Integer(2)])
+    );
+    assert_eq!(
+      evaluator.evaluate_statements("y = 1, 2"),
+      Err(CalcError::MisplacedSeparator)
+    );
+    assert_eq!(evaluator.evaluate_statements("3; 5;"), Ok(vec![]));
+    assert_eq!(
+      evaluator.evaluate_statements("2; *3; 1 + 1"),
+      Ok(vec![Value::Integer(2)])
+    );
+    assert_eq!(evaluator.evaluate("ans2"), Ok(6.0));
+    assert_eq!(
+      evaluator.evaluate_statements(" ; "),
+      Err(CalcError::NoExpression)
+    );
+    assert_eq!(
+      evaluator.evaluate_statements("z = 1; 1/; z"),
+      Err(CalcError::MissingOperand {
+        operator: "/".to_string(),
+        side: OperandSide::Right
+      })
+    );
+    assert_eq!(evaluator.evaluate("z"), Ok(1.0));
+  }
+
+  #[test]
+  fn test_statements_with_text() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+      evaluator.evaluate_statements("debug(\"a;b\", 1); parse(16, \"ff\")"),
+      Ok(vec![Value::Integer(255)])
+    );
+    assert_eq!(evaluator.take_annotations(), vec!["a;b = 1".to_string()]);
+    assert_eq!(
+      evaluator.evaluate_statements("debug(\"say \\\";\\\"\", 2)"),
+      Ok(vec![Value::Integer(2)])
+    );
+    assert!(evaluator.is_unfinished("debug(\";\", 1) +"));
+    assert!(!evaluator.is_unfinished("1 + debug(\"+;\", 1)"));
+  }
+
+  #[test]
+  fn test_statements_with_comments() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+      evaluator.evaluate_statements("1 /* a;b */ + 1"),
+      Ok(vec![Value::Integer(2)])
+    );
+    assert_eq!(
+      evaluator.evaluate_statements("x = 2 /* ; */; x /*;*/ * 3"),
+      Ok(vec![Value::Integer(6)])
+    );
+    assert!(matches!(
+      evaluator.evaluate_statements("1; 2 /* open; 3"),
+      Err(CalcError::UnterminatedComment { .. })
+    ));
+    assert!(evaluator.is_unfinished("1 + /* ; */"));
+    assert!(!evaluator.is_unfinished("1 /* + ; */ + 1"));
+  }
+
+  #[test]
+  fn test_continued_lines() {
+    let mut evaluator = Evaluator::new();
+    let mut continued = |line| {
+      evaluator
+        .evaluate_continued(line)
+        .map(|value| value.to_f64())
+    };
+    assert!(matches!(
+      continued("+5"),
+      Err(CalcError::MissingOperand { .. })
+    ));
+    assert_eq!(continued("10"), Ok(10.0));
+    assert_eq!(continued("+5"), Ok(15.0));
+    assert_eq!(continued("*2"), Ok(30.0));
+    assert_eq!(continued(" / 4 + 1"), Ok(8.5));
+    assert_eq!(continued("//2"), Ok(4.0));
+    assert_eq!(continued("^2"), Ok(16.0));
+    assert_eq!(continued("-5"), Ok(-5.0));
+    assert_eq!(continued("2*3"), Ok(6.0));
+  }
+
+  #[test]
+  fn test_history() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+      evaluator.evaluate("ans"),
+      Err(CalcError::UnknownVariable("ans".to_string()))
+    );
+    for i in 1..=HISTORY_LENGTH + 2 {
+      evaluator.evaluate(&i.to_string()).unwrap();
+    }
+    assert_eq!(evaluator.recall(1), Some(Value::Integer(12)));
+    assert_eq!(evaluator.recall(HISTORY_LENGTH), Some(Value::Integer(3)));
+    assert_eq!(evaluator.recall(HISTORY_LENGTH + 1), None);
+    assert_eq!(evaluator.recall(0), None);
+
+    assert_eq!(evaluator.evaluate("ans + ans3"), Ok(22.0));
+    assert_eq!(evaluator.evaluate("ans1 * 2"), Ok(44.0));
+    assert_eq!(evaluator.evaluate("ans2"), Ok(22.0));
+    assert!(evaluator.evaluate("ans01").is_err());
+    assert!(evaluator.evaluate("ans11").is_err());
+  }
+
+  #[test]
+  fn test_variables_shadow_history() {
+    let mut evaluator = Evaluator::new();
+    evaluator.evaluate("2").unwrap();
+    evaluator.evaluate("ans1 = 7").unwrap();
+    assert_eq!(evaluator.evaluate("ans1"), Ok(7.0));
+    assert_eq!(evaluator.evaluate("ans2"), Ok(7.0));
+  }
+
+  #[test]
+  fn test_round_steps() {
+    let mut evaluator = Evaluator::with_config(Config {
+      round_steps: Some(2),
+      ..Config::default()
+    });
+    assert_eq!(Evaluator::new().evaluate("1/3 + 1/3 + 1/3"), Ok(1.0));
+    assert_eq!(evaluator.evaluate("1/3 + 1/3 + 1/3"), Ok(0.99));
+    assert_eq!(evaluator.evaluate("sqrt(2) * sqrt(2)"), Ok(1.99));
+    assert_eq!(evaluator.evaluate("2/3"), Ok(0.67));
+    assert_eq!(evaluator.evaluate("7 * 6"), Ok(42.0));
+    assert_eq!(evaluator.evaluate("10^307 * 1.5"), Ok(1.5e307));
+    let mut evaluator = Evaluator::with_config(Config {
+      round_steps: Some(400),
+      ..Config::default()
+    });
+    assert_eq!(evaluator.evaluate("1/3"), Ok(1.0 / 3.0));
+  }
+
+  #[test]
+  fn test_integer_division() {
+    let evaluator = |integer_division, mode| {
+      Evaluator::with_config(Config {
+        integer_division,
+        mode,
+        ..Config::default()
+      })
+    };
+    let mut promote = evaluator(IntegerDivision::Promote, NumberMode::Float);
+    assert_eq!(promote.evaluate("7/2"), Ok(3.5));
+    let mut strict = evaluator(IntegerDivision::Strict, NumberMode::Float);
+    assert_eq!(
+      strict.evaluate("7/2"),
+      Err(CalcError::InexactDivision("7 / 2".to_string()))
+    );
+    assert_eq!(strict.evaluate("8/2"), Ok(4.0));
+    assert_eq!(strict.evaluate("7.5/2"), Ok(3.75));
+    let mut truncate = evaluator(IntegerDivision::Truncate, NumberMode::Float);
+    assert_eq!(truncate.evaluate("7/2"), Ok(3.0));
+    assert_eq!(truncate.evaluate("-7/2"), Ok(-4.0));
+    assert_eq!(truncate.evaluate("7.5/2"), Ok(3.75));
+    assert_eq!(truncate.evaluate("7 / 0"), Ok(f64::INFINITY));
+
+    let mut fractions = evaluator(IntegerDivision::Promote, NumberMode::Fraction);
+    assert_eq!(
+      fractions.evaluate_typed("7/2"),
+      Ok(Value::Rational(Rational::new(7, 2).unwrap()))
+    );
+    let mut fractions = evaluator(IntegerDivision::Truncate, NumberMode::Fraction);
+    assert_eq!(fractions.evaluate_typed("7/2"), Ok(Value::Integer(3)));
+  }
+
+  #[test]
+  fn test_boolean_arithmetic() {
+    let evaluator = |boolean_arithmetic| {
+      Evaluator::with_config(Config {
+        boolean_arithmetic,
+        ..Config::default()
+      })
+    };
+    let mut coerce = evaluator(BooleanArithmetic::Coerce);
+    assert_eq!(
+      coerce.evaluate_typed("(3>2) + (1>5)"),
+      Ok(Value::Integer(1))
+    );
+    assert_eq!(coerce.evaluate("mean(1 < 2, 0)"), Ok(0.5));
+
+    let mut strict = evaluator(BooleanArithmetic::Strict);
+    let unsupported = |name: &str| {
+      Err(CalcError::Unsupported(format!(
+        "'{}' can't be applied to true or false with strict-bool",
+        name
+      )))
+    };
+    assert_eq!(strict.evaluate("(3>2) + (1>5)"), unsupported("+"));
+    assert_eq!(strict.evaluate("-(3>2)"), unsupported("-"));
+    assert_eq!(strict.evaluate("abs(1 < 2)"), unsupported("abs"));
+    assert_eq!(strict.evaluate("mean([1, 1 < 2])"), unsupported("mean"));
+    assert_eq!(
+      strict.evaluate_typed("(3>2) == (1<5)"),
+      Ok(Value::Boolean(true))
+    );
+    assert_eq!(strict.evaluate("if(3 > 2, 10, 20)"), Ok(10.0));
+    assert_eq!(strict.evaluate("1 + 2"), Ok(3.0));
+    assert_eq!("strict-bool".parse(), Ok(BooleanArithmetic::Strict));
+    assert!("strict".parse::<BooleanArithmetic>().is_err());
+  }
+
+  #[test]
+  fn test_zero_power() {
+    let evaluator = |zero_power, strict_zero_power| {
+      Evaluator::with_config(Config {
+        zero_power,
+        strict_zero_power,
+        ..Config::default()
+      })
+    };
+    let mut default = Evaluator::new();
+    assert_eq!(default.evaluate("0^0"), Ok(1.0));
+    assert_eq!(default.evaluate("0^-1"), Ok(f64::INFINITY));
+    assert_eq!(default.evaluate("(-0)^-1"), Ok(f64::NEG_INFINITY));
+
+    let mut error = evaluator(ZeroPower::Error, false);
+    assert_eq!(
+      error.evaluate("0^0"),
+      Err(CalcError::Indeterminate("0 ^ 0".to_string()))
+    );
+    assert_eq!(
+      error.evaluate("0.0^(1 - 1)").unwrap_err().to_string(),
+      "0 ^ 0 is indeterminate"
+    );
+    assert_eq!(error.evaluate("0^-1"), Ok(f64::INFINITY));
+    assert_eq!(error.evaluate("2^0 + 0^2"), Ok(1.0));
+
+    let mut nan = evaluator(ZeroPower::Nan, false);
+    assert!(nan.evaluate("0^0").unwrap().is_nan());
+    assert_eq!(nan.evaluate("0^-1"), Ok(f64::INFINITY));
+    assert_eq!(nan.evaluate("5^0"), Ok(1.0));
+
+    let mut strict = evaluator(ZeroPower::One, true);
+    assert_eq!(strict.evaluate("0^0"), Ok(1.0));
+    assert_eq!(strict.evaluate("0^-1"), Err(CalcError::DivisionByZero));
+    assert_eq!(strict.evaluate("0^-0.5"), Err(CalcError::DivisionByZero));
+    assert_eq!(strict.evaluate("0^2"), Ok(0.0));
+    assert_eq!(strict.evaluate("2^-1"), Ok(0.5));
+    let mut both = evaluator(ZeroPower::Error, true);
+    assert!(matches!(
+      both.evaluate("0^0"),
+      Err(CalcError::Indeterminate(_))
+    ));
+    assert_eq!(both.evaluate("0^-2"), Err(CalcError::DivisionByZero));
+
+    assert_eq!("nan".parse(), Ok(ZeroPower::Nan));
+    assert!("zero".parse::<ZeroPower>().is_err());
+  }
+
+  #[test]
+  fn test_nan_policy() {
+    let mut propagate = Evaluator::new();
+    assert!(propagate.evaluate("0 * (1/0) + 1").unwrap().is_nan());
+    assert!(propagate.evaluate("sqrt(-1) * 2").unwrap().is_nan());
+
+    let mut error = Evaluator::with_config(Config {
+      nan_policy: NanPolicy::Error,
+      ..Config::default()
+    });
+    assert_eq!(
+      error.evaluate("0 * (1/0) + 1"),
+      Err(CalcError::NotANumber("0 * inf".to_string()))
+    );
+    assert_eq!(
+      error.evaluate("2 + sqrt(-1) * 2"),
+      Err(CalcError::NotANumber("sqrt(-1)".to_string()))
+    );
+    assert_eq!(
+      error.evaluate("(1/0) - (1/0)").unwrap_err().to_string(),
+      "inf - inf is not a number"
+    );
+    assert_eq!(error.evaluate("1/0"), Ok(f64::INFINITY));
+    assert_eq!(error.evaluate("2 * 3"), Ok(6.0));
+  }
+
+  #[test]
+  fn test_first_non_finite_step() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.evaluate("10^308 * 10 * 10"), Ok(f64::INFINITY));
+    assert_eq!(
+      evaluator.first_non_finite().map(ToString::to_string),
+      Some("1e308 * 10 = inf".to_string())
+    );
+    evaluator.evaluate("1 + sqrt(0 - 1)").unwrap();
+    assert_eq!(
+      evaluator.first_non_finite().map(ToString::to_string),
+      Some("sqrt(-1) = NaN".to_string())
+    );
+    evaluator.evaluate("2^10").unwrap();
+    assert_eq!(evaluator.first_non_finite(), None);
+  }
+
+  #[test]
+  fn test_registered_functions() {
+    let mut evaluator = Evaluator::new();
+    evaluator.register_fn("double", 1, |args| args[0] * 2.0);
+    evaluator.register_fn("sum3", 3, |args| args.iter().sum());
+    assert_eq!(evaluator.evaluate("double(4) + 1"), Ok(9.0));
+    assert_eq!(evaluator.evaluate("sum3(1, double(2), 3)"), Ok(8.0));
+    assert_eq!(
+      evaluator.evaluate("double(1, 2)"),
+      Err(CalcError::ArityMismatch {
+        function: "double".to_string(),
+        expected: 1,
+        found: 2
+      })
+    );
+  }
+
+  #[test]
+  fn test_registered_functions_shadow_builtins() {
+    let mut evaluator = Evaluator::new();
+    evaluator.register_fn("sqrt", 1, |_| 42.0);
+    assert_eq!(evaluator.evaluate("sqrt(4)"), Ok(42.0));
+    assert_eq!(Evaluator::new().evaluate("sqrt(4)"), Ok(2.0));
+  }
+
+  #[test]
+  fn test_builtin_constants() {
+    let mut evaluator = Evaluator::new();
+    assert!((evaluator.evaluate("phi").unwrap() - 1.618).abs() < 1e-3);
+    assert_eq!(evaluator.evaluate("phi^2 - phi"), Ok(1.0));
+    assert_eq!(evaluator.evaluate("sqrt2^2"), Ok(2.0000000000000004));
+    assert_eq!(evaluator.evaluate("2gamma_c"), Ok(1.1544313298030657));
+    assert_eq!(
+      evaluator.evaluate("phi = 2"),
+      Err(CalcError::ConstantAssignment("phi".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_registered_constants() {
+    let mut evaluator = Evaluator::new();
+    evaluator.register_const("golden", 1.618);
+    assert_eq!(evaluator.evaluate("2*golden"), Ok(3.236));
+    assert_eq!(evaluator.constant("golden"), Some(1.618));
+    assert_eq!(
+      evaluator.evaluate("golden = 2"),
+      Err(CalcError::ConstantAssignment("golden".to_string()))
+    );
+    assert_eq!(
+      evaluator.evaluate("golden += 1"),
+      Err(CalcError::ConstantAssignment("golden".to_string()))
+    );
+    assert_eq!(evaluator.variable("golden"), None);
+  }
+
+  #[test]
+  fn test_token_count() {
+    let evaluator = Evaluator::new();
+    assert_eq!(evaluator.token_count("2+3*(4-1)"), Ok(9));
+    assert_eq!(evaluator.token_count("2(3)"), Ok(5));
+    assert_eq!(evaluator.token_count("max(1, 2)"), Ok(6));
+    assert_eq!(evaluator.token_count("   "), Ok(0));
+    assert_eq!(evaluator.token_count("(1"), Ok(2));
+    assert!(evaluator.token_count("1.2.3").is_err());
+  }
+
+  #[test]
+  fn test_unfinished_expressions() {
+    let evaluator = Evaluator::new();
+    for unfinished in [
+      "5 +",
+      "2 * (3 -",
+      "x =",
+      "y += ",
+      "1 <",
+      "3 * -",
+      "x = 1; 2 ^",
+    ] {
+      assert!(evaluator.is_unfinished(unfinished), "{}", unfinished);
+    }
+    for finished in ["5 + 1", "+1", "(2", "x = 5;", "sqrt(", "", "1.2.3 +"] {
+      assert!(!evaluator.is_unfinished(finished), "{}", finished);
+    }
+  }
+
+  #[test]
+  fn test_max_paren_depth() {
+    let evaluator = Evaluator::new();
+    assert_eq!(evaluator.max_paren_depth("1+2"), Ok(0));
+    assert_eq!(evaluator.max_paren_depth("((1+2))"), Ok(2));
+    assert_eq!(evaluator.max_paren_depth("(1)*(2*(3))"), Ok(2));
+    assert_eq!(evaluator.max_paren_depth("max(⌊[1, (2)]⌋)"), Ok(4));
+    for unbalanced in ["(1", "1)", ")(", "(1]", "⌊1⌉"] {
+      assert_eq!(
+        evaluator.max_paren_depth(unbalanced),
+        Err(CalcError::MismatchedParentheses)
+      );
+    }
+
+    let lenient = Evaluator::with_config(Config {
+      auto_close: true,
+      ..Config::default()
+    });
+    assert_eq!(lenient.max_paren_depth("((1+2"), Ok(2));
+  }
+
+  #[test]
+  fn test_ast_json() {
+    let evaluator = Evaluator::new();
+    assert_eq!(
+      evaluator.ast_json("2+3*4"),
+      Ok(
+        concat!(
+          r#"{"type":"binary","operator":"+","left":{"type":"number","value":2},"right":"#,
+          r#"{"type":"binary","operator":"*","left":{"type":"number","value":3},"right":"#,
+          r#"{"type":"number","value":4}}}"#
+        )
+        .to_string()
+      )
+    );
+    assert_eq!(
+      evaluator.ast_json("-max(x, pi)"),
+      Ok(
+        concat!(
+          r#"{"type":"unary","operator":"-","operand":{"type":"call","name":"max","arguments":"#,
+          r#"[{"type":"variable","name":"x"},{"type":"constant","name":"pi"}]}}"#
+        )
+        .to_string()
+      )
+    );
+    assert_eq!(
+      evaluator.ast_json("y += [1, \"a\"]"),
+      Ok(
+        concat!(
+          r#"{"type":"assignment","name":"y","operator":"+=","value":{"type":"list","items":"#,
+          r#"[{"type":"number","value":1},{"type":"text","value":"a"}]}}"#
+        )
+        .to_string()
+      )
+    );
+    assert_eq!(
+      evaluator.ast_json("(2"),
+      Err(CalcError::MismatchedParentheses)
+    );
+  }
+
+  #[test]
+  fn test_simplify_steps() {
+    let mut evaluator = Evaluator::new();
+    let steps = |evaluator: &mut Evaluator, expression| evaluator.simplify_steps(expression);
+    assert_eq!(
+      steps(&mut evaluator, "2+3*4"),
+      Ok(vec![
+        "2 + 3 * 4".to_string(),
+        "2 + 12".to_string(),
+        "14".to_string()
+      ])
+    );
+    assert_eq!(
+      steps(&mut evaluator, "(1 - 3)^2 + sqrt(16)"),
+      Ok(vec![
+        "(1 - 3) ^ 2 + sqrt(16)".to_string(),
+        "(-2) ^ 2 + sqrt(16)".to_string(),
+        "4 + sqrt(16)".to_string(),
+        "4 + 4".to_string(),
+        "8".to_string()
+      ])
+    );
+    assert_eq!(
+      steps(&mut evaluator, "x*(2+3) + 2*pi"),
+      Ok(vec![
+        "x * (2 + 3) + 2 * pi".to_string(),
+        "x * 5 + 2 * pi".to_string()
+      ])
+    );
+    assert_eq!(steps(&mut evaluator, "7"), Ok(vec!["7".to_string()]));
+    assert_eq!(
+      steps(&mut evaluator, "1 + 7//0"),
+      Err(CalcError::DivisionByZero)
+    );
+    assert_eq!(evaluator.simplify_steps("x"), Ok(vec!["x".to_string()]));
+  }
+
+  #[test]
+  fn test_normalize() {
+    let evaluator = Evaluator::new();
+    let normalize = |expression| evaluator.normalize(expression).unwrap();
+    assert_eq!(normalize("2(3+4)"), "2 * (3 + 4)");
+    assert_eq!(normalize("  1+2*3 "), "1 + 2 * 3");
+    assert_eq!(normalize("((1+2))*3"), "(1 + 2) * 3");
+    assert_eq!(normalize("(1+2)+3"), "1 + 2 + 3");
+    assert_eq!(normalize("1-(2-3)"), "1 - (2 - 3)");
+    assert_eq!(normalize("(2^3)^2"), "(2 ^ 3) ^ 2");
+    assert_eq!(normalize("2^(3^2)"), "2 ^ 3 ^ 2");
+    assert_eq!(normalize("(-2)^2"), "(-2) ^ 2");
+    assert_eq!(normalize("-(2^2)"), "-2 ^ 2");
+    assert_eq!(normalize("-(1+x)*2pi"), "-(1 + x) * 2 * pi");
+    assert_eq!(normalize(r#"convert(1,"a\"b")"#), r#"convert(1, "a\"b")"#);
+    assert_eq!(normalize("x+=max(1,2)/4"), "x += max(1, 2) / 4");
+    assert_eq!(normalize("⌊3.7⌋+0.50"), "floor(3.7) + 0.5");
+    assert_eq!(
+      evaluator.normalize("2+"),
+      evaluator.validate("2+").map(|_| String::new())
+    );
+  }
+
+  #[test]
+  fn test_aliases() {
+    let mut evaluator = Evaluator::new();
+    evaluator.define_alias("k", "*1000");
+    evaluator.define_alias("half", "/2");
+    assert_eq!(evaluator.evaluate("5k"), Ok(5000.0));
+    assert_eq!(evaluator.evaluate("3k half"), Ok(1500.0));
+    assert_eq!(
+      evaluator.evaluate("kilo"),
+      Err(CalcError::UnknownVariable("kilo".to_string()))
+    );
+    evaluator.evaluate("k2 = 4").unwrap();
+    assert_eq!(
+      evaluator.evaluate("k2k"),
+      Err(CalcError::UnknownVariable("k2k".to_string()))
+    );
+    assert_eq!(evaluator.evaluate("k2 * 2"), Ok(8.0));
+  }
+
+  #[test]
+  fn test_physics_constants() {
+    let mut evaluator = Evaluator::new();
+    assert!(evaluator.evaluate("c").is_err());
+    evaluator.register_physics_constants();
+    assert_eq!(evaluator.evaluate("2*c"), Ok(599584916.0));
+    assert_eq!(evaluator.evaluate("N_A"), Ok(6.02214076e23));
+    assert_eq!(evaluator.evaluate("h"), Ok(6.62607015e-34));
+    assert_eq!(evaluator.evaluate("g"), Ok(9.80665));
+    assert_eq!(
+      evaluator.evaluate("g = 10"),
+      Err(CalcError::ConstantAssignment("g".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_allowed_constant_assignment() {
+    let mut evaluator = Evaluator::with_config(Config {
+      allow_constant_assignment: true,
+      ..Config::default()
+    });
+    evaluator.register_const("rate", 0.5);
+    assert_eq!(evaluator.evaluate("rate *= 3"), Ok(1.5));
+    assert_eq!(evaluator.constant("rate"), Some(1.5));
+    assert_eq!(
+      evaluator.evaluate("pi = 3"),
+      Err(CalcError::ConstantAssignment("pi".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_known_names() {
+    let mut evaluator = Evaluator::new();
+    evaluator.register_fn("double", 1, |args| args[0] * 2.0);
+    evaluator.register_fn("sqrt", 2, |args| args[0] + args[1]);
+    evaluator.register_const("golden", 1.618);
+    evaluator.define("cube(x) = x^3").unwrap();
+
+    let names = evaluator.known_names();
+    let function = |name: &str, arity| KnownName::Function {
+      name: name.to_string(),
+      arity: Arity::Exactly(arity),
+    };
+    assert!(names.contains(&function("double", 1)));
+    assert!(names.contains(&function("cube", 1)));
+    assert!(names.contains(&function("if", 3)));
+    assert!(names.contains(&function("pct_of", 2)));
+    assert!(names.contains(&function("sqrt", 2)));
+    assert!(!names.contains(&function("sqrt", 1)));
+    assert!(names.contains(&function("iterate", 4)));
+    let sum = names
+      .iter()
+      .find(|name| name.to_string().starts_with("sum "));
+    assert_eq!(sum.unwrap().to_string(), "sum (1 or 4 arguments)");
+    assert!(names.contains(&KnownName::Function {
+      name: TEMPLATE.to_string(),
+      arity: Arity::AtLeast(TEMPLATE_MINIMUM_ARITY)
+    }));
+    assert!(names.contains(&KnownName::Constant {
+      name: "golden".to_string(),
+      value: 1.618
+    }));
+    assert_eq!(
+      names.last().unwrap().to_string(),
+      "sqrt2 = 1.4142135623730951"
+    );
+    let built_in = BUILTINS.len()
+      + SERIES.len()
+      + TEXT_FUNCTIONS.len()
+      + RANGE_EXTREMA.len()
+      + CALCULUS.len()
+      + [CONDITIONAL, TEMPLATE].len()
+      + BUILTIN_CONSTANTS.len();
+    // `sqrt` replaces the built-in one, so only the other registered names add to it.
+    assert_eq!(names.len(), built_in + ["double", "cube", "golden"].len());
+  }
+
+  #[test]
+  fn test_fraction_mode_falls_back_to_float() {
+    let mut evaluator = fraction_evaluator();
+    assert_eq!(
+      evaluator.evaluate_typed("2^0.5"),
+      Ok(Value::Float(2f64.powf(0.5)))
+    );
+    assert_eq!(
+      evaluator.evaluate_typed("1/0"),
+      Err(CalcError::DivisionByZero)
+    );
+  }
+}
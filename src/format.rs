@@ -0,0 +1,306 @@
+use std::str::FromStr;
+
+/// How a result is rounded to a fixed number of decimal places for display.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum Rounding {
+  /// Drop the extra digits, rounding toward zero.
+  Trunc,
+  /// Round halves away from zero, so `2.5` becomes `3` and `-2.5` becomes `-3`.
+  #[default]
+  HalfUp,
+  /// Round halves to the nearest even digit (banker's rounding), so `2.5` becomes `2`.
+  HalfEven,
+}
+
+impl FromStr for Rounding {
+  type Err = String;
+
+  fn from_str(str: &str) -> Result<Rounding, String> {
+    match str {
+      "trunc" => Ok(Rounding::Trunc),
+      "half-up" => Ok(Rounding::HalfUp),
+      "half-even" => Ok(Rounding::HalfEven),
+      _ => Err(format!(
+        "Unknown rounding '{}', expected trunc, half-up or half-even",
+        str
+      )),
+    }
+  }
+}
+
+/// Round `value` to `decimals` decimal places.
+pub fn round(value: f64, decimals: u32, rounding: Rounding) -> f64 {
+  round_places(value, decimals as i32, rounding)
+}
+
+/// Round `value` to `places` decimal places, or to a multiple of `10^-places` when negative.
+///
+/// The digits rounded are those of the shortest decimal that reads back as `value`, the ones it
+/// is printed with, rather than of the float scaled by a power of ten: `0.29 * 100` is
+/// `28.999999999999996`, which would truncate to `0.28`, and scaling can overflow. A value
+/// without digits beyond `places` is returned as it is.
+fn round_places(value: f64, places: i32, rounding: Rounding) -> f64 {
+  if !value.is_finite() || value == 0.0 {
+    return value;
+  }
+  let shortest = format!("{:e}", value.abs());
+  let (mantissa, exponent) = shortest.split_once('e').expect("scientific notation");
+  let exponent: i32 = exponent.parse().expect("an integer exponent");
+  let digits: Vec<u8> = mantissa
+    .bytes()
+    .filter(u8::is_ascii_digit)
+    .map(|digit| digit - b'0')
+    .collect();
+  // The digits down to the `places`th decimal, which may be none of them or all.
+  let kept = exponent.saturating_add(1).saturating_add(places);
+  if kept >= digits.len() as i32 {
+    return value;
+  }
+  // Then the first digit dropped is a leading zero, so less than half is dropped.
+  if kept < 0 {
+    return 0f64.copysign(value);
+  }
+  let (kept, dropped) = digits.split_at(kept as usize);
+  let next = dropped[0];
+  let beyond_next = dropped[1..].iter().any(|&digit| digit != 0);
+  let last_odd = kept.last().is_some_and(|digit| digit % 2 == 1);
+  let up = match rounding {
+    Rounding::Trunc => false,
+    Rounding::HalfUp => next >= 5,
+    Rounding::HalfEven => next > 5 || next == 5 && (beyond_next || last_odd),
+  };
+  let kept = kept
+    .iter()
+    .fold(0_u64, |n, &digit| n * 10 + u64::from(digit))
+    + u64::from(up);
+  let rounded: f64 = format!("{}e{}", kept, -places).parse().expect("a decimal");
+  rounded.copysign(value)
+}
+
+/// Integers up to this magnitude are printed without a fractional part. Beyond it, floats can no
+/// longer represent every integer, so digits printed out in full would suggest precision that
+/// isn't there.
+const MAX_EXACT_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/// Format `value` the shortest way that reads back as the same number, leaving out the `.0` of
+/// integral values, so `4.0` is printed as `4` but `0.5` stays `0.5`.
+pub fn format_number(value: f64) -> String {
+  if value.fract() == 0.0 && value.abs() <= MAX_EXACT_INTEGER {
+    format!("{}", value as i64)
+  } else {
+    format!("{:?}", value)
+  }
+}
+
+/// Format `value` with the shortest digits that read back as the same number like
+/// [`format_number`], but always in positional notation, so `1e-7` is `0.0000001` and `1e20` is
+/// `100000000000000000000`.
+pub fn format_plain(value: f64) -> String {
+  if value == 0.0 {
+    return "0".to_string();
+  }
+  format!("{}", value)
+}
+
+/// Format `value` with exactly `decimals` decimal places.
+pub fn format_fixed(value: f64, decimals: u32, rounding: Rounding) -> String {
+  format!("{:.*}", decimals as usize, round(value, decimals, rounding))
+}
+
+/// From this many digits before the decimal point on, `format_significant` switches to scientific
+/// notation rather than padding with zeros.
+const MAX_PADDED_DIGITS: i32 = 16;
+
+/// Format `value` rounded to `figures` significant figures, so `12345` to three figures is `12300`
+/// and `0.0012345` is `0.00123`. Trailing zeros are kept to show the precision, so `2` is `2.00`
+/// and `0` is `0.00`. Very large values use scientific notation like `1.23e30`.
+pub fn format_significant(value: f64, figures: u32, rounding: Rounding) -> String {
+  if !value.is_finite() || figures == 0 {
+    return format_number(value);
+  }
+  if value == 0.0 {
+    return format!("{:.*}", figures as usize - 1, 0.0);
+  }
+  let magnitude = |value: f64| value.abs().log10().floor() as i32;
+  let places = figures as i32 - 1 - magnitude(value);
+  let rounded = round_places(value, places, rounding);
+  // Rounding can carry into a new leading digit, like 9.996 to 10.0, which takes up a figure.
+  let places = figures as i32 - 1 - magnitude(rounded);
+  if magnitude(rounded) >= MAX_PADDED_DIGITS {
+    format!("{:.*e}", figures as usize - 1, rounded)
+  } else {
+    format!("{:.*}", places.max(0) as usize, rounded)
+  }
+}
+
+/// Format an integer in `base`, from 2 to 36, with lowercase letters for the digits above 9. With
+/// `prefix`, bases 2, 8 and 16 start with `0b`, `0o` and `0x`, so `255` is `0xff`.
+pub fn format_radix(value: i64, base: u32, prefix: bool) -> String {
+  let mut magnitude = value.unsigned_abs();
+  let mut digits = Vec::new();
+  loop {
+    digits.push(char::from_digit((magnitude % base as u64) as u32, base).unwrap());
+    magnitude /= base as u64;
+    if magnitude == 0 {
+      break;
+    }
+  }
+  let prefix = match (prefix, base) {
+    (true, 2) => "0b",
+    (true, 8) => "0o",
+    (true, 16) => "0x",
+    _ => "",
+  };
+  let sign = if value < 0 { "-" } else { "" };
+  format!(
+    "{}{}{}",
+    sign,
+    prefix,
+    digits.iter().rev().collect::<String>()
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_significant_figures() {
+    let format = |value| format_significant(value, 3, Rounding::HalfUp);
+    assert_eq!(format(12345.0), "12300");
+    assert_eq!(format(-12345.0), "-12300");
+    assert_eq!(format(0.0012345), "0.00123");
+    assert_eq!(format(0.1), "0.100");
+    assert_eq!(format(1.0), "1.00");
+    assert_eq!(format(0.9996), "1.00");
+    assert_eq!(format(9.996), "10.0");
+    assert_eq!(format(999.6), "1000");
+    assert_eq!(format(123.456), "123");
+    assert_eq!(format(1.2345e30), "1.23e30");
+    assert_eq!(format(0.0), "0.00");
+    assert_eq!(format(-0.0), "0.00");
+    assert_eq!(format_significant(12355.0, 4, Rounding::Trunc), "12350");
+    assert_eq!(format_significant(2.5, 1, Rounding::HalfEven), "2");
+  }
+
+  #[test]
+  fn test_significant_trailing_zeros() {
+    let format = |value, figures| format_significant(value, figures, Rounding::HalfUp);
+    assert_eq!(format(2.0, 3), "2.00");
+    assert_eq!(format(1.5, 4), "1.500");
+    assert_eq!(format(-1.5, 4), "-1.500");
+    assert_eq!(format(0.25, 5), "0.25000");
+    assert_eq!(format(0.002, 2), "0.0020");
+    assert_eq!(format(40.0, 4), "40.00");
+    assert_eq!(format(1.0, 1), "1");
+    assert_eq!(format(0.0, 1), "0");
+    assert_eq!(format(0.0, 4), "0.000");
+    assert_eq!(format(2.0e20, 3), "2.00e20");
+  }
+
+  #[test]
+  fn test_format_number() {
+    assert_eq!(format_number(4.0), "4");
+    assert_eq!(format_number(-12.0), "-12");
+    assert_eq!(format_number(-0.0), "0");
+    assert_eq!(format_number(0.5), "0.5");
+    assert_eq!(format_number(1.0 / 3.0), "0.3333333333333333");
+    assert_eq!(format_number(9007199254740992.0), "9007199254740992");
+    assert_eq!(format_number(1e300), "1e300");
+    assert_eq!(format_number(f64::INFINITY), "inf");
+    assert_eq!(format_number(f64::NAN), "NaN");
+  }
+
+  #[test]
+  fn test_format_plain() {
+    assert_eq!(format_plain(0.0000001), "0.0000001");
+    assert_eq!(format_plain(-1.5e-10), "-0.00000000015");
+    assert_eq!(format_plain(1e20), "100000000000000000000");
+    assert_eq!(format_plain(2f64.powi(60)), "1152921504606847000");
+    assert_eq!(format_plain(-0.0), "0");
+    assert_eq!(format_plain(0.5), "0.5");
+    assert!(!format_plain(1e300).contains('e'));
+    assert!(!format_plain(f64::MIN_POSITIVE).contains('e'));
+    assert_eq!(format_plain(f64::INFINITY), "inf");
+  }
+
+  #[test]
+  fn test_format_radix() {
+    assert_eq!(format_radix(255, 16, true), "0xff");
+    assert_eq!(format_radix(255, 16, false), "ff");
+    assert_eq!(format_radix(8, 8, true), "0o10");
+    assert_eq!(format_radix(8, 8, false), "10");
+    assert_eq!(format_radix(5, 2, true), "0b101");
+    assert_eq!(format_radix(5, 2, false), "101");
+    assert_eq!(format_radix(0, 2, true), "0b0");
+    assert_eq!(format_radix(-255, 16, true), "-0xff");
+    assert_eq!(format_radix(35, 36, true), "z");
+    assert_eq!(format_radix(i64::MIN, 16, true), "-0x8000000000000000");
+  }
+
+  #[test]
+  fn test_trunc() {
+    assert_eq!(round(2.5, 0, Rounding::Trunc), 2.0);
+    assert_eq!(round(2.99, 1, Rounding::Trunc), 2.9);
+    assert_eq!(round(-2.5, 0, Rounding::Trunc), -2.0);
+  }
+
+  #[test]
+  fn test_rounding_inexact_decimals() {
+    // `0.29 * 100` is just below `29`, which mustn't cost a digit.
+    assert_eq!(round(0.29, 2, Rounding::Trunc), 0.29);
+    assert_eq!(round(0.57, 2, Rounding::Trunc), 0.57);
+    assert_eq!(round(-0.57, 2, Rounding::Trunc), -0.57);
+    assert_eq!(round(0.579, 2, Rounding::Trunc), 0.57);
+    assert_eq!(round(2.675, 2, Rounding::HalfUp), 2.68);
+    assert_eq!(round(1.005, 2, Rounding::HalfEven), 1.0);
+    assert_eq!(round(0.0004, 2, Rounding::HalfUp), 0.0);
+    assert_eq!(round(0.006, 2, Rounding::HalfUp), 0.01);
+    assert_eq!(round(9.995, 2, Rounding::HalfUp), 10.0);
+    assert_eq!(format_fixed(0.29, 2, Rounding::Trunc), "0.29");
+  }
+
+  #[test]
+  fn test_rounding_extremes() {
+    // Scaling `10^300` by `10^10` would overflow.
+    assert_eq!(round(1e300, 10, Rounding::HalfUp), 1e300);
+    assert_eq!(round(f64::MAX, 2, Rounding::Trunc), f64::MAX);
+    // `10^400` is infinite, which would make `1.5` NaN.
+    assert_eq!(round(1.5, 400, Rounding::HalfUp), 1.5);
+    assert_eq!(round(1.0 / 3.0, 400, Rounding::HalfEven), 1.0 / 3.0);
+    assert!(format_fixed(1.5, 400, Rounding::HalfUp).starts_with("1.5000"));
+    assert_eq!(round(f64::INFINITY, 2, Rounding::HalfUp), f64::INFINITY);
+    assert!(round(f64::NAN, 2, Rounding::HalfUp).is_nan());
+  }
+
+  #[test]
+  fn test_half_up() {
+    assert_eq!(round(2.5, 0, Rounding::HalfUp), 3.0);
+    assert_eq!(round(3.5, 0, Rounding::HalfUp), 4.0);
+    assert_eq!(round(-2.5, 0, Rounding::HalfUp), -3.0);
+    assert_eq!(round(0.25, 1, Rounding::HalfUp), 0.3);
+  }
+
+  #[test]
+  fn test_half_even() {
+    assert_eq!(round(2.5, 0, Rounding::HalfEven), 2.0);
+    assert_eq!(round(3.5, 0, Rounding::HalfEven), 4.0);
+    assert_eq!(round(-2.5, 0, Rounding::HalfEven), -2.0);
+    assert_eq!(round(0.25, 1, Rounding::HalfEven), 0.2);
+    assert_eq!(round(2.6, 0, Rounding::HalfEven), 3.0);
+  }
+
+  #[test]
+  fn test_format_fixed() {
+    assert_eq!(format_fixed(2.5, 0, Rounding::HalfEven), "2");
+    assert_eq!(format_fixed(2.5, 0, Rounding::HalfUp), "3");
+    assert_eq!(format_fixed(1.0 / 3.0, 3, Rounding::Trunc), "0.333");
+    assert_eq!(format_fixed(2.0, 2, Rounding::HalfUp), "2.00");
+  }
+
+  #[test]
+  fn test_parse_rounding() {
+    assert_eq!("half-even".parse(), Ok(Rounding::HalfEven));
+    assert!("nearest".parse::<Rounding>().is_err());
+  }
+}
@@ -0,0 +1,1703 @@
+use std::fmt;
+
+use crate::error::CalcError;
+use crate::evaluator::{Config, IntegerPolicy, DEFAULT_EPSILON};
+use crate::rational::Rational;
+use crate::value::Value;
+
+/// How many arguments a function takes.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Arity {
+  Exactly(usize),
+  /// Any number of arguments from the given minimum. Lists passed to these are spread into
+  /// separate arguments, so `mean([1, 2], 3)` is `mean(1, 2, 3)`.
+  AtLeast(usize),
+  /// From the first number of arguments to the second, the later ones optional.
+  Between(usize, usize),
+  /// Either the first number of arguments or the second, like `sum` of a list or over a range.
+  Either(usize, usize),
+}
+
+impl Arity {
+  pub fn accepts(self, count: usize) -> bool {
+    match self {
+      Arity::Exactly(arity) => count == arity,
+      Arity::AtLeast(minimum) => count >= minimum,
+      Arity::Between(minimum, maximum) => (minimum..=maximum).contains(&count),
+      Arity::Either(fewer, more) => count == fewer || count == more,
+    }
+  }
+
+  /// The fewest arguments accepted.
+  pub fn minimum(self) -> usize {
+    match self {
+      Arity::Exactly(count)
+      | Arity::AtLeast(count)
+      | Arity::Between(count, _)
+      | Arity::Either(count, _) => count,
+    }
+  }
+
+  /// The number of arguments closest to `count` that would be accepted, to report when `count`
+  /// isn't.
+  pub fn expected(self, count: usize) -> usize {
+    match self {
+      Arity::Between(_, maximum) if count > maximum => maximum,
+      Arity::Either(fewer, more) if count > fewer => more,
+      arity => arity.minimum(),
+    }
+  }
+
+  /// Fail unless `count` arguments are accepted by `function`, with [`Arity::mismatch`].
+  pub(crate) fn check(self, function: &str, count: usize) -> Result<(), CalcError> {
+    if self.accepts(count) {
+      Ok(())
+    } else {
+      Err(self.mismatch(function, count))
+    }
+  }
+
+  /// The error for `function` given `count` arguments it doesn't accept:
+  /// [`CalcError::NotEnoughArguments`] for too few to a function taking any number from a
+  /// minimum, [`CalcError::ArityChoiceMismatch`] for one taking either of two numbers, and
+  /// [`CalcError::ArityMismatch`] with the closest accepted count otherwise.
+  pub(crate) fn mismatch(self, function: &str, count: usize) -> CalcError {
+    let function = function.to_string();
+    match self {
+      Arity::AtLeast(minimum) => CalcError::NotEnoughArguments {
+        function,
+        minimum,
+        found: count,
+      },
+      Arity::Either(fewer, more) => CalcError::ArityChoiceMismatch {
+        function,
+        expected: (fewer, more),
+        found: count,
+      },
+      _ => CalcError::ArityMismatch {
+        function,
+        expected: self.expected(count),
+        found: count,
+      },
+    }
+  }
+}
+
+impl fmt::Display for Arity {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Arity::Exactly(1) => write!(f, "1 argument"),
+      Arity::Exactly(count) => write!(f, "{} arguments", count),
+      Arity::AtLeast(1) => write!(f, "at least 1 argument"),
+      Arity::AtLeast(minimum) => write!(f, "at least {} arguments", minimum),
+      Arity::Between(minimum, maximum) => write!(f, "{} to {} arguments", minimum, maximum),
+      Arity::Either(fewer, more) => write!(f, "{} or {} arguments", fewer, more),
+    }
+  }
+}
+
+/// A function every expression can call.
+pub(crate) struct Builtin {
+  pub(crate) name: &'static str,
+  pub(crate) arity: Arity,
+  angle: Angle,
+  /// Only ever called with a number of arguments `arity` accepts, with angles in radians except
+  /// for [`Angle::Turn`].
+  function: Implementation,
+}
+
+enum Implementation {
+  Real(fn(&[f64]) -> Result<f64, CalcError>),
+  /// Functions only defined for integers, whose arguments are converted according to the
+  /// configured [`IntegerPolicy`].
+  Integer(fn(&[i64]) -> Result<f64, CalcError>),
+  /// Integer functions whose result is text.
+  Text(fn(&[i64]) -> Result<String, CalcError>),
+}
+
+/// Where a function deals in angles, which are converted from and to the configured units.
+#[derive(Copy, Clone, PartialEq)]
+enum Angle {
+  Neither,
+  /// Forward trigonometric functions take an angle.
+  Argument,
+  /// Inverse trigonometric functions return one.
+  Result,
+  /// Functions from an angle to an angle, which get it in the input unit along with the size of a
+  /// full turn in that unit, so degrees stay exact.
+  Turn,
+}
+
+const fn builtin(
+  name: &'static str,
+  arity: usize,
+  function: fn(&[f64]) -> Result<f64, CalcError>,
+) -> Builtin {
+  Builtin {
+    name,
+    arity: Arity::Exactly(arity),
+    angle: Angle::Neither,
+    function: Implementation::Real(function),
+  }
+}
+
+const fn variadic(
+  name: &'static str,
+  minimum: usize,
+  function: fn(&[f64]) -> Result<f64, CalcError>,
+) -> Builtin {
+  Builtin {
+    name,
+    arity: Arity::AtLeast(minimum),
+    angle: Angle::Neither,
+    function: Implementation::Real(function),
+  }
+}
+
+const fn optional(
+  name: &'static str,
+  minimum: usize,
+  maximum: usize,
+  function: fn(&[f64]) -> Result<f64, CalcError>,
+) -> Builtin {
+  Builtin {
+    name,
+    arity: Arity::Between(minimum, maximum),
+    angle: Angle::Neither,
+    function: Implementation::Real(function),
+  }
+}
+
+const fn integer(
+  name: &'static str,
+  arity: usize,
+  function: fn(&[i64]) -> Result<f64, CalcError>,
+) -> Builtin {
+  Builtin {
+    name,
+    arity: Arity::Exactly(arity),
+    angle: Angle::Neither,
+    function: Implementation::Integer(function),
+  }
+}
+
+const fn integer_variadic(
+  name: &'static str,
+  minimum: usize,
+  function: fn(&[i64]) -> Result<f64, CalcError>,
+) -> Builtin {
+  Builtin {
+    name,
+    arity: Arity::AtLeast(minimum),
+    angle: Angle::Neither,
+    function: Implementation::Integer(function),
+  }
+}
+
+const fn text(
+  name: &'static str,
+  arity: usize,
+  function: fn(&[i64]) -> Result<String, CalcError>,
+) -> Builtin {
+  Builtin {
+    name,
+    arity: Arity::Exactly(arity),
+    angle: Angle::Neither,
+    function: Implementation::Text(function),
+  }
+}
+
+const fn angular(
+  name: &'static str,
+  angle: Angle,
+  function: fn(&[f64]) -> Result<f64, CalcError>,
+) -> Builtin {
+  Builtin {
+    name,
+    arity: Arity::Exactly(1),
+    angle,
+    function: Implementation::Real(function),
+  }
+}
+
+pub(crate) const BUILTINS: &[Builtin] = &[
+  builtin("sqrt", 1, |x| Ok(x[0].sqrt())),
+  builtin("abs", 1, |x| Ok(x[0].abs())),
+  builtin("recip", 1, |x| {
+    if x[0] == 0.0 {
+      return Err(CalcError::DivisionByZero);
+    }
+    Ok(1.0 / x[0])
+  }),
+  builtin("neg", 1, |x| Ok(-x[0])),
+  builtin("floor", 1, |x| Ok(x[0].floor())),
+  builtin("ceil", 1, |x| Ok(x[0].ceil())),
+  builtin("round", 1, |x| Ok(x[0].round())),
+  // Snapping to the nearest multiple of a step, or the one below or above.
+  builtin("roundto", 2, |x| snap(x[0], x[1], f64::round)),
+  builtin("floorto", 2, |x| snap(x[0], x[1], f64::floor)),
+  builtin("ceilto", 2, |x| snap(x[0], x[1], f64::ceil)),
+  // Truncation toward zero, which is where these two differ from floor and ceil.
+  builtin("int", 1, |x| Ok(x[0].trunc())),
+  builtin("frac", 1, |x| Ok(x[0] - x[0].trunc())),
+  angular("sin", Angle::Argument, |x| Ok(x[0].sin())),
+  angular("cos", Angle::Argument, |x| Ok(x[0].cos())),
+  angular("tan", Angle::Argument, |x| Ok(x[0].tan())),
+  angular("asin", Angle::Result, |x| {
+    if x[0].abs() > 1.0 {
+      return Err(domain_error("asin", x[0]));
+    }
+    Ok(x[0].asin())
+  }),
+  angular("acos", Angle::Result, |x| {
+    if x[0].abs() > 1.0 {
+      return Err(domain_error("acos", x[0]));
+    }
+    Ok(x[0].acos())
+  }),
+  angular("atan", Angle::Result, |x| Ok(x[0].atan())),
+  // The same angle from zero up to a full turn.
+  angular("normalize_angle", Angle::Turn, |x| {
+    Ok(x[0].rem_euclid(x[1]))
+  }),
+  // The same angle from just above minus half a turn up to half a turn.
+  angular("normalize_angle_signed", Angle::Turn, |x| {
+    let angle = x[0].rem_euclid(x[1]);
+    Ok(if angle > x[1] / 2.0 {
+      angle - x[1]
+    } else {
+      angle
+    })
+  }),
+  builtin("to_radians", 1, |x| Ok(x[0].to_radians())),
+  builtin("to_degrees", 1, |x| Ok(x[0].to_degrees())),
+  builtin("ln", 1, |x| Ok(x[0].ln())),
+  builtin("log2", 1, |x| {
+    if x[0] <= 0.0 {
+      return Err(domain_error("log2", x[0]));
+    }
+    Ok(x[0].log2())
+  }),
+  // How many digits the integer part has, so every number below 10 in size has one.
+  builtin("ndigits", 1, |x| {
+    let size = x[0].abs();
+    if !size.is_finite() {
+      return Err(domain_error("ndigits", x[0]));
+    }
+    Ok(if size < 1.0 {
+      1.0
+    } else {
+      size.log10().floor() + 1.0
+    })
+  }),
+  builtin("is_pow2", 1, |x| Ok(is_power_of_two(x[0]) as u8 as f64)),
+  builtin("exp", 1, |x| Ok(x[0].exp())),
+  integer_variadic("gcd", 2, |n| {
+    Ok(
+      n.iter()
+        .fold(0, |divisor, &b| gcd(divisor, b.unsigned_abs())) as f64,
+    )
+  }),
+  integer_variadic("lcm", 2, |n| Ok(lcm(n))),
+  // How often events repeating every so many days, say, all fall on the same day again.
+  integer_variadic("schedule", 2, |n| {
+    if let Some(&period) = n.iter().find(|&&period| period <= 0) {
+      return Err(domain_error("schedule", period as f64));
+    }
+    Ok(lcm(n))
+  }),
+  // Division truncating toward zero, so the remainder takes the sign of the dividend and
+  // `quot(a, b) * b + rem(a, b)` is always `a`.
+  integer("quot", 2, |n| {
+    if n[1] == 0 {
+      return Err(CalcError::DivisionByZero);
+    }
+    // Only `i64::MIN / -1` overflows, and its quotient is exact as a float.
+    Ok(
+      n[0]
+        .checked_div(n[1])
+        .map_or(-(n[0] as f64), |quotient| quotient as f64),
+    )
+  }),
+  integer("rem", 2, |n| {
+    if n[1] == 0 {
+      return Err(CalcError::DivisionByZero);
+    }
+    Ok(n[0].wrapping_rem(n[1]) as f64)
+  }),
+  // `1` if `a` divides `b` evenly, otherwise `0`.
+  integer("divides", 2, |n| {
+    if n[0] == 0 {
+      return Err(CalcError::DivisionByZero);
+    }
+    Ok(f64::from(u8::from(n[1].wrapping_rem(n[0]) == 0)))
+  }),
+  integer("modinv", 2, |n| modular_inverse(n[0], n[1])),
+  // The set bits of the 64-bit two's complement, like `~`, so `popcount(-1)` is 64.
+  integer("popcount", 1, |n| Ok(n[0].count_ones() as f64)),
+  // The bits needed to write a non-negative integer in binary, `floor(log2(n)) + 1`, counted
+  // exactly. `0` needs none.
+  integer("bitlen", 1, |n| {
+    if n[0] < 0 {
+      return Err(domain_error("bitlen", n[0] as f64));
+    }
+    Ok((i64::BITS - n[0].leading_zeros()) as f64)
+  }),
+  integer("digitsum", 1, |n| Ok(digit_sum(n[0]) as f64)),
+  integer("fib", 1, |n| {
+    if n[0] < 0 {
+      return Err(domain_error("fib", n[0] as f64));
+    }
+    Ok(fibonacci(n[0] as u64))
+  }),
+  integer("factorial", 1, |n| {
+    if n[0] < 0 {
+      return Err(domain_error("factorial", n[0] as f64));
+    }
+    Ok((1..=n[0]).map(|i| i as f64).product())
+  }),
+  builtin("pct_change", 2, |x| {
+    let [old, new] = [x[0], x[1]];
+    if old == 0.0 {
+      return Err(CalcError::DivisionByZero);
+    }
+    Ok((new - old) / old * 100.0)
+  }),
+  builtin("pct_of", 2, |x| {
+    let [part, whole] = [x[0], x[1]];
+    if whole == 0.0 {
+      return Err(CalcError::DivisionByZero);
+    }
+    Ok(part / whole * 100.0)
+  }),
+  // `principal` after `years` at a yearly `rate` like `0.05`, compounded `times` a year. A rate
+  // of `-1` or below would lose more than everything.
+  builtin("compound", 4, |x| {
+    let [principal, rate, times, years] = [x[0], x[1], x[2], x[3]];
+    if times < 1.0 || times.fract() != 0.0 || !times.is_finite() {
+      return Err(domain_error("compound", times));
+    }
+    if rate <= -1.0 || !rate.is_finite() {
+      return Err(domain_error("compound", rate));
+    }
+    Ok(principal * (1.0 + rate / times).powf(times * years))
+  }),
+  // Whether `a` and `b` are at most `epsilon` apart, which is optional, as `1` or `0`.
+  optional("approx", 2, 3, |x| {
+    let epsilon = x.get(2).copied().unwrap_or(DEFAULT_EPSILON);
+    if epsilon < 0.0 {
+      return Err(domain_error("approx", epsilon));
+    }
+    Ok(f64::from((x[0] - x[1]).abs() <= epsilon))
+  }),
+  builtin("prevpow", 2, |x| prevpow(x[0], x[1])),
+  // `sqrt(a^2 + b^2)` without squaring, which would overflow for large `a` or `b`.
+  builtin("hypot", 2, |x| Ok(x[0].hypot(x[1]))),
+  // The distance between two points in the plane or in space, by way of hypot for the same reason.
+  builtin("dist", 4, |x| Ok((x[2] - x[0]).hypot(x[3] - x[1]))),
+  builtin("dist3", 6, |x| {
+    Ok((x[3] - x[0]).hypot(x[4] - x[1]).hypot(x[5] - x[2]))
+  }),
+  // Linear interpolation from `a` at `t = 0` to `b` at `t = 1`, extrapolating beyond them.
+  builtin("lerp", 3, |x| Ok(lerp(x[0], x[1], x[2]))),
+  // The same with `t` clamped to `[0, 1]`, so it never leaves the range from `a` to `b`.
+  builtin("clamplerp", 3, |x| {
+    Ok(lerp(x[0], x[1], x[2].clamp(0.0, 1.0)))
+  }),
+  // `value` moved from the range `in_lo` to `in_hi` to the same place in `out_lo` to `out_hi`,
+  // extrapolating outside it.
+  builtin("map", 5, |x| {
+    let [value, in_lo, in_hi, out_lo, out_hi] = [x[0], x[1], x[2], x[3], x[4]];
+    if in_lo == in_hi {
+      return Err(CalcError::DivisionByZero);
+    }
+    Ok(lerp(out_lo, out_hi, (value - in_lo) / (in_hi - in_lo)))
+  }),
+  // Where `value` falls from `lo` to `hi` as a fraction, clamped to the range.
+  builtin("norm", 3, |x| {
+    let [value, lo, hi] = [x[0], x[1], x[2]];
+    if lo == hi {
+      return Err(CalcError::DivisionByZero);
+    }
+    Ok(((value - lo) / (hi - lo)).clamp(0.0, 1.0))
+  }),
+  // `value` wrapped around into `[lo, hi)`, the way hours wrap around a clock.
+  builtin("wrap", 3, |x| {
+    let [value, lo, hi] = [x[0], x[1], x[2]];
+    if lo >= hi {
+      return Err(CalcError::InvalidRange(format!(
+        "the start {} isn't less than the end {}",
+        lo, hi
+      )));
+    }
+    Ok(lo + (value - lo).rem_euclid(hi - lo))
+  }),
+  builtin("binompdf", 3, |x| binomial_probability(x[0], x[1], x[2])),
+  // The sum of the first `n` terms of `a, a + d, a + 2d, ...`.
+  builtin("arithsum", 3, |x| {
+    let [a, d, n] = [x[0], x[1], term_count("arithsum", x[2])?];
+    Ok(n / 2.0 * (2.0 * a + (n - 1.0) * d))
+  }),
+  // The sum of the first `n` terms of `a, ar, ar^2, ...`.
+  builtin("geomsum", 3, |x| {
+    let [a, r, n] = [x[0], x[1], term_count("geomsum", x[2])?];
+    if r == 1.0 {
+      return Ok(a * n);
+    }
+    Ok(a * (1.0 - r.powf(n)) / (1.0 - r))
+  }),
+  text("roman", 1, |n| roman(n[0])),
+  text("reduce", 2, |n| {
+    if n[1] == 0 {
+      return Err(CalcError::DivisionByZero);
+    }
+    Rational::new(n[0], n[1])
+      .map(|fraction| fraction.to_string())
+      .ok_or_else(|| domain_error("reduce", n[0] as f64))
+  }),
+  variadic("mean", 1, |x| Ok(x.iter().sum::<f64>() / x.len() as f64)),
+  // Multiplying the roots rather than taking the root of the product can't overflow.
+  variadic("gmean", 1, |x| {
+    positive("gmean", x)?;
+    let exponent = 1.0 / x.len() as f64;
+    Ok(x.iter().map(|x| x.powf(exponent)).product())
+  }),
+  variadic("hmean", 1, |x| {
+    positive("hmean", x)?;
+    Ok(x.len() as f64 / x.iter().map(|x| x.recip()).sum::<f64>())
+  }),
+  variadic("rms", 1, |x| Ok(root_mean_square(x))),
+  variadic("max", 1, |x| {
+    Ok(x[extreme_index(x, |x, extreme| x > extreme)])
+  }),
+  variadic("min", 1, |x| {
+    Ok(x[extreme_index(x, |x, extreme| x < extreme)])
+  }),
+  variadic("argmax", 1, |x| {
+    Ok((extreme_index(x, |x, extreme| x > extreme) + 1) as f64)
+  }),
+  variadic("argmin", 1, |x| {
+    Ok((extreme_index(x, |x, extreme| x < extreme) + 1) as f64)
+  }),
+  variadic("wavg", 2, weighted_average),
+  variadic("percentile", 2, |x| percentile(x[0], &x[1..])),
+  // The polynomial with coefficients from the constant term up, at `x`, by Horner's method.
+  variadic("poly", 2, |x| {
+    Ok(x[1..].iter().rev().fold(0.0, |sum, c| sum * x[0] + c))
+  }),
+];
+
+/// The sum of the decimal digits of `n`, ignoring its sign.
+fn digit_sum(n: i64) -> u64 {
+  let mut n = n.unsigned_abs();
+  let mut sum = 0;
+  while n > 0 {
+    sum += n % 10;
+    n /= 10;
+  }
+  sum
+}
+
+/// The index of the number `beats` every other, the first one on a tie.
+fn extreme_index(numbers: &[f64], beats: fn(f64, f64) -> bool) -> usize {
+  let mut best = 0;
+  for (i, &x) in numbers.iter().enumerate().skip(1) {
+    if beats(x, numbers[best]) {
+      best = i;
+    }
+  }
+  best
+}
+
+/// The square root of the mean of the squares. The numbers are scaled by the largest of them
+/// first, as with hypot, so squaring them can't overflow.
+fn root_mean_square(numbers: &[f64]) -> f64 {
+  let scale = numbers.iter().fold(0.0, |scale: f64, x| scale.max(x.abs()));
+  if scale == 0.0 || !scale.is_finite() {
+    return scale;
+  }
+  let squares: f64 = numbers.iter().map(|x| (x / scale).powi(2)).sum();
+  scale * (squares / numbers.len() as f64).sqrt()
+}
+
+/// Fail unless every argument of `name` is positive.
+fn positive(name: &str, arguments: &[f64]) -> Result<(), CalcError> {
+  match arguments
+    .iter()
+    .find(|&&argument| argument <= 0.0 || argument.is_nan())
+  {
+    Some(&argument) => Err(domain_error(name, argument)),
+    None => Ok(()),
+  }
+}
+
+/// The multiple of `step` that `rounding` picks for `value`.
+fn snap(value: f64, step: f64, rounding: fn(f64) -> f64) -> Result<f64, CalcError> {
+  if step == 0.0 {
+    return Err(CalcError::DivisionByZero);
+  }
+  Ok(rounding(value / step) * step)
+}
+
+/// The `p`th percentile of `values`, interpolating linearly between the two closest ranks, so the
+/// 0th is the smallest value, the 100th the largest and the 50th the median.
+fn percentile(p: f64, values: &[f64]) -> Result<f64, CalcError> {
+  if !(0.0..=100.0).contains(&p) {
+    return Err(domain_error("percentile", p));
+  }
+  let mut sorted = values.to_vec();
+  sorted.sort_by(f64::total_cmp);
+  let rank = p / 100.0 * (sorted.len() - 1) as f64;
+  let (below, above) = (sorted[rank.floor() as usize], sorted[rank.ceil() as usize]);
+  Ok(lerp(below, above, rank.fract()))
+}
+
+/// The mean of `value, weight` pairs, each value counting in proportion to its weight.
+fn weighted_average(x: &[f64]) -> Result<f64, CalcError> {
+  if !x.len().is_multiple_of(2) {
+    return Err(CalcError::Unsupported(
+      "'wavg' takes pairs of a value and its weight".to_string(),
+    ));
+  }
+  let (total, weights) = x.chunks(2).fold((0.0, 0.0), |(total, weights), pair| {
+    (total + pair[0] * pair[1], weights + pair[1])
+  });
+  if weights == 0.0 {
+    return Err(CalcError::DivisionByZero);
+  }
+  Ok(total / weights)
+}
+
+/// The Roman numeral for an integer from 1 to 3999, like `MMXXIV` for 2024.
+fn roman(n: i64) -> Result<String, CalcError> {
+  const NUMERALS: [(i64, &str); 13] = [
+    (1000, "M"),
+    (900, "CM"),
+    (500, "D"),
+    (400, "CD"),
+    (100, "C"),
+    (90, "XC"),
+    (50, "L"),
+    (40, "XL"),
+    (10, "X"),
+    (9, "IX"),
+    (5, "V"),
+    (4, "IV"),
+    (1, "I"),
+  ];
+  if !(1..=3999).contains(&n) {
+    return Err(domain_error("roman", n as f64));
+  }
+  let mut rest = n;
+  let mut numeral = String::new();
+  for (value, letters) in NUMERALS {
+    while rest >= value {
+      numeral.push_str(letters);
+      rest -= value;
+    }
+  }
+  Ok(numeral)
+}
+
+/// The largest power of an integer `base` from 2 up that is at most `n`, like 64 for `base` 2 and
+/// `n` 100.
+fn prevpow(base: f64, n: f64) -> Result<f64, CalcError> {
+  if base < 2.0 || base.fract() != 0.0 || !base.is_finite() {
+    return Err(domain_error("prevpow", base));
+  }
+  if n < 1.0 || n.is_nan() {
+    return Err(domain_error("prevpow", n));
+  }
+  if n.is_infinite() {
+    return Ok(n);
+  }
+  let mut power = base.powi(n.log(base).floor() as i32);
+  // The logarithm can land just below a whole number for an exact power, or just above one.
+  if power > n {
+    power /= base;
+  } else if power * base <= n {
+    power *= base;
+  }
+  Ok(power)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+  a + (b - a) * t
+}
+
+/// The number of terms of a series, which has to be a non-negative integer.
+fn term_count(name: &str, n: f64) -> Result<f64, CalcError> {
+  if n.fract() != 0.0 || n < 0.0 {
+    return Err(domain_error(name, n));
+  }
+  Ok(n)
+}
+
+/// The chance of exactly `k` successes in `n` trials that each succeed with probability `p`.
+fn binomial_probability(n: f64, k: f64, p: f64) -> Result<f64, CalcError> {
+  if n.fract() != 0.0 || n < 0.0 {
+    return Err(domain_error("binompdf", n));
+  }
+  if k.fract() != 0.0 || !(0.0..=n).contains(&k) {
+    return Err(domain_error("binompdf", k));
+  }
+  if !(0.0..=1.0).contains(&p) {
+    return Err(domain_error("binompdf", p));
+  }
+  // `n choose k`, multiplying and dividing in turn so it stays exact as long as it fits.
+  let combinations = (1..=k.min(n - k) as u64).fold(1.0, |combinations, i| {
+    combinations * (n + 1.0 - i as f64) / i as f64
+  });
+  Ok(combinations * p.powf(k) * (1.0 - p).powf(n - k))
+}
+
+pub(crate) fn find_builtin(name: &str) -> Option<&'static Builtin> {
+  BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+/// Call a built-in function by name, converting angles and integers as `config` says.
+pub(crate) fn call_builtin(
+  name: &str,
+  arguments: &[f64],
+  config: &Config,
+) -> Result<Value, CalcError> {
+  let builtin = find_builtin(name).ok_or_else(|| CalcError::UnknownFunction(name.to_string()))?;
+  builtin.arity.check(name, arguments.len())?;
+  let integers = || {
+    arguments
+      .iter()
+      .map(|&argument| to_integer(name, argument, config.integer_policy))
+      .collect::<Result<Vec<_>, _>>()
+  };
+  let result = match builtin.function {
+    Implementation::Real(function) => match builtin.angle {
+      Angle::Neither => function(arguments)?,
+      Angle::Argument => function(&[config.angle_input_unit.to_radians(arguments[0])])?,
+      Angle::Result => config
+        .angle_output_unit
+        .radians_to_unit(function(arguments)?),
+      Angle::Turn => {
+        let (input, output) = (config.angle_input_unit, config.angle_output_unit);
+        let angle = function(&[arguments[0], input.full_turn()])?;
+        if input == output {
+          angle
+        } else {
+          output.radians_to_unit(input.to_radians(angle))
+        }
+      }
+    },
+    Implementation::Integer(function) => function(&integers()?)?,
+    Implementation::Text(function) => return function(&integers()?).map(Value::Text),
+  };
+  Ok(Value::Float(result))
+}
+
+/// Convert the operand of an integer-only function or operator, rejecting fractions or truncating
+/// them toward zero depending on `policy`. Values out of the range of an `i64` are always an error.
+pub(crate) fn to_integer(
+  function: &str,
+  value: f64,
+  policy: IntegerPolicy,
+) -> Result<i64, CalcError> {
+  let integer = match policy {
+    IntegerPolicy::Strict => value,
+    IntegerPolicy::Truncate => value.trunc(),
+  };
+  if integer.fract() != 0.0 || integer.abs() >= i64::MAX as f64 || integer.is_nan() {
+    return Err(CalcError::NonIntegerOperand {
+      function: function.to_string(),
+      value,
+    });
+  }
+  Ok(integer as i64)
+}
+
+/// The greatest common divisor, which is zero only when both are.
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+  while b != 0 {
+    (a, b) = (b, a % b);
+  }
+  a
+}
+
+/// The `x` from `0` to `modulus - 1` with `a * x ≡ 1 (mod modulus)`, by the extended Euclidean
+/// algorithm.
+fn modular_inverse(a: i64, modulus: i64) -> Result<f64, CalcError> {
+  if modulus <= 1 {
+    return Err(domain_error("modinv", modulus as f64));
+  }
+  // Wider than the operands, since the coefficients can reach the modulus.
+  let (mut r, mut next_r) = (
+    i128::from(modulus),
+    i128::from(a).rem_euclid(modulus.into()),
+  );
+  let (mut x, mut next_x) = (0_i128, 1_i128);
+  while next_r != 0 {
+    let quotient = r / next_r;
+    (r, next_r) = (next_r, r - quotient * next_r);
+    (x, next_x) = (next_x, x - quotient * next_x);
+  }
+  if r != 1 {
+    return Err(CalcError::NoModularInverse { a, modulus });
+  }
+  Ok(x.rem_euclid(modulus.into()) as f64)
+}
+
+/// The least common multiple of all the numbers, folding pairwise. It's kept in floating point,
+/// since it can easily overflow an integer, and only the remainder of dividing it by the next
+/// number is needed for their greatest common divisor.
+fn lcm(n: &[i64]) -> f64 {
+  n.iter().fold(1.0, |multiple: f64, &b| {
+    let b = b.unsigned_abs();
+    if multiple == 0.0 || b == 0 {
+      return 0.0;
+    }
+    let divisor = gcd(b, (multiple % b as f64) as u64);
+    multiple / divisor as f64 * b as f64
+  })
+}
+
+/// The `n`th Fibonacci number, counting from `fib(0) = 0`. It's exact up to `fib(78)`, the last
+/// one below `2^53`, then rounded, and infinite from `fib(1477)` on.
+fn fibonacci(n: u64) -> f64 {
+  let (mut current, mut next) = (0.0_f64, 1.0_f64);
+  for _ in 0..n {
+    if current.is_infinite() {
+      break;
+    }
+    (current, next) = (next, current + next);
+  }
+  current
+}
+
+fn domain_error(name: &str, argument: f64) -> CalcError {
+  CalcError::DomainError {
+    function: name.to_string(),
+    argument,
+  }
+}
+
+/// Only positive whole numbers can be powers of two, fractions like `0.5` don't count.
+fn is_power_of_two(argument: f64) -> bool {
+  argument.fract() == 0.0
+    && argument > 0.0
+    && argument <= u64::MAX as f64
+    && (argument as u64).is_power_of_two()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::f64::consts::{PI, TAU};
+
+  use crate::testing::assert_close;
+  use crate::testing::Tolerance::{Absolute, Relative};
+  use crate::{evaluate, evaluate_typed, validate, AngleUnit, CalcError, Config, Evaluator, Value};
+
+  #[test]
+  fn test_int_and_frac() {
+    assert_eq!(evaluate("int(3.7)"), Ok(3.0));
+    assert_eq!(evaluate("int(-3.7)"), Ok(-3.0));
+    assert_eq!(evaluate("frac(3.25)"), Ok(0.25));
+    assert_eq!(evaluate("frac(-3.25)"), Ok(-0.25));
+  }
+
+  #[test]
+  fn test_int_and_frac_differ_from_floor_and_ceil_on_negatives() {
+    assert_eq!(evaluate("floor(-3.7)"), Ok(-4.0));
+    assert_eq!(evaluate("ceil(-3.2)"), Ok(-3.0));
+    assert_eq!(evaluate("int(-3.2)"), Ok(-3.0));
+    assert_eq!(evaluate("int(-3.7)"), Ok(-3.0));
+    assert_eq!(evaluate("-3.25-floor(-3.25)"), Ok(0.75));
+    assert_eq!(evaluate("frac(-3.25)"), Ok(-0.25));
+  }
+
+  #[test]
+  fn test_round_to_multiples() {
+    assert_eq!(evaluate("roundto(7, 5)"), Ok(5.0));
+    assert_eq!(evaluate("roundto(8, 5)"), Ok(10.0));
+    assert_eq!(evaluate("roundto(7.5, 5)"), Ok(10.0));
+    assert_eq!(evaluate("roundto(-7, 5)"), Ok(-5.0));
+    assert_eq!(evaluate("roundto(0.37, 0.25)"), Ok(0.25));
+    assert_eq!(evaluate("floorto(9, 5)"), Ok(5.0));
+    assert_eq!(evaluate("floorto(-1, 5)"), Ok(-5.0));
+    assert_eq!(evaluate("ceilto(6, 5)"), Ok(10.0));
+    assert_eq!(evaluate("ceilto(10, 5)"), Ok(10.0));
+    for function in ["roundto", "floorto", "ceilto"] {
+      assert_eq!(
+        evaluate(&format!("{}(3, 0)", function)),
+        Err(CalcError::DivisionByZero)
+      );
+    }
+  }
+
+  #[test]
+  fn test_log2() {
+    assert_eq!(evaluate("log2(1024)"), Ok(10.0));
+    assert_eq!(evaluate("log2(0.5)"), Ok(-1.0));
+    assert_eq!(
+      evaluate("log2(0)"),
+      Err(CalcError::DomainError {
+        function: "log2".to_string(),
+        argument: 0.0
+      })
+    );
+    assert!(evaluate("log2(-8)").is_err());
+  }
+
+  #[test]
+  fn test_angle_conversions() {
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    assert_eq!(evaluate("to_radians(180)"), Ok(PI));
+    assert_eq!(evaluate("to_radians(90)"), Ok(FRAC_PI_2));
+    assert_eq!(evaluate("to_radians(0)"), Ok(0.0));
+    assert_eq!(evaluate("to_degrees(pi)"), Ok(180.0));
+    assert_eq!(evaluate("to_degrees(-pi/2)"), Ok(-90.0));
+    assert!((evaluate("sin(to_radians(30))").unwrap() - 0.5).abs() < 1e-12);
+  }
+
+  #[test]
+  fn test_inverse_trigonometry() {
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    assert_eq!(evaluate("asin(1)"), Ok(FRAC_PI_2));
+    assert_eq!(evaluate("acos(1)"), Ok(0.0));
+    assert_eq!(evaluate("atan(1)"), Ok(FRAC_PI_4));
+    assert_eq!(
+      evaluate("acos(-1.5)"),
+      Err(CalcError::DomainError {
+        function: "acos".to_string(),
+        argument: -1.5
+      })
+    );
+  }
+
+  #[test]
+  fn test_angle_units() {
+    use crate::{AngleUnit, Config, Evaluator};
+
+    let evaluator = |angle_input_unit, angle_output_unit| {
+      Evaluator::with_config(Config {
+        angle_input_unit,
+        angle_output_unit,
+        ..Config::default()
+      })
+    };
+    let close = |result: Result<f64, CalcError>, expected: f64| {
+      assert!((result.unwrap() - expected).abs() < 1e-12)
+    };
+
+    let mut degrees_in = evaluator(AngleUnit::Degrees, AngleUnit::Radians);
+    close(degrees_in.evaluate("sin(30)"), 0.5);
+    close(
+      degrees_in.evaluate("asin(0.5)"),
+      std::f64::consts::FRAC_PI_6,
+    );
+
+    let mut degrees_out = evaluator(AngleUnit::Radians, AngleUnit::Degrees);
+    close(degrees_out.evaluate("sin(pi/6)"), 0.5);
+    close(degrees_out.evaluate("asin(0.5)"), 30.0);
+
+    let mut degrees = evaluator(AngleUnit::Degrees, AngleUnit::Degrees);
+    close(degrees.evaluate("acos(cos(60))"), 60.0);
+    close(degrees.evaluate("to_degrees(pi)"), 180.0);
+
+    let mut gradians = evaluator(AngleUnit::Gradians, AngleUnit::Gradians);
+    close(gradians.evaluate("sin(100)"), 1.0);
+    close(gradians.evaluate("cos(200)"), -1.0);
+    close(gradians.evaluate("sin(300)"), -1.0);
+    close(gradians.evaluate("asin(1)"), 100.0);
+    close(gradians.evaluate("acos(0)"), 100.0);
+    close(gradians.evaluate("atan(1)"), 50.0);
+  }
+
+  #[test]
+  fn test_integer_functions() {
+    assert_eq!(evaluate("gcd(12, 18)"), Ok(6.0));
+    assert_eq!(evaluate("gcd(-4, 6)"), Ok(2.0));
+    assert_eq!(evaluate("gcd(0, 0)"), Ok(0.0));
+    assert_eq!(evaluate("gcd(12, 18, 24)"), Ok(6.0));
+    assert_eq!(evaluate("gcd(12, 18, 0, -9)"), Ok(3.0));
+    assert_eq!(evaluate("gcd([8, 12], 20)"), Ok(4.0));
+    assert_eq!(evaluate("lcm(2, 3, 4)"), Ok(12.0));
+    assert_eq!(evaluate("lcm(4, 6, 10, -15)"), Ok(60.0));
+    assert_eq!(evaluate("lcm(2, 0, 3)"), Ok(0.0));
+    assert_eq!(
+      evaluate("lcm(2^40, 3^20, 5^10)"),
+      Ok(2f64.powi(40) * 3f64.powi(20) * 5f64.powi(10))
+    );
+    assert!(matches!(
+      evaluate("gcd(12)"),
+      Err(CalcError::NotEnoughArguments { .. })
+    ));
+    assert!(evaluate("lcm(2, 3, 4.5)").is_err());
+    assert_eq!(evaluate("lcm(4, 6)"), Ok(12.0));
+    assert_eq!(evaluate("lcm(-4, 6)"), Ok(12.0));
+    assert_eq!(evaluate("lcm(0, 5)"), Ok(0.0));
+    assert_eq!(evaluate("schedule(4, 6)"), Ok(12.0));
+    assert_eq!(evaluate("schedule(4, 6, 10)"), Ok(60.0));
+    assert_eq!(evaluate("schedule(7, 7)"), Ok(7.0));
+    assert!(matches!(
+      evaluate("schedule(4, 1.5)"),
+      Err(CalcError::NonIntegerOperand { .. })
+    ));
+    for invalid in ["schedule(4, 0)", "schedule(-3, 6)"] {
+      assert!(matches!(
+        evaluate(invalid),
+        Err(CalcError::DomainError { .. })
+      ));
+    }
+    assert_eq!(evaluate("factorial(0)"), Ok(1.0));
+    assert_eq!(evaluate("factorial(5)"), Ok(120.0));
+    assert!(evaluate("factorial(-1)").is_err());
+  }
+
+  #[test]
+  fn test_divides() {
+    assert_eq!(evaluate("divides(3, 12)"), Ok(1.0));
+    assert_eq!(evaluate("divides(5, 12)"), Ok(0.0));
+    assert_eq!(evaluate("divides(-4, 12)"), Ok(1.0));
+    assert_eq!(evaluate("divides(7, -21)"), Ok(1.0));
+    assert_eq!(evaluate("divides(7, 0)"), Ok(1.0));
+    assert_eq!(evaluate("divides(-1, -2^62)"), Ok(1.0));
+    assert_eq!(evaluate("if(divides(4, 2024), 366, 365)"), Ok(366.0));
+    assert_eq!(evaluate("divides(0, 12)"), Err(CalcError::DivisionByZero));
+    assert!(matches!(
+      evaluate("divides(1.5, 3)"),
+      Err(CalcError::NonIntegerOperand { .. })
+    ));
+  }
+
+  #[test]
+  fn test_digit_sum() {
+    assert_eq!(evaluate("digitsum(12345)"), Ok(15.0));
+    assert_eq!(evaluate("digitsum(7)"), Ok(7.0));
+    assert_eq!(evaluate("digitsum(0)"), Ok(0.0));
+    assert_eq!(evaluate("digitsum(-99)"), Ok(18.0));
+    assert_eq!(evaluate("digitsum(1000000)"), Ok(1.0));
+    assert_eq!(evaluate("digitsum(2^62)"), Ok(85.0));
+    assert!(matches!(
+      evaluate("digitsum(1.5)"),
+      Err(CalcError::NonIntegerOperand { .. })
+    ));
+  }
+
+  #[test]
+  fn test_bit_length() {
+    assert_eq!(evaluate("bitlen(0)"), Ok(0.0));
+    assert_eq!(evaluate("bitlen(1)"), Ok(1.0));
+    assert_eq!(evaluate("bitlen(255)"), Ok(8.0));
+    assert_eq!(evaluate("bitlen(256)"), Ok(9.0));
+    assert_eq!(evaluate("bitlen(2^32)"), Ok(33.0));
+    assert_eq!(evaluate("bitlen(2^48 - 1)"), Ok(48.0));
+    assert_eq!(evaluate("bitlen(2^53 - 1)"), Ok(53.0));
+    assert_eq!(evaluate("bitlen(2^62)"), Ok(63.0));
+    assert_eq!(
+      evaluate("bitlen(-1)"),
+      Err(CalcError::DomainError {
+        function: "bitlen".to_string(),
+        argument: -1.0
+      })
+    );
+    assert!(matches!(
+      evaluate("bitlen(2.5)"),
+      Err(CalcError::NonIntegerOperand { .. })
+    ));
+  }
+
+  #[test]
+  fn test_popcount() {
+    assert_eq!(evaluate("popcount(0)"), Ok(0.0));
+    assert_eq!(evaluate("popcount(7)"), Ok(3.0));
+    assert_eq!(evaluate("popcount(255)"), Ok(8.0));
+    assert_eq!(evaluate("popcount(2^40)"), Ok(1.0));
+    assert_eq!(evaluate("popcount(1024)"), Ok(1.0));
+    assert_eq!(evaluate("popcount(2^32 - 1)"), Ok(32.0));
+    assert_eq!(evaluate("popcount(-1)"), Ok(64.0));
+    assert!(matches!(
+      evaluate("popcount(1.5)"),
+      Err(CalcError::NonIntegerOperand { .. })
+    ));
+  }
+
+  #[test]
+  fn test_fibonacci() {
+    assert_eq!(evaluate("fib(0)"), Ok(0.0));
+    assert_eq!(evaluate("fib(1)"), Ok(1.0));
+    assert_eq!(evaluate("fib(2)"), Ok(1.0));
+    assert_eq!(evaluate("fib(10)"), Ok(55.0));
+    assert_eq!(evaluate("fib(78)"), Ok(8_944_394_323_791_464.0));
+    assert_eq!(
+      evaluate_typed("fib(78)"),
+      Ok(Value::Integer(8_944_394_323_791_464))
+    );
+    // Past `2^53` the sum is rounded: the exact `fib(79)` ends in 7.
+    assert_eq!(evaluate("fib(79)"), Ok(14_472_334_024_676_220.0));
+    assert_eq!(evaluate("fib(1476)"), Ok(1.3069892237633987e308));
+    assert_eq!(evaluate("fib(1477)"), Ok(f64::INFINITY));
+    assert_eq!(evaluate("fib(10^12)"), Ok(f64::INFINITY));
+    assert!(matches!(
+      evaluate("fib(-1)"),
+      Err(CalcError::DomainError { .. })
+    ));
+    assert!(evaluate("fib(2.5)").is_err());
+  }
+
+  #[test]
+  fn test_quotient_and_remainder() {
+    assert_eq!(evaluate("quot(17, 5)"), Ok(3.0));
+    assert_eq!(evaluate("rem(17, 5)"), Ok(2.0));
+    assert_eq!(evaluate("quot(-17, 5)"), Ok(-3.0));
+    assert_eq!(evaluate("rem(-17, 5)"), Ok(-2.0));
+    assert_eq!(evaluate("quot(17, -5)"), Ok(-3.0));
+    assert_eq!(evaluate("rem(17, -5)"), Ok(2.0));
+    assert_eq!(evaluate("quot(-17, 5)*5 + rem(-17, 5)"), Ok(-17.0));
+    assert_eq!(evaluate("quot(1, 0)"), Err(CalcError::DivisionByZero));
+    assert_eq!(evaluate("rem(1, 0)"), Err(CalcError::DivisionByZero));
+    assert!(evaluate("quot(7.5, 2)").is_err());
+  }
+
+  #[test]
+  fn test_modular_inverse() {
+    assert_eq!(evaluate("modinv(3, 11)"), Ok(4.0));
+    assert_eq!(evaluate("modinv(10, 17)"), Ok(12.0));
+    assert_eq!(evaluate("modinv(-3, 11)"), Ok(7.0));
+    assert_eq!(evaluate("modinv(14, 11)"), Ok(4.0));
+    assert_eq!(evaluate("modinv(1, 2)"), Ok(1.0));
+    assert_eq!(
+      evaluate("modinv(2, 4)"),
+      Err(CalcError::NoModularInverse { a: 2, modulus: 4 })
+    );
+    assert_eq!(
+      evaluate("modinv(0, 7)"),
+      Err(CalcError::NoModularInverse { a: 0, modulus: 7 })
+    );
+    assert!(matches!(
+      evaluate("modinv(3, 1)"),
+      Err(CalcError::DomainError { .. })
+    ));
+    assert!(evaluate("modinv(3.5, 11)").is_err());
+  }
+
+  #[test]
+  fn test_integer_policies() {
+    use crate::{Config, Evaluator, IntegerPolicy};
+
+    assert_eq!(
+      evaluate("factorial(2.5)"),
+      Err(CalcError::NonIntegerOperand {
+        function: "factorial".to_string(),
+        value: 2.5
+      })
+    );
+    assert!(evaluate("gcd(12, 1/0)").is_err());
+
+    let mut lenient = Evaluator::with_config(Config {
+      integer_policy: IntegerPolicy::Truncate,
+      ..Config::default()
+    });
+    assert_eq!(lenient.evaluate("factorial(3.9)"), Ok(6.0));
+    assert_eq!(lenient.evaluate("gcd(12.5, -18.2)"), Ok(6.0));
+    assert!(lenient.evaluate("gcd(12, 1/0)").is_err());
+  }
+
+  #[test]
+  fn test_ndigits() {
+    assert_eq!(evaluate("ndigits(12345)"), Ok(5.0));
+    assert_eq!(evaluate("ndigits(0)"), Ok(1.0));
+    assert_eq!(evaluate("ndigits(-0)"), Ok(1.0));
+    assert_eq!(evaluate("ndigits(-12345)"), Ok(5.0));
+    assert_eq!(evaluate("ndigits(0.25)"), Ok(1.0));
+    assert_eq!(evaluate("ndigits(-0.999)"), Ok(1.0));
+    assert_eq!(evaluate("ndigits(9.99)"), Ok(1.0));
+    assert_eq!(evaluate("ndigits(10)"), Ok(2.0));
+    assert_eq!(evaluate("ndigits(999.5)"), Ok(3.0));
+    assert_eq!(evaluate("ndigits(1000)"), Ok(4.0));
+    assert!(matches!(
+      evaluate("ndigits(10^400)"),
+      Err(CalcError::DomainError { .. })
+    ));
+  }
+
+  #[test]
+  fn test_lerp() {
+    assert_eq!(evaluate("lerp(0, 10, 0)"), Ok(0.0));
+    assert_eq!(evaluate("lerp(0, 10, 0.5)"), Ok(5.0));
+    assert_eq!(evaluate("lerp(0, 10, 1)"), Ok(10.0));
+    assert_eq!(evaluate("lerp(0, 10, 2)"), Ok(20.0));
+    assert_eq!(evaluate("lerp(0, 10, -0.5)"), Ok(-5.0));
+    assert_eq!(evaluate("lerp(4, 2, 0.25)"), Ok(3.5));
+    assert_eq!(evaluate("clamplerp(0, 10, 0.5)"), Ok(5.0));
+    assert_eq!(evaluate("clamplerp(0, 10, 2)"), Ok(10.0));
+    assert_eq!(evaluate("clamplerp(0, 10, -1)"), Ok(0.0));
+  }
+
+  #[test]
+  fn test_map() {
+    assert_eq!(evaluate("map(5, 0, 10, 0, 100)"), Ok(50.0));
+    assert_eq!(evaluate("map(0, 0, 10, 0, 100)"), Ok(0.0));
+    assert_eq!(evaluate("map(10, 0, 10, 0, 100)"), Ok(100.0));
+    assert_eq!(evaluate("map(512, 0, 1024, -1, 1)"), Ok(0.0));
+    assert_eq!(evaluate("map(2, 0, 10, 100, 0)"), Ok(80.0));
+    assert_eq!(evaluate("map(15, 0, 10, 0, 100)"), Ok(150.0));
+    assert_eq!(evaluate("map(-5, 0, 10, 0, 100)"), Ok(-50.0));
+    assert_eq!(
+      evaluate("map(5, 3, 3, 0, 100)"),
+      Err(CalcError::DivisionByZero)
+    );
+  }
+
+  #[test]
+  fn test_series_sums() {
+    assert_eq!(evaluate("arithsum(1, 2, 5)"), Ok(25.0));
+    assert_eq!(evaluate("arithsum(1, 1, 100)"), Ok(5050.0));
+    assert_eq!(evaluate("arithsum(10, -3, 4)"), Ok(22.0));
+    assert_eq!(evaluate("arithsum(0.5, 0.5, 3)"), Ok(3.0));
+    assert_eq!(evaluate("arithsum(7, 2, 0)"), Ok(0.0));
+    assert_eq!(evaluate("geomsum(1, 2, 10)"), Ok(1023.0));
+    assert_eq!(evaluate("geomsum(3, 1, 4)"), Ok(12.0));
+    assert_eq!(evaluate("geomsum(1, 0.5, 3)"), Ok(1.75));
+    assert_eq!(evaluate("geomsum(2, -1, 3)"), Ok(2.0));
+    assert_eq!(evaluate("geomsum(5, 3, 0)"), Ok(0.0));
+    assert_eq!(evaluate("geomsum(1, 2, 3) == sum(k, 0, 2, 2^k)"), Ok(1.0));
+    for name in ["arithsum", "geomsum"] {
+      for n in [-1.0, 2.5] {
+        assert_eq!(
+          evaluate(&format!("{}(1, 2, {})", name, n)),
+          Err(CalcError::DomainError {
+            function: name.to_string(),
+            argument: n
+          })
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn test_norm() {
+    assert_eq!(evaluate("norm(7, 0, 10)"), Ok(0.7));
+    assert_eq!(evaluate("norm(0, 0, 10)"), Ok(0.0));
+    assert_eq!(evaluate("norm(10, 0, 10)"), Ok(1.0));
+    assert_eq!(evaluate("norm(-5, -10, 10)"), Ok(0.25));
+    assert_eq!(evaluate("norm(15, 0, 10)"), Ok(1.0));
+    assert_eq!(evaluate("norm(-3, 0, 10)"), Ok(0.0));
+    assert_eq!(evaluate("norm(2, 10, 0)"), Ok(0.8));
+    assert_eq!(evaluate("norm(5, 3, 3)"), Err(CalcError::DivisionByZero));
+  }
+
+  #[test]
+  fn test_wrap() {
+    assert_eq!(evaluate("wrap(25, 0, 24)"), Ok(1.0));
+    assert_eq!(evaluate("wrap(-1, 0, 24)"), Ok(23.0));
+    assert_eq!(evaluate("wrap(13, 0, 24)"), Ok(13.0));
+    assert_eq!(evaluate("wrap(0, 0, 24)"), Ok(0.0));
+    assert_eq!(evaluate("wrap(24, 0, 24)"), Ok(0.0));
+    assert_eq!(evaluate("wrap(-49, 0, 24)"), Ok(23.0));
+    assert_eq!(evaluate("wrap(190, -180, 180)"), Ok(-170.0));
+    assert_eq!(evaluate("wrap(-200, -180, 180)"), Ok(160.0));
+    assert_eq!(evaluate("wrap(7.5, 1, 4)"), Ok(1.5));
+    for invalid in ["wrap(5, 3, 3)", "wrap(5, 10, 0)"] {
+      assert!(matches!(evaluate(invalid), Err(CalcError::InvalidRange(_))));
+    }
+  }
+
+  #[test]
+  fn test_reduce() {
+    let reduce = |arguments| crate::evaluate_typed(&format!("reduce({})", arguments));
+    let fraction = |fraction: &str| Ok(Value::Text(fraction.to_string()));
+    assert_eq!(reduce("6, 8"), fraction("3/4"));
+    assert_eq!(reduce("3, 4"), fraction("3/4"));
+    assert_eq!(reduce("4, 2"), fraction("2"));
+    assert_eq!(reduce("0, 5"), fraction("0"));
+    assert_eq!(reduce("6, -8"), fraction("-3/4"));
+    assert_eq!(reduce("-6, -8"), fraction("3/4"));
+    assert_eq!(reduce("1, 0"), Err(CalcError::DivisionByZero));
+    assert!(matches!(
+      reduce("1.5, 2"),
+      Err(CalcError::NonIntegerOperand { .. })
+    ));
+  }
+
+  #[test]
+  fn test_roman() {
+    let roman = |n| crate::evaluate_typed(&format!("roman({})", n));
+    let numeral = |numeral: &str| Ok(Value::Text(numeral.to_string()));
+    assert_eq!(roman("1"), numeral("I"));
+    assert_eq!(roman("4"), numeral("IV"));
+    assert_eq!(roman("9"), numeral("IX"));
+    assert_eq!(roman("14"), numeral("XIV"));
+    assert_eq!(roman("40"), numeral("XL"));
+    assert_eq!(roman("90"), numeral("XC"));
+    assert_eq!(roman("400"), numeral("CD"));
+    assert_eq!(roman("900"), numeral("CM"));
+    assert_eq!(roman("1994"), numeral("MCMXCIV"));
+    assert_eq!(roman("2024"), numeral("MMXXIV"));
+    assert_eq!(roman("3999"), numeral("MMMCMXCIX"));
+    assert_eq!(roman("2 * 2"), numeral("IV"));
+    for out_of_range in ["0", "-4", "4000"] {
+      assert!(matches!(
+        roman(out_of_range),
+        Err(CalcError::DomainError { .. })
+      ));
+    }
+    assert!(matches!(
+      roman("4.5"),
+      Err(CalcError::NonIntegerOperand { .. })
+    ));
+    assert!(matches!(
+      evaluate("roman(4) + 1"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert!(matches!(
+      evaluate("roman(4)"),
+      Err(CalcError::Unsupported(_))
+    ));
+  }
+
+  #[test]
+  fn test_is_pow2() {
+    assert_eq!(evaluate("is_pow2(1024)"), Ok(1.0));
+    assert_eq!(evaluate("is_pow2(1)"), Ok(1.0));
+    assert_eq!(evaluate("is_pow2(1000)"), Ok(0.0));
+    assert_eq!(evaluate("is_pow2(0.5)"), Ok(0.0));
+    assert_eq!(evaluate("is_pow2(-4)"), Ok(0.0));
+  }
+
+  #[test]
+  fn test_recip_and_neg() {
+    assert_eq!(evaluate("recip(4)"), Ok(0.25));
+    assert_eq!(evaluate("recip(-0.5)"), Ok(-2.0));
+    assert_eq!(evaluate("recip(recip(8))"), Ok(8.0));
+    assert_eq!(evaluate("recip(0)"), Err(CalcError::DivisionByZero));
+    assert_eq!(evaluate("neg(5)"), Ok(-5.0));
+    assert_eq!(evaluate("neg(-2) * 3"), Ok(6.0));
+  }
+
+  #[test]
+  fn test_percentages() {
+    assert_eq!(evaluate("pct_change(100, 150)"), Ok(50.0));
+    assert_eq!(evaluate("pct_change(200, 150)"), Ok(-25.0));
+    assert_eq!(evaluate("pct_of(25, 200)"), Ok(12.5));
+    assert_eq!(evaluate("pct_of(2+3, 10*2)"), Ok(25.0));
+    assert_eq!(evaluate("pct_change(0, 5)"), Err(CalcError::DivisionByZero));
+    assert_eq!(evaluate("pct_of(5, 0)"), Err(CalcError::DivisionByZero));
+  }
+
+  #[test]
+  fn test_compound_interest() {
+    let close = |expression, expected| assert_close(expression, expected, Absolute(0.005));
+    close("compound(1000, 0.05, 12, 10)", 1647.01);
+    close("compound(1000, 0.05, 1, 10)", 1628.89);
+    close("compound(5000, 0.04, 4, 5)", 6100.95);
+    close("compound(2500, 0.03, 365, 2.5)", 2694.70);
+    assert_eq!(evaluate("compound(1000, 0, 12, 10)"), Ok(1000.0));
+    assert_eq!(evaluate("compound(100, 0.1, 1, 0)"), Ok(100.0));
+    for invalid in [
+      "compound(1000, 0.05, 0, 10)",
+      "compound(1000, 0.05, 2.5, 10)",
+      "compound(1000, -1, 1, 10)",
+    ] {
+      assert!(matches!(
+        evaluate(invalid),
+        Err(CalcError::DomainError { .. })
+      ));
+    }
+  }
+
+  #[test]
+  fn test_argument_count() {
+    assert_eq!(
+      evaluate("pct_of(5)"),
+      Err(CalcError::ArityMismatch {
+        function: "pct_of".to_string(),
+        expected: 2,
+        found: 1
+      })
+    );
+    assert_eq!(
+      evaluate("sqrt(4, 9)"),
+      Err(CalcError::ArityMismatch {
+        function: "sqrt".to_string(),
+        expected: 1,
+        found: 2
+      })
+    );
+    assert_eq!(
+      evaluate("sqrt()"),
+      Err(CalcError::ArityMismatch {
+        function: "sqrt".to_string(),
+        expected: 1,
+        found: 0
+      })
+    );
+    let too_few = |function: &str, minimum, found| {
+      Err(CalcError::NotEnoughArguments {
+        function: function.to_string(),
+        minimum,
+        found,
+      })
+    };
+    assert_eq!(evaluate("mean()"), too_few("mean", 1, 0));
+    assert_eq!(evaluate("lcm()"), too_few("lcm", 2, 0));
+    assert_eq!(evaluate("lcm(4)"), too_few("lcm", 2, 1));
+    assert_eq!(validate("gcd()").map(|()| 0.0), too_few("gcd", 2, 0));
+    assert_eq!(
+      evaluate("mean()").unwrap_err().to_string(),
+      "'mean' takes at least 1 argument(s) but was given 0"
+    );
+  }
+
+  #[test]
+  fn test_approx() {
+    assert_eq!(evaluate("approx(0.1 + 0.2, 0.3)"), Ok(1.0));
+    assert_eq!(evaluate("approx(1, 1.001)"), Ok(0.0));
+    assert_eq!(evaluate("approx(1, 1.001, 0.01)"), Ok(1.0));
+    assert_eq!(evaluate("approx(3.14, pi, 0.001)"), Ok(0.0));
+    assert_eq!(evaluate("approx(2, 2, 0)"), Ok(1.0));
+    assert!(matches!(
+      evaluate("approx(1, 1, -1)"),
+      Err(CalcError::DomainError { .. })
+    ));
+    for (arguments, expected) in [("1", 2), ("1, 2, 3, 4", 3)] {
+      assert!(matches!(
+        evaluate(&format!("approx({})", arguments)),
+        Err(CalcError::ArityMismatch { expected: e, .. }) if e == expected
+      ));
+    }
+  }
+
+  #[test]
+  fn test_normalize_angle() {
+    let radians = |expression| evaluate(expression).unwrap();
+    assert!((radians("normalize_angle(7)") - 0.7168146928204138).abs() < 1e-12);
+    assert!((radians("normalize_angle(-1)") - (TAU - 1.0)).abs() < 1e-12);
+    assert_eq!(radians("normalize_angle(2)"), 2.0);
+    assert_eq!(radians("normalize_angle(0)"), 0.0);
+    assert!((radians("normalize_angle_signed(4)") - (4.0 - TAU)).abs() < 1e-12);
+    assert_eq!(radians("normalize_angle_signed(-3)"), -3.0);
+    assert!((radians("normalize_angle_signed(-pi)") - PI).abs() < 1e-12);
+
+    let mut degrees = Evaluator::with_config(Config {
+      angle_input_unit: AngleUnit::Degrees,
+      angle_output_unit: AngleUnit::Degrees,
+      ..Config::default()
+    });
+    for (expression, expected) in [
+      ("normalize_angle(370)", 10.0),
+      ("normalize_angle(-90)", 270.0),
+      ("normalize_angle(720)", 0.0),
+      ("normalize_angle(45)", 45.0),
+      ("normalize_angle_signed(270)", -90.0),
+      ("normalize_angle_signed(-180)", 180.0),
+      ("normalize_angle_signed(-45)", -45.0),
+    ] {
+      assert_eq!(degrees.evaluate(expression), Ok(expected), "{}", expression);
+    }
+
+    let mut mixed = Evaluator::with_config(Config {
+      angle_input_unit: AngleUnit::Degrees,
+      ..Config::default()
+    });
+    assert!((mixed.evaluate("normalize_angle(450)").unwrap() - PI / 2.0).abs() < 1e-12);
+  }
+
+  #[test]
+  fn test_prevpow() {
+    assert_eq!(evaluate("prevpow(2, 100)"), Ok(64.0));
+    assert_eq!(evaluate("prevpow(10, 5432)"), Ok(1000.0));
+    assert_eq!(evaluate("prevpow(2, 100.5)"), Ok(64.0));
+    assert_eq!(evaluate("prevpow(7, 1)"), Ok(1.0));
+    assert_eq!(evaluate("prevpow(7, 6.9)"), Ok(1.0));
+    for (base, power) in [
+      (2, 64),
+      (3, 243),
+      (10, 1000),
+      (5, 5i64.pow(20)),
+      (2, 1 << 52),
+    ] {
+      assert_eq!(
+        evaluate(&format!("prevpow({}, {})", base, power)),
+        Ok(power as f64)
+      );
+      assert_eq!(
+        evaluate(&format!("prevpow({}, {})", base, power - 1)),
+        Ok((power / base) as f64)
+      );
+    }
+    for invalid in ["prevpow(1, 10)", "prevpow(2.5, 10)", "prevpow(2, 0.5)"] {
+      assert!(matches!(
+        evaluate(invalid),
+        Err(CalcError::DomainError { .. })
+      ));
+    }
+  }
+
+  #[test]
+  fn test_binomial_probability() {
+    assert_eq!(evaluate("binompdf(10, 3, 0.5)"), Ok(0.1171875));
+    assert_eq!(evaluate("binompdf(4, 0, 0.25)"), Ok(0.31640625));
+    assert_eq!(evaluate("binompdf(4, 4, 0.25)"), Ok(0.00390625));
+    assert_eq!(evaluate("binompdf(4, 1, 0.25)"), Ok(0.421875));
+    assert_eq!(evaluate("binompdf(0, 0, 0.3)"), Ok(1.0));
+    assert_eq!(evaluate("binompdf(5, 0, 0)"), Ok(1.0));
+    assert_eq!(evaluate("binompdf(5, 5, 1)"), Ok(1.0));
+    assert_eq!(evaluate("binompdf(5, 2, 1)"), Ok(0.0));
+    let total = evaluate("sum(k, 0, 20, binompdf(20, k, 0.3))").unwrap();
+    assert!((total - 1.0).abs() < 1e-12);
+    for invalid in [
+      "5, 6, 0.5",
+      "5, -1, 0.5",
+      "5, 2.5, 0.5",
+      "4.5, 2, 0.5",
+      "5, 2, 1.5",
+    ] {
+      assert!(matches!(
+        evaluate(&format!("binompdf({})", invalid)),
+        Err(CalcError::DomainError { .. })
+      ));
+    }
+  }
+
+  #[test]
+  fn test_distance() {
+    assert_eq!(evaluate("dist(0, 0, 3, 4)"), Ok(5.0));
+    assert_eq!(evaluate("dist(1, 2, -4, 14)"), Ok(13.0));
+    assert_eq!(evaluate("dist(2, 2, 2, 2)"), Ok(0.0));
+    assert_eq!(
+      evaluate("dist(0, 0, 3 * 2^700, 4 * 2^700)"),
+      Ok(5.0 * 2f64.powi(700))
+    );
+    assert_eq!(evaluate("dist3(0, 0, 0, 2, 3, 6)"), Ok(7.0));
+    assert_eq!(evaluate("dist3(1, 1, 1, -1, -1, 0)"), Ok(3.0));
+    assert!(matches!(
+      evaluate("dist(0, 0, 3)"),
+      Err(CalcError::ArityMismatch { .. })
+    ));
+  }
+
+  #[test]
+  fn test_hypot() {
+    assert_eq!(evaluate("hypot(3, 4)"), Ok(5.0));
+    assert_eq!(evaluate("hypot(-5, 12)"), Ok(13.0));
+    assert_eq!(evaluate("hypot(0, 0)"), Ok(0.0));
+    assert_eq!(
+      evaluate("sqrt((3 * 2^700)^2 + (4 * 2^700)^2)"),
+      Ok(f64::INFINITY)
+    );
+    assert_eq!(
+      evaluate("hypot(3 * 2^700, 4 * 2^700)"),
+      Ok(5.0 * 2f64.powi(700))
+    );
+  }
+
+  #[test]
+  fn test_poly() {
+    assert_eq!(evaluate("poly(2, 1, 0, 3)"), Ok(13.0));
+    assert_eq!(evaluate("poly(5, 7)"), Ok(7.0));
+    assert_eq!(evaluate("poly(-1, 1, 1, 1, 1)"), Ok(0.0));
+    assert_eq!(evaluate("poly(0.5, 0, 0, 0, 8)"), Ok(1.0));
+    assert_eq!(evaluate("poly(3, [2, -4, 1])"), Ok(-1.0));
+    assert!(matches!(
+      evaluate("poly(2)"),
+      Err(CalcError::NotEnoughArguments { .. })
+    ));
+  }
+
+  #[test]
+  fn test_weighted_average() {
+    assert_eq!(evaluate("wavg(90, 0.3, 80, 0.7)"), Ok(83.0));
+    assert_eq!(evaluate("wavg(5, 2)"), Ok(5.0));
+    assert_eq!(evaluate("wavg([1, 1], [4, 3])"), Ok(3.25));
+    assert!(matches!(
+      evaluate("wavg(90, 0.3, 80)"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert_eq!(
+      evaluate("wavg(1, 1, 2, -1)"),
+      Err(CalcError::DivisionByZero)
+    );
+    assert!(matches!(
+      evaluate("wavg(1)"),
+      Err(CalcError::NotEnoughArguments { .. })
+    ));
+  }
+
+  #[test]
+  fn test_geometric_and_harmonic_mean() {
+    let close = |expression, expected| assert_close(expression, expected, Relative(1e-12));
+    assert_eq!(evaluate("gmean(1, 4, 16)"), Ok(4.0));
+    assert_eq!(evaluate("gmean(5)"), Ok(5.0));
+    close("gmean([2, 8])", 4.0);
+    close("gmean(3, 12)", 6.0);
+    close("gmean(2^1000, 2^1020)", 2f64.powi(1010));
+    close("hmean(1, 2, 4)", 12.0 / 7.0);
+    close("hmean(2, 6)", 3.0);
+    close("hmean([40, 60])", 48.0);
+    for name in ["gmean", "hmean"] {
+      for (arguments, argument) in [("1, -4", -4.0), ("0, 2", 0.0)] {
+        assert_eq!(
+          evaluate(&format!("{}({})", name, arguments)),
+          Err(CalcError::DomainError {
+            function: name.to_string(),
+            argument
+          })
+        );
+      }
+      assert_eq!(
+        evaluate(&format!("{}()", name)),
+        Err(CalcError::NotEnoughArguments {
+          function: name.to_string(),
+          minimum: 1,
+          found: 0
+        })
+      );
+    }
+  }
+
+  #[test]
+  fn test_max_and_min() {
+    assert_eq!(evaluate("max(3, 9, 2)"), Ok(9.0));
+    assert_eq!(evaluate("min(3, 9, 2)"), Ok(2.0));
+    assert_eq!(evaluate("max(-7)"), Ok(-7.0));
+    assert_eq!(evaluate("min([1, -8], 3)"), Ok(-8.0));
+    assert_eq!(
+      evaluate("max()"),
+      Err(CalcError::NotEnoughArguments {
+        function: "max".to_string(),
+        minimum: 1,
+        found: 0
+      })
+    );
+    assert_eq!(
+      evaluate("min()").unwrap_err().to_string(),
+      "'min' takes at least 1 argument(s) but was given 0"
+    );
+  }
+
+  #[test]
+  fn test_argmax_and_argmin() {
+    assert_eq!(evaluate("argmax(3, 9, 2)"), Ok(2.0));
+    assert_eq!(evaluate("argmin(3, 9, 2)"), Ok(3.0));
+    assert_eq!(evaluate("argmax(5)"), Ok(1.0));
+    assert_eq!(evaluate("argmin(-1, -4.5, 0)"), Ok(2.0));
+    assert_eq!(evaluate("argmax([1, 8], 3)"), Ok(2.0));
+    // Ties go to the first.
+    assert_eq!(evaluate("argmax(4, 7, 1, 7)"), Ok(2.0));
+    assert_eq!(evaluate("argmin(2, 2, 2)"), Ok(1.0));
+    assert!(matches!(
+      evaluate("argmax()"),
+      Err(CalcError::NotEnoughArguments { .. })
+    ));
+    // The position is that of the extreme `max` and `min` give.
+    assert_eq!(
+      evaluate("[4, 7, 1, 7][argmax(4, 7, 1, 7) - 1]"),
+      evaluate("max(4, 7, 1, 7)")
+    );
+    assert_eq!(
+      evaluate("[5, 1, 1][argmin(5, 1, 1) - 1]"),
+      evaluate("min(5, 1, 1)")
+    );
+  }
+
+  #[test]
+  fn test_root_mean_square() {
+    let close = |expression, expected| assert_close(expression, expected, Relative(1e-12));
+    close("rms(3, 4)", 12.5f64.sqrt());
+    close("rms(1, 2, 3, 4, 5)", 11f64.sqrt());
+    close("rms(-2, 2, -2, 2)", 2.0);
+    close("rms([1, 7])", 5.0);
+    close("rms(2^600, 2^600)", 2f64.powi(600));
+    assert_eq!(evaluate("rms(-7)"), Ok(7.0));
+    assert_eq!(evaluate("rms(0, 0)"), Ok(0.0));
+    assert_eq!(
+      evaluate("rms()"),
+      Err(CalcError::NotEnoughArguments {
+        function: "rms".to_string(),
+        minimum: 1,
+        found: 0
+      })
+    );
+  }
+
+  #[test]
+  fn test_percentile() {
+    assert_eq!(evaluate("percentile(50, 1, 2, 3, 4)"), Ok(2.5));
+    assert_eq!(evaluate("percentile(0, 4, 1, 3, 2)"), Ok(1.0));
+    assert_eq!(evaluate("percentile(100, 4, 1, 3, 2)"), Ok(4.0));
+    assert_eq!(evaluate("percentile(50, 7, 3, 5)"), Ok(5.0));
+    assert_eq!(evaluate("percentile(25, 10, 20, 30, 40, 50)"), Ok(20.0));
+    assert_eq!(evaluate("percentile(90, [1, 2, 3, 4, 5])"), Ok(4.6));
+    assert_eq!(evaluate("percentile(30, 8)"), Ok(8.0));
+    for p in [-1.0, 100.5] {
+      assert_eq!(
+        evaluate(&format!("percentile({}, 1, 2)", p)),
+        Err(CalcError::DomainError {
+          function: "percentile".to_string(),
+          argument: p
+        })
+      );
+    }
+    assert_eq!(
+      evaluate("percentile(50)"),
+      Err(CalcError::NotEnoughArguments {
+        function: "percentile".to_string(),
+        minimum: 2,
+        found: 1
+      })
+    );
+  }
+
+  #[test]
+  fn test_mean() {
+    assert_eq!(evaluate("mean(1, 2, 3, 4)"), Ok(2.5));
+    assert_eq!(evaluate("mean([1, 2, 3])"), Ok(2.0));
+    assert_eq!(evaluate("mean([1, 2], 6)"), Ok(3.0));
+    assert_eq!(evaluate("mean([[1], [2, 3.5]])"), Ok(6.5 / 3.0));
+    assert_eq!(
+      evaluate("mean([])"),
+      Err(CalcError::NotEnoughArguments {
+        function: "mean".to_string(),
+        minimum: 1,
+        found: 0
+      })
+    );
+    assert!(matches!(
+      evaluate("sqrt([4])"),
+      Err(CalcError::Unsupported(_))
+    ));
+  }
+
+  #[test]
+  fn test_missing_parentheses() {
+    assert_eq!(
+      evaluate("sqrt 16"),
+      Err(CalcError::MissingFunctionParens {
+        function: "sqrt".to_string(),
+        argument: "16".to_string()
+      })
+    );
+    assert_eq!(
+      evaluate("1 + abs pi").unwrap_err().to_string(),
+      "Function 'abs' must be called with parentheses, e.g. abs(pi)"
+    );
+    assert_eq!(
+      evaluate("sqrt"),
+      Err(CalcError::UnknownVariable("sqrt".to_string()))
+    );
+    assert_eq!(evaluate("sqrt(16)"), Ok(4.0));
+    assert_eq!(evaluate("sqrt (16)"), Ok(4.0));
+  }
+
+  #[test]
+  fn test_misplaced_separator() {
+    assert_eq!(evaluate("1,5"), Err(CalcError::MisplacedSeparator));
+    assert_eq!(evaluate("sqrt((4, 9))"), Err(CalcError::MisplacedSeparator));
+  }
+
+  #[test]
+  fn test_nested_calls() {
+    assert_eq!(evaluate("sqrt(abs(-16))+floor(2.5)"), Ok(6.0));
+    assert_eq!(evaluate("2*int(1.5)^2"), Ok(2.0));
+    assert_eq!(evaluate("pct_of(sqrt(16), pct_change(10, 30))"), Ok(2.0));
+  }
+}
@@ -0,0 +1,262 @@
+//! Order of operations calculator using the Shunting Yard algorithm.
+
+mod ast;
+mod calculus;
+mod conditional;
+mod constants;
+mod data;
+mod define;
+mod error;
+mod evaluator;
+mod format;
+mod functions;
+mod number;
+mod parser;
+mod polish;
+mod rates;
+mod rational;
+mod saved;
+mod series;
+mod solve;
+mod table;
+mod template;
+#[cfg(test)]
+mod testing;
+mod text;
+mod token;
+mod units;
+mod validate;
+mod value;
+
+pub use data::parse_constants;
+pub use error::{CalcError, OperandSide};
+pub use evaluator::{
+  AngleUnit, BooleanArithmetic, Config, Evaluator, IntegerDivision, IntegerPolicy, KnownName,
+  NanPolicy, NonFiniteStep, NumberMode, Wrap, ZeroPower, DEFAULT_EPSILON, DEFAULT_MAX_INPUT_LENGTH,
+  HISTORY_LENGTH, SANDBOX_MAX_INPUT_LENGTH, SANDBOX_TIMEOUT,
+};
+pub use format::{
+  format_fixed, format_number, format_plain, format_radix, format_significant, round, Rounding,
+};
+pub use functions::Arity;
+pub use number::{eval_rpn_with, Number};
+pub use rates::parse_rates;
+pub use rational::Rational;
+pub use solve::Solution;
+pub use table::Histogram;
+pub use token::{
+  strip_comments, Associativity, Bracket, Operator, OperatorInfo, Parenthesis, Token,
+};
+pub use units::Dimension;
+pub use validate::validate;
+pub use value::Value;
+
+/// Evaluate an expression with the default configuration.
+pub fn evaluate(expression: &str) -> Result<f64, CalcError> {
+  Evaluator::new().evaluate(expression)
+}
+
+/// Evaluate an expression with the default configuration, also returning how many operators were
+/// applied.
+pub fn evaluate_with_stats(expression: &str) -> Result<(f64, usize), CalcError> {
+  Evaluator::new().evaluate_with_stats(expression)
+}
+
+/// Evaluate an expression with the default configuration, reporting what kind of number the
+/// result is.
+pub fn evaluate_typed(expression: &str) -> Result<Value, CalcError> {
+  Evaluator::new().evaluate_typed(expression)
+}
+
+/// Split an expression into tokens with the default configuration, the first step of evaluating
+/// it.
+pub fn tokenize(expression: &str) -> Result<Vec<Token>, CalcError> {
+  Evaluator::new().tokenise(expression)
+}
+
+/// Reorder tokens from [`tokenize`] into reverse polish notation, the second step.
+pub fn to_rpn(tokens: Vec<Token>) -> Result<Vec<Token>, CalcError> {
+  parser::shunting_yard(tokens)
+}
+
+/// Evaluate tokens from [`to_rpn`] with the default configuration, the last step. Together the
+/// three steps let tokens be changed before evaluating them:
+///
+/// ```
+/// use calculator::{eval_rpn, to_rpn, tokenize, Operator, Token};
+///
+/// let tokens = tokenize("8 / 2 + 1").unwrap();
+/// assert_eq!(eval_rpn(to_rpn(tokens.clone()).unwrap()), Ok(5.0));
+///
+/// let swapped = tokens
+///   .into_iter()
+///   .map(|token| match token {
+///     Token::Operator(Operator::Divide) => Token::Operator(Operator::Multiply),
+///     token => token,
+///   })
+///   .collect();
+/// assert_eq!(eval_rpn(to_rpn(swapped).unwrap()), Ok(17.0));
+/// ```
+pub fn eval_rpn(rpn: Vec<Token>) -> Result<f64, CalcError> {
+  Evaluator::new().eval_rpn(rpn)
+}
+
+/// Count the tokens of an expression with the default configuration. See
+/// [`Evaluator::token_count`].
+pub fn token_count(expression: &str) -> Result<usize, CalcError> {
+  Evaluator::new().token_count(expression)
+}
+
+/// Measure how deeply the parentheses of an expression nest with the default configuration. See
+/// [`Evaluator::max_paren_depth`].
+pub fn max_paren_depth(expression: &str) -> Result<usize, CalcError> {
+  Evaluator::new().max_paren_depth(expression)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn evaluate_expression(expression: &str) -> f64 {
+    evaluate(expression).unwrap()
+  }
+
+  #[test]
+  fn test_basic_operations() {
+    assert_eq!(evaluate_expression("2+2"), 4.0);
+    assert_eq!(evaluate_expression("5-3"), 2.0);
+    assert_eq!(evaluate_expression("4*2"), 8.0);
+    assert_eq!(evaluate_expression("8/2"), 4.0);
+  }
+
+  #[test]
+  fn test_operator_precedence() {
+    assert_eq!(evaluate_expression("2+3*4"), 14.0);
+    assert_eq!(evaluate_expression("2*3+4"), 10.0);
+    assert_eq!(evaluate_expression("2+3*4-5"), 9.0);
+  }
+
+  #[test]
+  fn test_parentheses_handling() {
+    assert_eq!(evaluate_expression("(2+3)*4"), 20.0);
+    assert_eq!(evaluate_expression("2*(3+4)"), 14.0);
+    assert_eq!(evaluate_expression("2*(3+4)-5"), 9.0);
+  }
+
+  #[test]
+  fn test_edge_cases() {
+    assert_eq!(evaluate_expression("0+0"), 0.0);
+    assert_eq!(evaluate_expression("0*1000"), 0.0);
+    assert_eq!(evaluate_expression("1000000/1"), 1000000.0);
+    assert_eq!(evaluate_expression("1/1000000"), 0.000001);
+  }
+
+  #[test]
+  fn test_floor_division() {
+    assert_eq!(evaluate_expression("7//2"), 3.0);
+    assert_eq!(evaluate_expression("-7//2"), -4.0);
+    assert_eq!(evaluate_expression("7//-2"), -4.0);
+    assert_eq!(evaluate_expression("-7//-2"), 3.0);
+    assert_eq!(evaluate_expression("7.5//2.5"), 3.0);
+    assert_eq!(evaluate_expression("1+7//2*2"), 7.0);
+    assert_eq!(evaluate("7//0"), Err(CalcError::DivisionByZero));
+  }
+
+  #[test]
+  fn test_superscript_exponents() {
+    assert_eq!(evaluate_expression("2³"), 8.0);
+    assert_eq!(evaluate_expression("(1+2)²"), 9.0);
+    assert_eq!(evaluate_expression("2¹⁰"), 1024.0);
+    assert_eq!(evaluate_expression("3²+4²"), 25.0);
+    assert_eq!(evaluate_expression("-2²"), -4.0);
+    assert_eq!(evaluate_expression("2⁻¹"), 0.5);
+    assert_eq!(evaluate_expression("2⁰"), 1.0);
+  }
+
+  #[test]
+  fn test_floor_and_ceiling_brackets() {
+    assert_eq!(evaluate_expression("⌊3.7⌋"), 3.0);
+    assert_eq!(evaluate_expression("⌈3.2⌉"), 4.0);
+    assert_eq!(evaluate_expression("⌊-3.2⌋"), -4.0);
+    assert_eq!(evaluate_expression("2*⌊7/2⌋+1"), 7.0);
+    assert_eq!(evaluate_expression("⌈(1.5+1)*2⌉"), 5.0);
+    assert_eq!(evaluate_expression("⌊⌈1.2⌉/3⌋"), 0.0);
+    assert_eq!(evaluate_expression("(⌊2.5⌋+⌈2.5⌉)^2"), 25.0);
+    assert_eq!(evaluate_expression("-⌊2.5⌋"), -2.0);
+    assert_eq!(evaluate("⌊3.7⌉"), Err(CalcError::MismatchedParentheses));
+    assert_eq!(evaluate("⌊3.7)"), Err(CalcError::MismatchedParentheses));
+    assert_eq!(evaluate("(⌊3.7)⌋"), Err(CalcError::MismatchedParentheses));
+    assert_eq!(evaluate("⌊3.7"), Err(CalcError::MismatchedParentheses));
+  }
+
+  #[test]
+  fn test_implicit_multiplication() {
+    assert_eq!(evaluate_expression("2(3+4)"), 14.0);
+    assert_eq!(evaluate_expression("(1+1)(2+3)"), 10.0);
+    assert_eq!(evaluate_expression("2pi"), 2.0 * std::f64::consts::PI);
+    assert_eq!(evaluate_expression("3sqrt(4)"), 6.0);
+    assert_eq!(evaluate_expression("2²(3)"), 12.0);
+    assert_eq!(evaluate_expression("1+2(3)^2"), 19.0);
+  }
+
+  #[test]
+  fn test_comparisons() {
+    assert_eq!(evaluate_expression("1+1 == 2"), 1.0);
+    assert_eq!(evaluate_expression("2 == 3"), 0.0);
+    assert_eq!(evaluate_expression("2 != 3"), 1.0);
+    assert_eq!(evaluate_expression("(1 == 1) + (2 != 2)"), 1.0);
+    assert_eq!(evaluate_expression("1 == 1 == 1"), 1.0);
+    assert_eq!(
+      evaluate("2 =="),
+      Err(CalcError::MissingOperand {
+        operator: "==".to_string(),
+        side: OperandSide::Right
+      })
+    );
+  }
+
+  #[test]
+  fn test_unary_minus() {
+    assert_eq!(evaluate_expression("-2+5"), 3.0);
+    assert_eq!(evaluate_expression("2*-3"), -6.0);
+    assert_eq!(evaluate_expression("-2^2"), -4.0);
+    assert_eq!(evaluate_expression("2^-1"), 0.5);
+    assert_eq!(evaluate_expression("--3"), 3.0);
+  }
+
+  #[test]
+  fn test_errors() {
+    assert_eq!(evaluate(""), Err(CalcError::NoExpression));
+    assert_eq!(evaluate("(2+3"), Err(CalcError::MismatchedParentheses));
+    assert_eq!(evaluate("2+3)"), Err(CalcError::MismatchedParentheses));
+    assert_eq!(evaluate("()"), Err(CalcError::InvalidExpression));
+    assert_eq!(
+      evaluate("1.2.3"),
+      Err(CalcError::InvalidNumber {
+        number: "1.2.3".to_string(),
+        column: 4,
+        span: 0..5
+      })
+    );
+  }
+
+  #[test]
+  fn test_missing_operands() {
+    let missing = |operator: &str, side| {
+      Err(CalcError::MissingOperand {
+        operator: operator.to_string(),
+        side,
+      })
+    };
+    assert_eq!(evaluate("2+"), missing("+", OperandSide::Right));
+    assert_eq!(evaluate("*2"), missing("*", OperandSide::Left));
+    assert_eq!(evaluate("(2^)"), missing("^", OperandSide::Right));
+    assert_eq!(evaluate("2+*3"), missing("+", OperandSide::Right));
+    assert_eq!(evaluate("abs(/2)"), missing("/", OperandSide::Left));
+    assert_eq!(evaluate("3*-"), missing("-", OperandSide::Right));
+    assert_eq!(
+      evaluate("2+").unwrap_err().to_string(),
+      "Operator '+' is missing its right operand"
+    );
+  }
+}
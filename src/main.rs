@@ -1,236 +1,1790 @@
-use std::env::args;
+use std::env::{self, args};
+use std::fmt::Display;
+use std::fs::{self, File, OpenOptions};
+use std::io::{stdin, stdout, BufRead, IsTerminal, Write};
+use std::process::exit;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-#[derive(Copy, Clone, PartialEq, Debug)]
-enum Token {
-  Number(f32),
-  Operator(Operator),
-  Parenthesis(Parenthesis),
+use calculator::{
+  format_fixed, format_number, format_plain, format_radix, format_significant, parse_constants,
+  parse_rates, strip_comments, AngleUnit, BooleanArithmetic, CalcError, Config, Evaluator,
+  Histogram, IntegerDivision, IntegerPolicy, NanPolicy, NumberMode, Rational, Rounding, Value,
+  Wrap, ZeroPower,
+};
+
+/// How close a fraction printed by `--approx-fraction` has to be to the result.
+const APPROXIMATION_TOLERANCE: f64 = 1e-6;
+/// The largest denominator `--approx-fraction` will print.
+const APPROXIMATION_MAX_DENOMINATOR: i64 = 1000;
+/// Environment variable holding the expression to evaluate when none are given as arguments.
+const EXPRESSION_VARIABLE: &str = "CALC_EXPR";
+/// How many `#` the bar of the fullest bucket of a `--hist` histogram has.
+const HISTOGRAM_WIDTH: usize = 40;
+/// How often `--watch` checks whether the file has changed.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+/// The variable `--map` binds to the number on each line of standard input.
+const MAP_PLACEHOLDER: &str = "_";
+
+/// The variable and range given to `--table`.
+struct TableRange {
+  variable: String,
+  start: f64,
+  end: f64,
+  step: f64,
 }
 
-#[derive(Copy, Clone, PartialEq, Debug)]
-enum Operator {
-  Add,
-  Subtract,
-  Multiply,
-  Divide,
-  Power,
+#[derive(Default)]
+struct Options {
+  approx_fraction: bool,
+  periodic: bool,
+  solve: bool,
+  check: bool,
+  warn_overflow: bool,
+  quiet: bool,
+  plain: bool,
+  physics: bool,
+  digit_grouping: bool,
+  auto_close: bool,
+  units: bool,
+  angle_input_unit: AngleUnit,
+  angle_output_unit: AngleUnit,
+  integer_policy: IntegerPolicy,
+  nan_policy: NanPolicy,
+  integer_division: IntegerDivision,
+  boolean_arithmetic: BooleanArithmetic,
+  zero_power: ZeroPower,
+  strict_zero_power: bool,
+  wrap_bits: Option<u32>,
+  signed: bool,
+  epsilon: Option<f64>,
+  aliases: Vec<(String, String)>,
+  rates: Vec<(String, f64)>,
+  constants: Vec<(String, f64)>,
+  tokens_json: bool,
+  ast_json: bool,
+  json: bool,
+  interactive_error: bool,
+  normalize: bool,
+  /// Only check that expressions are valid, printing nothing for those that are.
+  check_syntax: bool,
+  /// Read expressions in Polish notation, operators first.
+  polish: bool,
+  show_simplify: bool,
+  precision: Option<u32>,
+  significant_figures: Option<u32>,
+  rounding: Rounding,
+  round_steps: Option<u32>,
+  decimal_point: Option<char>,
+  /// Separate the thousands of results, with `.` when the decimal point is `,` and `,` otherwise.
+  thousands_separators: bool,
+  base: Option<u32>,
+  prefix: bool,
+  table: Option<TableRange>,
+  histogram: Option<usize>,
+  /// The least width `--table` right-aligns its columns to.
+  column_width: Option<usize>,
+  csv: Option<String>,
+  files: Vec<String>,
+  watch: Option<String>,
+  map: Option<String>,
+  /// The file `--log` appends every evaluation to.
+  journal: Option<File>,
+  max_input_length: Option<usize>,
+  timeout_ms: Option<u64>,
+  max_operations: Option<usize>,
+  max_magnitude: Option<f64>,
+  sandbox: bool,
+  expressions: Vec<String>,
 }
 
-impl Operator {
-  /// [Order of operations](https://en.wikipedia.org/wiki/Order_of_operations)
-  fn precedence(&self) -> i32 {
-    match self {
-      Operator::Add | Operator::Subtract => 1,
-      Operator::Multiply | Operator::Divide => 2,
-      Operator::Power => 3,
+/// Parse the value following a flag like `--precision 3`.
+fn flag_value<T: FromStr>(
+  arguments: &mut impl Iterator<Item = String>,
+  flag: &str,
+) -> Result<T, String>
+where
+  T::Err: Display,
+{
+  let value = arguments
+    .next()
+    .ok_or_else(|| format!("Missing value for '{}'", flag))?;
+  value
+    .parse()
+    .map_err(|error| format!("Invalid value '{}' for '{}': {}", value, flag, error))
+}
+
+fn parse_args() -> Result<Options, String> {
+  let mut options = Options::default();
+  let mut arguments = args().skip(1);
+  while let Some(argument) = arguments.next() {
+    match argument.as_str() {
+      "--approx-fraction" => options.approx_fraction = true,
+      "--periodic" => options.periodic = true,
+      "--solve" => options.solve = true,
+      "--check" => options.check = true,
+      "--warn-overflow" => options.warn_overflow = true,
+      "--quiet" => options.quiet = true,
+      "--plain" => options.plain = true,
+      "--physics" => options.physics = true,
+      "--grouping" => options.digit_grouping = true,
+      "--thousands" => options.thousands_separators = true,
+      "--auto-close" => options.auto_close = true,
+      "--units" => options.units = true,
+      "--degrees" => {
+        options.angle_input_unit = AngleUnit::Degrees;
+        options.angle_output_unit = AngleUnit::Degrees;
+      }
+      "--integers" => options.integer_policy = flag_value(&mut arguments, "--integers")?,
+      "--nan" => options.nan_policy = flag_value(&mut arguments, "--nan")?,
+      "--int-div" => options.integer_division = flag_value(&mut arguments, "--int-div")?,
+      "--bools" => options.boolean_arithmetic = flag_value(&mut arguments, "--bools")?,
+      "--zero-pow" => options.zero_power = flag_value(&mut arguments, "--zero-pow")?,
+      "--strict-zero-pow" => options.strict_zero_power = true,
+      "--wrap" => options.wrap_bits = Some(flag_value(&mut arguments, "--wrap")?),
+      "--signed" => options.signed = true,
+      "--epsilon" => match flag_value(&mut arguments, "--epsilon")? {
+        epsilon if epsilon >= 0.0 && f64::is_finite(epsilon) => options.epsilon = Some(epsilon),
+        _ => return Err("'--epsilon' needs a finite, non-negative tolerance".to_string()),
+      },
+      "--alias" => {
+        let alias: String = flag_value(&mut arguments, "--alias")?;
+        let (name, expansion) = alias
+          .split_once('=')
+          .ok_or_else(|| format!("Invalid alias '{}', expected NAME=EXPANSION", alias))?;
+        options
+          .aliases
+          .push((name.trim().to_string(), expansion.to_string()));
+      }
+      "--rates" => {
+        let path: String = flag_value(&mut arguments, "--rates")?;
+        let csv =
+          fs::read_to_string(&path).map_err(|error| format!("Can't read '{}': {}", path, error))?;
+        options.rates = parse_rates(&csv).map_err(|error| error.to_string())?;
+      }
+      "--data" => {
+        let path: String = flag_value(&mut arguments, "--data")?;
+        let json =
+          fs::read_to_string(&path).map_err(|error| format!("Can't read '{}': {}", path, error))?;
+        options
+          .constants
+          .extend(parse_constants(&json).map_err(|error| error.to_string())?);
+      }
+      "--angle-input" => options.angle_input_unit = flag_value(&mut arguments, "--angle-input")?,
+      "--angle-output" => options.angle_output_unit = flag_value(&mut arguments, "--angle-output")?,
+      "--tokens-json" => options.tokens_json = true,
+      "--ast-json" => options.ast_json = true,
+      "--json" => options.json = true,
+      "--interactive-error" => options.interactive_error = true,
+      "--normalize" => options.normalize = true,
+      "--check-syntax" => options.check_syntax = true,
+      "--polish" => options.polish = true,
+      "--show-simplify" => options.show_simplify = true,
+      "--precision" => options.precision = Some(flag_value(&mut arguments, "--precision")?),
+      "--sigfigs" => match flag_value(&mut arguments, "--sigfigs")? {
+        0 => return Err("'--sigfigs' needs at least one figure".to_string()),
+        figures => options.significant_figures = Some(figures),
+      },
+      "--round-steps" => options.round_steps = Some(flag_value(&mut arguments, "--round-steps")?),
+      "--rounding" => options.rounding = flag_value(&mut arguments, "--rounding")?,
+      "--decimal-point" => match flag_value(&mut arguments, "--decimal-point")? {
+        point if char::is_ascii_digit(&point) => {
+          return Err("'--decimal-point' can't be a digit".to_string())
+        }
+        point => options.decimal_point = Some(point),
+      },
+      "--base" => match flag_value(&mut arguments, "--base")? {
+        base @ 2..=36 => options.base = Some(base),
+        _ => return Err("'--base' needs a base from 2 to 36".to_string()),
+      },
+      "--prefix" => options.prefix = true,
+      "--max-input-length" => {
+        options.max_input_length = Some(flag_value(&mut arguments, "--max-input-length")?)
+      }
+      "--sandbox" => options.sandbox = true,
+      "--timeout-ms" => options.timeout_ms = Some(flag_value(&mut arguments, "--timeout-ms")?),
+      "--max-ops" => options.max_operations = Some(flag_value(&mut arguments, "--max-ops")?),
+      "--max-magnitude" => match flag_value(&mut arguments, "--max-magnitude")? {
+        limit if limit > 0.0 && f64::is_finite(limit) => options.max_magnitude = Some(limit),
+        _ => return Err("'--max-magnitude' needs a finite, positive limit".to_string()),
+      },
+      "--table" => {
+        options
+          .expressions
+          .push(flag_value(&mut arguments, "--table")?);
+        options.table = Some(TableRange {
+          variable: flag_value(&mut arguments, "--table")?,
+          start: flag_value(&mut arguments, "--table")?,
+          end: flag_value(&mut arguments, "--table")?,
+          step: flag_value(&mut arguments, "--table")?,
+        });
+      }
+      "--hist" => options.histogram = Some(flag_value(&mut arguments, "--hist")?),
+      "--column-width" => {
+        options.column_width = Some(flag_value(&mut arguments, "--column-width")?)
+      }
+      "--csv" => options.csv = Some(flag_value(&mut arguments, "--csv")?),
+      "--file" => options.files.push(flag_value(&mut arguments, "--file")?),
+      "--watch" => options.watch = Some(flag_value(&mut arguments, "--watch")?),
+      "--map" => options.map = Some(flag_value(&mut arguments, "--map")?),
+      "--log" => {
+        let path: String = flag_value(&mut arguments, "--log")?;
+        options.journal = Some(open_journal(&path)?);
+      }
+      flag if flag.starts_with("--") => return Err(format!("Unknown option '{}'", flag)),
+      _ => options.expressions.push(argument),
     }
   }
+  if options
+    .wrap_bits
+    .is_some_and(|bits| Wrap::new(bits, options.signed).is_none())
+  {
+    return Err("'--wrap' takes a number of bits between 1 and 64".to_string());
+  }
+  if options.signed && options.wrap_bits.is_none() {
+    return Err("'--signed' only applies to '--wrap'".to_string());
+  }
+  if !options.files.is_empty() && !options.expressions.is_empty() {
+    return Err("'--file' can't be combined with expressions".to_string());
+  }
+  if options.watch.is_some() && (!options.files.is_empty() || !options.expressions.is_empty()) {
+    return Err("'--watch' can't be combined with '--file' or expressions".to_string());
+  }
+  if options.map.is_some()
+    && (!options.files.is_empty() || options.watch.is_some() || !options.expressions.is_empty())
+  {
+    return Err("'--map' can't be combined with '--file', '--watch' or expressions".to_string());
+  }
+  if options.csv.is_some() && options.table.is_none() {
+    return Err("'--csv' only applies to '--table'".to_string());
+  }
+  if options.histogram.is_some() && (options.table.is_none() || options.csv.is_some()) {
+    return Err("'--hist' only applies to '--table' without '--csv'".to_string());
+  }
+  if options.column_width.is_some()
+    && (options.table.is_none() || options.csv.is_some() || options.histogram.is_some())
+  {
+    return Err(
+      "'--column-width' only applies to '--table' without '--csv' or '--hist'".to_string(),
+    );
+  }
+  if options.precision.is_some() && options.significant_figures.is_some() {
+    return Err("'--precision' and '--sigfigs' can't be combined".to_string());
+  }
+  Ok(options)
+}
 
-  /// [Operator associativity](https://en.wikipedia.org/wiki/Operator_associativity)
-  fn associativity(&self) -> Associativity {
-    match self {
-      Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide => {
-        Associativity::Left
-      }
-      Operator::Power => Associativity::Right,
+fn new_evaluator(options: &Options) -> Evaluator {
+  let defaults = Config::default();
+  let mut evaluator = Evaluator::with_config(Config {
+    max_input_length: options
+      .max_input_length
+      .unwrap_or(defaults.max_input_length),
+    digit_grouping: options.digit_grouping,
+    auto_close: options.auto_close,
+    units: options.units,
+    round_steps: options.round_steps,
+    angle_input_unit: options.angle_input_unit,
+    angle_output_unit: options.angle_output_unit,
+    integer_policy: options.integer_policy,
+    nan_policy: options.nan_policy,
+    integer_division: options.integer_division,
+    boolean_arithmetic: options.boolean_arithmetic,
+    zero_power: options.zero_power,
+    strict_zero_power: options.strict_zero_power,
+    wrap: options
+      .wrap_bits
+      .and_then(|bits| Wrap::new(bits, options.signed)),
+    epsilon: options.epsilon.unwrap_or(defaults.epsilon),
+    timeout: options.timeout_ms.map(Duration::from_millis),
+    max_operations: options.max_operations,
+    max_magnitude: options.max_magnitude,
+    sandbox: options.sandbox,
+    mode: if options.periodic {
+      NumberMode::Fraction
+    } else {
+      defaults.mode
+    },
+    ..defaults
+  });
+  register_options(&mut evaluator, options);
+  evaluator
+}
+
+/// Register whatever the options add on top of the configuration.
+fn register_options(evaluator: &mut Evaluator, options: &Options) {
+  if options.physics {
+    evaluator.register_physics_constants();
+  }
+  for (name, expansion) in &options.aliases {
+    evaluator.define_alias(name, expansion);
+  }
+  for (name, value) in &options.constants {
+    evaluator.register_const(name, *value);
+  }
+  for (pair, rate) in &options.rates {
+    evaluator.set_rate(pair, *rate);
+  }
+}
+
+fn format_result(result: f64, options: &Options) -> String {
+  // Whole numbers in another base, whenever they fit an integer.
+  if let Some(base) = options.base {
+    if result.fract() == 0.0 && result.abs() < i64::MAX as f64 {
+      return format_radix(result as i64, base, options.prefix);
+    }
+  }
+  let approximation = (options.approx_fraction && !options.quiet)
+    .then(|| {
+      Rational::approximate(
+        result,
+        APPROXIMATION_TOLERANCE,
+        APPROXIMATION_MAX_DENOMINATOR,
+      )
+    })
+    .flatten()
+    .filter(|fraction| !fraction.is_integer());
+  let decimal = match (options.precision, options.significant_figures) {
+    (Some(precision), _) => format_fixed(result, precision, options.rounding),
+    (None, Some(figures)) => format_significant(result, figures, options.rounding),
+    (None, None) if options.plain => format_plain(result),
+    (None, None) => format_number(result),
+  };
+  let decimal = match options.decimal_point {
+    Some(point) => decimal.replace('.', &point.to_string()),
+    None => decimal,
+  };
+  let decimal = match options.decimal_point {
+    _ if !options.thousands_separators => decimal,
+    Some(',') => group_thousands(&decimal, '.'),
+    _ => group_thousands(&decimal, ','),
+  };
+  match approximation {
+    Some(fraction) => format!("{} ≈ {}", decimal, fraction),
+    None => decimal,
+  }
+}
+
+/// Separate every three digits of the whole part of a decimal like `-1234567.5` with
+/// `separator`, as in `-1,234,567.5`. The decimals are left alone, and so is anything with an
+/// exponent, like `1.2e18`.
+fn group_thousands(decimal: &str, separator: char) -> String {
+  if decimal.contains('e') {
+    return decimal.to_string();
+  }
+  let unsigned = decimal.trim_start_matches('-');
+  let whole = unsigned
+    .find(|c: char| !c.is_ascii_digit())
+    .unwrap_or(unsigned.len());
+  let (digits, rest) = unsigned.split_at(whole);
+  let mut grouped = decimal[..decimal.len() - unsigned.len()].to_string();
+  for (i, digit) in digits.chars().enumerate() {
+    if i > 0 && (digits.len() - i).is_multiple_of(3) {
+      grouped.push(separator);
+    }
+    grouped.push(digit);
+  }
+  grouped + rest
+}
+
+/// Format a result, which may be a list of results.
+fn format_value(value: &Value, options: &Options) -> String {
+  match value {
+    Value::List(items) => {
+      let items: Vec<String> = items
+        .iter()
+        .map(|item| format_value(item, options))
+        .collect();
+      format!("[{}]", items.join(", "))
+    }
+    Value::Boolean(boolean) => boolean.to_string(),
+    Value::Text(text) => text.clone(),
+    Value::Rational(fraction) if options.periodic => fraction
+      .periodic_decimal()
+      .unwrap_or_else(|| format_result(fraction.to_f64(), options)),
+    Value::Quantity(magnitude, dimension) => {
+      format!("{} {}", format_result(*magnitude, options), dimension)
+    }
+    number => format_result(number.to_f64(), options),
+  }
+}
+
+/// Evaluate a line into its output, printing what `debug` calls noted along the way to stderr.
+fn evaluate_line(
+  evaluator: &mut Evaluator,
+  expression: &str,
+  options: &Options,
+) -> Result<String, CalcError> {
+  let output = line_output(evaluator, expression, options);
+  for annotation in evaluator.take_annotations() {
+    eprintln!("{}", annotation);
+  }
+  if let Some(journal) = &options.journal {
+    record(journal, expression, &output, SystemTime::now());
+  }
+  if options.json {
+    return Ok(match output {
+      Ok(output) => format!("{{\"result\":{:?}}}", output),
+      Err(error) => format!(
+        "{{\"error\":{:?},\"error_code\":\"{}\"}}",
+        error.to_string(),
+        error.code()
+      ),
+    });
+  }
+  output
+}
+
+fn line_output(
+  evaluator: &mut Evaluator,
+  expression: &str,
+  options: &Options,
+) -> Result<String, CalcError> {
+  if options.tokens_json {
+    return evaluator.tokens_json(expression);
+  }
+  if options.ast_json {
+    return evaluator.ast_json(expression);
+  }
+  if options.normalize {
+    return evaluator.normalize(expression);
+  }
+  if options.check_syntax {
+    return evaluator.validate(expression).map(|()| String::new());
+  }
+  if options.show_simplify {
+    return Ok(evaluator.simplify_steps(expression)?.join(" → "));
+  }
+  if let (Some(range), Some(buckets)) = (&options.table, options.histogram) {
+    let histogram = evaluator.histogram(
+      expression,
+      &range.variable,
+      range.start,
+      range.end,
+      range.step,
+      buckets,
+    )?;
+    return Ok(histogram_lines(&histogram, options).join("\n"));
+  }
+  if let Some(range) = &options.table {
+    let rows = evaluator.table(
+      expression,
+      &range.variable,
+      range.start,
+      range.end,
+      range.step,
+    )?;
+    let (mut xs, mut values): (Vec<String>, Vec<String>) = rows
+      .into_iter()
+      .map(|(x, value)| (format_result(x, options), format_result(value, options)))
+      .unzip();
+    xs.insert(0, range.variable.clone());
+    values.insert(0, "value".to_string());
+    if options.csv.is_none() {
+      let point = options.decimal_point.unwrap_or('.');
+      let width = options.column_width.unwrap_or(0);
+      align_column(&mut xs, point, width);
+      align_column(&mut values, point, width);
     }
+    let lines: Vec<String> = xs
+      .iter()
+      .zip(&values)
+      .map(|(x, value)| match options.csv {
+        Some(_) => format!("{},{}", csv_field(x), csv_field(value)),
+        None => format!("{}  {}", x, value),
+      })
+      .collect();
+    return Ok(lines.join("\n"));
   }
+  if options.solve {
+    return Ok(evaluator.solve(expression)?.to_string());
+  }
+  if options.check {
+    return Ok(evaluator.holds(expression)?.to_string());
+  }
+  let results = if options.polish {
+    vec![evaluator.evaluate_prefix(expression)?]
+  } else {
+    evaluator.evaluate_statements(expression)?
+  };
+  if options.warn_overflow && !options.quiet {
+    if let Some(step) = evaluator.first_non_finite() {
+      eprintln!("Warning: first non-finite step: {}", step);
+    }
+  }
+  let results: Vec<String> = results
+    .iter()
+    .map(|result| format_value(result, options))
+    .collect();
+  Ok(results.join("\n"))
 }
 
-#[derive(Copy, Clone, PartialEq, Debug)]
-enum Parenthesis {
-  Left,
-  Right,
+/// Give the plain decimals of a table column, like `0.5` and `12`, the same number of decimals,
+/// `0.5` and `12.0`, then right-align every cell to the widest or to `width`, whichever is wider.
+/// Anything else, like the header or `inf`, only gets aligned.
+fn align_column(cells: &mut [String], point: char, width: usize) {
+  let is_decimal = |cell: &str| {
+    cell
+      .trim_start_matches('-')
+      .chars()
+      .all(|c| c.is_ascii_digit() || c == point)
+      && cell.ends_with(|c: char| c.is_ascii_digit())
+  };
+  let decimals = |cell: &str| {
+    cell
+      .split_once(point)
+      .map_or(0, |(_, fraction)| fraction.chars().count())
+  };
+  let most = cells
+    .iter()
+    .filter(|cell| is_decimal(cell))
+    .map(|cell| decimals(cell))
+    .max()
+    .unwrap_or(0);
+  for cell in cells.iter_mut().filter(|cell| is_decimal(cell)) {
+    let missing = most - decimals(cell);
+    if missing > 0 && !cell.contains(point) {
+      cell.push(point);
+    }
+    cell.push_str(&"0".repeat(missing));
+  }
+  let width = cells
+    .iter()
+    .map(|cell| cell.chars().count())
+    .fold(width, usize::max);
+  for cell in cells.iter_mut() {
+    *cell = format!("{:>width$}", cell);
+  }
 }
 
-#[derive(PartialEq)]
-enum Associativity {
-  Left,
-  Right,
+/// One line of `LOW to HIGH  COUNT  ###` per bucket, with the fullest bucket's bar
+/// [`HISTOGRAM_WIDTH`] long.
+fn histogram_lines(histogram: &Histogram, options: &Options) -> Vec<String> {
+  let fullest = histogram.counts.iter().copied().max().unwrap_or(0).max(1);
+  let lines: Vec<[String; 2]> = (0..histogram.counts.len())
+    .map(|i| {
+      let (low, high) = histogram.bounds(i);
+      [
+        format!(
+          "{} to {}",
+          format_result(low, options),
+          format_result(high, options)
+        ),
+        histogram.counts[i].to_string(),
+      ]
+    })
+    .collect();
+  let width = |column: usize| {
+    lines
+      .iter()
+      .map(|line| line[column].len())
+      .max()
+      .unwrap_or(0)
+  };
+  let (range_width, count_width) = (width(0), width(1));
+  lines
+    .iter()
+    .zip(&histogram.counts)
+    .map(|([range, count], &n)| {
+      let bar = "#".repeat(n * HISTOGRAM_WIDTH / fullest);
+      format!("{:<range_width$}  {:>count_width$}  {}", range, count, bar)
+        .trim_end()
+        .to_string()
+    })
+    .collect()
 }
 
-impl Associativity {
-  fn is_left(&self) -> bool {
-    *self == Associativity::Left
+/// Quote a CSV field if it contains anything that would otherwise split or end it.
+fn csv_field(field: &str) -> String {
+  if field.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", field.replace('"', "\"\""))
+  } else {
+    field.to_string()
   }
+}
+
+/// Print the output of a line, or write it to the file given to `--csv`. Lines whose statements
+/// were all suppressed with `;` print nothing. Failing to print, say to a closed pipe, is an error
+/// like any other rather than a panic.
+fn emit(output: &str, options: &Options) -> Result<(), String> {
+  match &options.csv {
+    None if output.is_empty() => Ok(()),
+    Some(path) => fs::write(path, format!("{}\n", output))
+      .map_err(|error| format!("Can't write '{}': {}", path, error)),
+    None => write_stdout(&format!("{}\n", output)),
+  }
+}
+
+/// Write to standard output and flush it, so that a prompt shows before the input it asks for.
+fn write_stdout(text: &str) -> Result<(), String> {
+  let mut stdout = stdout();
+  write!(stdout, "{}", text)
+    .and_then(|()| stdout.flush())
+    .map_err(|error| format!("Can't write to standard output: {}", error))
+}
+
+/// Report a failed step of the REPL on standard error, giving whether it succeeded.
+fn reported(result: Result<(), String>) -> bool {
+  match result {
+    Ok(()) => true,
+    Err(error) => {
+      eprintln!("Error: {}", error);
+      false
+    }
+  }
+}
 
-  fn is_right(&self) -> bool {
-    *self == Associativity::Right
+/// Pick the expressions to evaluate: explicit arguments win over `CALC_EXPR`, which is ignored
+/// when empty. With neither, expressions are read from standard input instead.
+fn select_expressions(arguments: Vec<String>, environment: Option<String>) -> Vec<String> {
+  if !arguments.is_empty() {
+    return arguments;
   }
+  environment
+    .filter(|expression| !expression.trim().is_empty())
+    .into_iter()
+    .collect()
 }
 
-/// Convert a vector of tokens into reverse polish notation.
-/// ([Shunting Yard](https://aquarchitect.github.io/swift-algorithm-club/Shunting%20Yard/))
-fn shunting_yard(tokens: Vec<Token>) -> Vec<Token> {
-  let mut output: Vec<Token> = Vec::new();
-  let mut stack: Vec<Token> = Vec::new();
+/// Open the file given to `--log` for appending, creating it if it doesn't exist.
+fn open_journal(path: &str) -> Result<File, String> {
+  OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(path)
+    .map_err(|error| format!("Can't open the journal '{}': {}", path, error))
+}
+
+/// Append a line with the time, the expression and its output or error to the journal. Failing to
+/// is reported without stopping the evaluation.
+fn record(
+  mut journal: &File,
+  expression: &str,
+  output: &Result<String, CalcError>,
+  time: SystemTime,
+) {
+  let outcome = match output {
+    Ok(output) => output.replace('\n', " "),
+    Err(error) => format!("Error: {}", error),
+  };
+  let entry = format!(
+    "{}\t{}\t{}\n",
+    utc_timestamp(time),
+    expression.replace('\n', " "),
+    outcome
+  );
+  if let Err(error) = journal
+    .write_all(entry.as_bytes())
+    .and_then(|()| journal.flush())
+  {
+    eprintln!("Error: Can't write to the journal: {}", error);
+  }
+}
+
+/// A time in UTC like `2024-03-01T12:30:05Z`.
+fn utc_timestamp(time: SystemTime) -> String {
+  let seconds = time
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |since| since.as_secs());
+  let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+  // Howard Hinnant's civil-from-days, counting in eras of 400 years from 0000-03-01.
+  let days = days + 719_468;
+  let era = days / 146_097;
+  let day_of_era = days % 146_097;
+  let year_of_era =
+    (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let month = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * month + 2) / 5 + 1;
+  let month = if month < 10 { month + 3 } else { month - 9 };
+  let year = year_of_era + era * 400 + u64::from(month <= 2);
+  format!(
+    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+    year,
+    month,
+    day,
+    seconds / 3600,
+    seconds % 3600 / 60,
+    seconds % 60
+  )
+}
+
+/// Whether the output of a line counts as success, which with `--check` means the equation held.
+/// With `--json` an error is part of the output rather than reported on its own.
+fn passed(output: &str, options: &Options) -> bool {
+  if !options.json {
+    return !(options.check && output == "false");
+  }
+  let failed = output.starts_with("{\"error\"");
+  !(failed || options.check && output == "{\"result\":\"false\"}")
+}
+
+/// Evaluate expressions given as arguments one after another with a shared evaluator, so later
+/// ones can use the variables of earlier ones. Returns whether every expression succeeded.
+fn evaluate_arguments(
+  expressions: &[String],
+  options: &Options,
+  mut emit: impl FnMut(&str) -> Result<(), String>,
+) -> bool {
+  let mut evaluator = new_evaluator(options);
+  let mut succeeded = true;
+  for expression in expressions {
+    let result = evaluate_line(&mut evaluator, expression, options)
+      .map_err(|error| error.to_string())
+      .and_then(|output| emit(&output).map(|()| output));
+    match result {
+      Ok(output) => succeeded &= passed(&output, options),
+      Err(error) => {
+        eprintln!("Error: {}", error);
+        succeeded = false;
+      }
+    }
+  }
+  succeeded
+}
+
+/// Evaluate every line of each file in turn, each file with an evaluator of its own so one can't
+/// affect the next. With more than one file, outputs and errors start with the file they came
+/// from. Returns whether every line of every file succeeded.
+fn evaluate_files(
+  paths: &[String],
+  options: &Options,
+  mut emit: impl FnMut(&str) -> Result<(), String>,
+) -> bool {
+  let label = |path: &str, text: &str| match paths.len() {
+    1 => text.to_string(),
+    _ => format!("{}: {}", path, text),
+  };
+  let mut succeeded = true;
+  for path in paths {
+    let contents = match fs::read_to_string(path) {
+      Ok(contents) => contents,
+      Err(error) => {
+        eprintln!("Error: Can't read '{}': {}", path, error);
+        succeeded = false;
+        continue;
+      }
+    };
+    succeeded &= evaluate_contents(&contents, options, |text| label(path, text), &mut emit);
+  }
+  succeeded
+}
 
-  tokens.iter().for_each(|&token| match token {
-    Token::Operator(operator) => {
-      while let Some(Token::Operator(top)) = stack.last() {
-        if (operator.associativity().is_left() && operator.precedence() <= top.precedence())
-          || (operator.associativity().is_right() && operator.precedence() < top.precedence())
-        {
-          output.push(stack.pop().unwrap());
-        } else {
-          break;
+/// Evaluate every line of the contents of a file with an evaluator of its own, passing outputs
+/// and errors through `label` first. Returns whether every line succeeded.
+fn evaluate_contents(
+  contents: &str,
+  options: &Options,
+  label: impl Fn(&str) -> String,
+  mut emit: impl FnMut(&str) -> Result<(), String>,
+) -> bool {
+  let mut evaluator = new_evaluator(options);
+  let mut pending = String::new();
+  let mut succeeded = true;
+  for line in contents.lines() {
+    let Some(line) = complete_line(&evaluator, &mut pending, line.to_string()) else {
+      continue;
+    };
+    let line = line.trim();
+    if line.is_empty() || strip_comments(line).is_ok_and(|line| line.trim().is_empty()) {
+      continue;
+    }
+    let result = evaluate_line(&mut evaluator, line, options)
+      .map_err(|error| error.to_string())
+      .and_then(|output| {
+        for line in output.lines() {
+          emit(&label(line))?;
         }
+        Ok(output)
+      });
+    match result {
+      Ok(output) => succeeded &= passed(&output, options),
+      Err(error) => {
+        eprintln!("Error: {}", label(&error));
+        succeeded = false;
       }
-      stack.push(token);
     }
-    Token::Parenthesis(paren) => match paren {
-      Parenthesis::Left => stack.push(token),
-      Parenthesis::Right => loop {
-        let popped = stack.pop().expect("Error: Mismatched parentheses");
-        if popped == Token::Parenthesis(Parenthesis::Left) {
-          break;
+  }
+  if !pending.is_empty() {
+    if let Err(error) = evaluator.validate(&pending) {
+      eprintln!("Error: {}", label(&error.to_string()));
+      succeeded = false;
+    }
+  }
+  succeeded
+}
+
+/// Call `changed` with the contents of the file given to `--watch` at first and whenever they
+/// change, checking every `interval` for as long as `keep_watching` says. A file that can't be
+/// read, say because an editor is replacing it, is reported once and then waited for.
+///
+/// This polls instead of using file system notifications, which would mean the first dependency
+/// of the crate, with a backend for every platform, and don't work on every file system. The
+/// `--watch` files are calculations of a few lines, so reading them every interval is cheap, and
+/// comparing contents rather than modification times can't miss an edit within the same tick.
+fn watch(
+  path: &str,
+  interval: Duration,
+  mut keep_watching: impl FnMut() -> bool,
+  mut changed: impl FnMut(&str),
+) {
+  let mut last: Option<String> = None;
+  let mut unreadable = false;
+  while keep_watching() {
+    match fs::read_to_string(path) {
+      Ok(contents) => {
+        unreadable = false;
+        if last.as_ref() != Some(&contents) {
+          changed(&contents);
+          last = Some(contents);
         }
-        output.push(popped);
-      },
-    },
-    Token::Number(_) => output.push(token),
-  });
+      }
+      Err(error) if !unreadable => {
+        eprintln!("Error: Can't read '{}': {}, waiting for it", path, error);
+        unreadable = true;
+      }
+      Err(_) => {}
+    }
+    thread::sleep(interval);
+  }
+}
 
-  while let Some(token) = stack.pop() {
-    output.push(token);
+/// Evaluate `template` once for every line of `input`, with [`MAP_PLACEHOLDER`] bound to the number
+/// on it, passing each result to `emit`. A line that isn't a number, or whose result is an error,
+/// is reported and skipped. Returns whether every line succeeded.
+fn map_lines(
+  template: &str,
+  input: impl BufRead,
+  options: &Options,
+  mut emit: impl FnMut(&str) -> Result<(), String>,
+) -> bool {
+  let mut evaluator = new_evaluator(options);
+  // A template that can't work for any number is reported once rather than on every line.
+  let valid = evaluator
+    .set_variable(MAP_PLACEHOLDER, Value::Integer(0))
+    .and_then(|()| evaluator.validate(template));
+  if let Err(error) = valid {
+    eprintln!("Error: {}", error);
+    return false;
   }
+  let mut succeeded = true;
+  for line in input.lines() {
+    let line = match line {
+      Ok(line) => line,
+      Err(error) => {
+        eprintln!("Error: Can't read standard input: {}", error);
+        return false;
+      }
+    };
+    let line = line.trim();
+    if line.is_empty() {
+      continue;
+    }
+    let result = line
+      .parse()
+      .map_err(|_| format!("'{}' isn't a number", line))
+      .and_then(|number| {
+        evaluator
+          .set_variable(MAP_PLACEHOLDER, Value::Float(number))
+          .and_then(|()| evaluate_line(&mut evaluator, template, options))
+          .map_err(|error| error.to_string())
+      })
+      .and_then(|output| emit(&output).map(|()| output));
+    match result {
+      Ok(output) => succeeded &= passed(&output, options),
+      Err(error) => {
+        eprintln!("Error: {}", error);
+        succeeded = false;
+      }
+    }
+  }
+  succeeded
+}
 
-  output
+/// Show where a malformed line went wrong, with a caret under the column if the error has one.
+fn point_out(line: &str, error: &CalcError) -> String {
+  match error.column() {
+    Some(column) => format!("  {}\n  {}^", line, " ".repeat(column - 1)),
+    None => format!("  {}", line),
+  }
 }
 
-/// Evaluate a reverse polish notation expression represented as a vector of tokens.
-fn evaluate_rpn(tokens: Vec<Token>) -> f32 {
-  let mut stack: Vec<f32> = Vec::new();
-
-  tokens.iter().for_each(|&token| match token {
-    Token::Number(n) => stack.push(n),
-    Token::Operator(operator) => {
-      let right = stack.pop().expect("Error: Invalid expression");
-      let left = stack.pop().expect("Error: Invalid expression");
-      let result = match operator {
-        Operator::Add => left + right,
-        Operator::Subtract => left - right,
-        Operator::Multiply => left * right,
-        Operator::Divide => left / right,
-        Operator::Power => left.powf(right),
+/// Evaluate standard input line by line, sharing variables between lines. Prompts when attached
+/// to a terminal. Returns whether every line evaluated successfully.
+fn repl(options: &Options) -> bool {
+  let interactive = stdin().is_terminal();
+  let mut evaluator = new_evaluator(options);
+  let mut succeeded = true;
+  let mut lines = stdin().lock().lines();
+  // With `--interactive-error`, set after a malformed line to ask for its correction.
+  let mut fixing = false;
+  // The lines so far of an expression that isn't complete yet.
+  let mut pending = String::new();
+  // The last expression evaluated, for `edit`.
+  let mut previous: Option<String> = None;
+
+  loop {
+    if interactive {
+      let prompt = match (fixing, pending.is_empty()) {
+        (true, _) => "fix> ",
+        (false, true) => "> ",
+        (false, false) => "... ",
       };
-      stack.push(result);
+      succeeded &= reported(write_stdout(prompt));
     }
-    _ => panic!("Error: Invalid expression"),
-  });
+    let Some(Ok(line)) = lines.next() else {
+      break;
+    };
+    let Some(line) = complete_line(&evaluator, &mut pending, line) else {
+      continue;
+    };
+    let line = line.trim();
+    if line.is_empty() || strip_comments(line).is_ok_and(|line| line.trim().is_empty()) {
+      continue;
+    }
+    if interactive && matches!(line, "exit" | "quit") {
+      break;
+    }
+    if line == "reset" {
+      evaluator.reset();
+      register_options(&mut evaluator, options);
+      continue;
+    }
+    if line == "total on" {
+      evaluator.start_total();
+      continue;
+    }
+    if line == "total off" {
+      if let Some(total) = evaluator.end_total() {
+        succeeded &= reported(write_stdout(&format!(
+          "{}\n",
+          format_value(&total, options)
+        )));
+      }
+      continue;
+    }
+    if evaluator.total().is_some() {
+      match evaluator.add_to_total(line) {
+        Ok(total) => {
+          succeeded &= reported(write_stdout(&format!(
+            "{}\n",
+            format_value(&total, options)
+          )))
+        }
+        Err(error) => {
+          eprintln!("Error: {}", error);
+          succeeded = false;
+        }
+      }
+      continue;
+    }
+    if line == "config" {
+      succeeded &= reported(write_stdout(&format!("{}\n", evaluator.config)));
+      continue;
+    }
+    if line == "functions" {
+      let names: String = evaluator
+        .known_names()
+        .iter()
+        .map(|name| format!("{}\n", name))
+        .collect();
+      succeeded &= reported(write_stdout(&names));
+      continue;
+    }
+    if let Some(saving) = line.strip_prefix("save ") {
+      let saved = match saving.split_once('=') {
+        Some((name, expression)) => evaluator.save(name.trim(), expression.trim()),
+        None => Err(CalcError::InvalidAssignment),
+      };
+      if let Err(error) = saved {
+        eprintln!("Error: {}", error);
+        succeeded = false;
+      }
+      continue;
+    }
+    if let Some(name) = line.strip_prefix("use ") {
+      match evaluator.use_saved(name.trim()) {
+        Ok(value) => {
+          succeeded &= reported(write_stdout(&format!(
+            "{}\n",
+            format_value(&value, options)
+          )))
+        }
+        Err(error) => {
+          eprintln!("Error: {}", error);
+          succeeded = false;
+        }
+      }
+      continue;
+    }
+    if let Some(n) = line.strip_prefix("recall ") {
+      match n.trim().parse().ok().and_then(|n| evaluator.recall(n)) {
+        Some(value) => {
+          succeeded &= reported(write_stdout(&format!(
+            "{}\n",
+            format_value(&value, options)
+          )))
+        }
+        None => {
+          eprintln!("Error: No result '{}' to recall", n.trim());
+          succeeded = false;
+        }
+      }
+      continue;
+    }
+    let expression = match remember_expression(&mut previous, line) {
+      Ok(expression) => expression,
+      Err(error) => {
+        eprintln!("Error: {}", error);
+        succeeded = false;
+        continue;
+      }
+    };
+    if let Some(change) = edit_change(line) {
+      if change.is_empty() || interactive {
+        succeeded &= reported(write_stdout(&format!("{}\n", expression)));
+      }
+      if change.is_empty() {
+        continue;
+      }
+    }
+    let line = expression.as_str();
+    let result = evaluate_line(&mut evaluator, line, options);
+    fixing = false;
+    if let Err(error) = &result {
+      if interactive && options.interactive_error && error.is_syntax_error() {
+        eprintln!("Error: {}\n{}", error, point_out(line, error));
+        fixing = true;
+        continue;
+      }
+    }
+    let result = result
+      .map_err(|error| error.to_string())
+      .and_then(|output| emit(&output, options).map(|()| output));
+    match result {
+      Ok(output) => succeeded &= passed(&output, options),
+      Err(error) => {
+        eprintln!("Error: {}", error);
+        succeeded = false;
+      }
+    }
+  }
 
-  stack.pop().expect("Error: Invalid expression")
-}
-
-/// Tokenise a string into a vector of tokens.
-fn tokenise(str: &mut str) -> Vec<Token> {
-  let mut tokens = Vec::new();
-  let mut number_buffer = String::new();
-
-  str.chars().for_each(|c| match c {
-    '0'..='9' | '.' | ',' => number_buffer.push(c),
-    '+' => push_non_number(
-      &mut tokens,
-      &mut number_buffer,
-      Token::Operator(Operator::Add),
-    ),
-    '-' => push_non_number(
-      &mut tokens,
-      &mut number_buffer,
-      Token::Operator(Operator::Subtract),
-    ),
-    '*' => push_non_number(
-      &mut tokens,
-      &mut number_buffer,
-      Token::Operator(Operator::Multiply),
-    ),
-    '/' | ':' => push_non_number(
-      &mut tokens,
-      &mut number_buffer,
-      Token::Operator(Operator::Divide),
-    ),
-    '^' => push_non_number(
-      &mut tokens,
-      &mut number_buffer,
-      Token::Operator(Operator::Power),
-    ),
-    '(' => push_non_number(
-      &mut tokens,
-      &mut number_buffer,
-      Token::Parenthesis(Parenthesis::Left),
-    ),
-    ')' => push_non_number(
-      &mut tokens,
-      &mut number_buffer,
-      Token::Parenthesis(Parenthesis::Right),
-    ),
-    _ => (),
-  });
+  if !pending.is_empty() {
+    if let Err(error) = evaluator.validate(&pending) {
+      eprintln!("Error: {}", error);
+      succeeded = false;
+    }
+  }
+  succeeded
+}
 
-  empty_number_buffer(&mut tokens, &mut number_buffer);
+/// The change of an `edit` line, empty for a bare `edit`, or `None` for any other line.
+fn edit_change(line: &str) -> Option<&str> {
+  line
+    .strip_prefix("edit")
+    .filter(|change| change.is_empty() || change.starts_with(' '))
+    .map(str::trim)
+}
 
-  tokens
+/// The expression a line of the REPL stands for, applying an `edit` line to the previous
+/// expression, and remembered as the previous expression for the next `edit`.
+fn remember_expression(previous: &mut Option<String>, line: &str) -> Result<String, String> {
+  let expression = match edit_change(line) {
+    Some(change) => edit_previous(previous.as_deref(), change)?,
+    None => line.to_string(),
+  };
+  *previous = Some(expression.clone());
+  Ok(expression)
 }
 
-/// Push a non-number token onto the token vector and clear the number buffer in one go.
-fn push_non_number(tokens: &mut Vec<Token>, number_buffer: &mut String, token: Token) {
-  assert!(!matches!(token, Token::Number(_)));
-  empty_number_buffer(tokens, number_buffer);
-  tokens.push(token);
+/// Apply an `edit OLD -> NEW` change to the previous expression, replacing the first `OLD` in it
+/// with `NEW`. Without a change the previous expression is given as it is.
+fn edit_previous(previous: Option<&str>, change: &str) -> Result<String, String> {
+  let previous = previous.ok_or("There is no previous expression to edit")?;
+  if change.is_empty() {
+    return Ok(previous.to_string());
+  }
+  let (old, new) = change
+    .split_once("->")
+    .ok_or("'edit' takes a change like 'edit 2 -> 3'")?;
+  let old = old.trim();
+  if old.is_empty() || !previous.contains(old) {
+    return Err(format!("'{}' isn't in the previous expression", old));
+  }
+  Ok(previous.replacen(old, new.trim(), 1))
 }
 
-fn empty_number_buffer(tokens: &mut Vec<Token>, number_buffer: &mut String) {
-  if !number_buffer.is_empty() {
-    tokens.push(Token::Number(
-      number_buffer
-        .parse()
-        .expect("Error: Invalid number in expression"),
-    ));
-    number_buffer.clear();
+/// Add a line of input to the lines before it that didn't complete an expression, giving the
+/// whole expression once it is complete. It isn't while a `/* ... */` comment is still open, or
+/// while it ends in an operator waiting for its right operand, like `5 +`.
+fn complete_line(evaluator: &Evaluator, pending: &mut String, line: String) -> Option<String> {
+  let line = if pending.is_empty() {
+    line
+  } else {
+    format!("{}\n{}", std::mem::take(pending), line)
+  };
+  let open_comment = matches!(
+    strip_comments(&line),
+    Err(CalcError::UnterminatedComment { .. })
+  );
+  if open_comment || evaluator.is_unfinished(&line) {
+    *pending = line;
+    return None;
   }
+  Some(line)
 }
 
 fn main() {
-  let expression = args().nth(1).expect("Error: No expression provided");
-  let result = evaluate_rpn(shunting_yard(tokenise(&mut expression.clone())));
-  println!("{:?}", result);
+  let mut options = parse_args().unwrap_or_else(|error| {
+    eprintln!("Error: {}", error);
+    exit(1);
+  });
+
+  let expressions = select_expressions(
+    std::mem::take(&mut options.expressions),
+    env::var(EXPRESSION_VARIABLE).ok(),
+  );
+  if let Some(path) = &options.watch {
+    watch(
+      path,
+      WATCH_INTERVAL,
+      || true,
+      |contents| {
+        evaluate_contents(contents, &options, str::to_string, |output| {
+          emit(output, &options)
+        });
+      },
+    );
+  }
+  let succeeded = if let Some(template) = &options.map {
+    map_lines(template, stdin().lock(), &options, |output| {
+      emit(output, &options)
+    })
+  } else if !options.files.is_empty() {
+    evaluate_files(&options.files, &options, |output| emit(output, &options))
+  } else if expressions.is_empty() {
+    repl(&options)
+  } else {
+    evaluate_arguments(&expressions, &options, |output| emit(output, &options))
+  };
+  if !succeeded {
+    exit(1);
+  }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
 
-  fn evaluate_expression(expression: &str) -> f32 {
-    evaluate_rpn(shunting_yard(tokenise(&mut expression.to_string())))
+  fn run(expression: &str) -> String {
+    evaluate_line(&mut Evaluator::new(), expression, &Options::default()).unwrap()
   }
 
   #[test]
-  fn test_basic_operations() {
-    assert_eq!(evaluate_expression("2+2"), 4.0);
-    assert_eq!(evaluate_expression("5-3"), 2.0);
-    assert_eq!(evaluate_expression("4*2"), 8.0);
-    assert_eq!(evaluate_expression("8/2"), 4.0);
+  fn test_integral_results() {
+    assert_eq!(run("2+2"), "4");
+    assert_eq!(run("1/2"), "0.5");
+    assert_eq!(run("-3*2"), "-6");
+    assert_eq!(run("2^53"), "9007199254740992");
+    assert_eq!(run("2^60"), "1.152921504606847e18");
+  }
+
+  #[test]
+  fn test_plain() {
+    let plain = Options {
+      plain: true,
+      ..Options::default()
+    };
+    assert_eq!(format_result(0.0000001, &plain), "0.0000001");
+    assert_eq!(format_result(2f64.powi(60), &plain), "1152921504606847000");
+    assert_eq!(format_result(0.0000001, &Options::default()), "1e-7");
+  }
+
+  #[test]
+  fn test_base() {
+    let base = |base, prefix| Options {
+      base: Some(base),
+      prefix,
+      ..Options::default()
+    };
+    assert_eq!(format_result(255.0, &base(16, true)), "0xff");
+    assert_eq!(format_result(255.0, &base(16, false)), "ff");
+    assert_eq!(format_result(64.0, &base(8, true)), "0o100");
+    assert_eq!(format_result(64.0, &base(8, false)), "100");
+    assert_eq!(format_result(10.0, &base(2, true)), "0b1010");
+    assert_eq!(format_result(10.0, &base(2, false)), "1010");
+    assert_eq!(format_result(0.5, &base(2, true)), "0.5");
+  }
+
+  #[test]
+  fn test_decimal_point() {
+    let comma = Options {
+      decimal_point: Some(','),
+      ..Options::default()
+    };
+    assert_eq!(format_result(1.5, &Options::default()), "1.5");
+    assert_eq!(format_result(1.5, &comma), "1,5");
+    assert_eq!(format_result(-1234.25, &comma), "-1234,25");
+    assert_eq!(format_result(3.0, &comma), "3");
+    assert_eq!(format_result(2f64.powi(60), &comma), "1,152921504606847e18");
+    let fixed = Options {
+      precision: Some(2),
+      approx_fraction: true,
+      ..comma
+    };
+    assert_eq!(format_result(0.5, &fixed), "0,50 ≈ 1/2");
+  }
+
+  #[test]
+  fn test_grouped_output() {
+    let english = Options {
+      thousands_separators: true,
+      ..Options::default()
+    };
+    assert_eq!(format_result(1234567.0, &english), "1,234,567");
+    assert_eq!(format_result(-1234567.25, &english), "-1,234,567.25");
+    assert_eq!(format_result(123456.0, &english), "123,456");
+    assert_eq!(format_result(999.5, &english), "999.5");
+    assert_eq!(format_result(0.123456, &english), "0.123456");
+    assert_eq!(
+      format_result(2f64.powi(60), &english),
+      "1.152921504606847e18"
+    );
+    assert_eq!(format_result(f64::INFINITY, &english), "inf");
+
+    let german = Options {
+      decimal_point: Some(','),
+      ..english
+    };
+    assert_eq!(format_result(1234567.0, &german), "1.234.567");
+    assert_eq!(format_result(-1234567.25, &german), "-1.234.567,25");
+    assert_eq!(format_result(1000.0, &german), "1.000");
+    let fixed = Options {
+      precision: Some(3),
+      ..german
+    };
+    assert_eq!(format_result(9876.54321, &fixed), "9.876,543");
   }
 
   #[test]
-  fn test_operator_precedence() {
-    assert_eq!(evaluate_expression("2+3*4"), 14.0);
-    assert_eq!(evaluate_expression("2*3+4"), 10.0);
-    assert_eq!(evaluate_expression("2+3*4-5"), 9.0);
+  fn test_quiet() {
+    let quiet = Options {
+      quiet: true,
+      approx_fraction: true,
+      warn_overflow: true,
+      ..Options::default()
+    };
+    let line = |line| evaluate_line(&mut Evaluator::new(), line, &quiet).unwrap();
+    assert_eq!(line("2+2").as_bytes(), b"4");
+    assert_eq!(line("1/3").as_bytes(), b"0.3333333333333333");
+    assert_eq!(line("10^400").as_bytes(), b"inf");
+  }
+
+  #[test]
+  fn test_statements() {
+    let mut evaluator = Evaluator::new();
+    let options = Options::default();
+    let mut line = |line| evaluate_line(&mut evaluator, line, &options).unwrap();
+    assert_eq!(line("x = 5;  x*2"), "10");
+    assert_eq!(line("x; x/2"), "2.5");
+    assert_eq!(line("1; 2; 3"), "3");
+    assert_eq!(line("y = 3;"), "");
+  }
+
+  #[test]
+  fn test_boolean_results() {
+    assert_eq!(run("3>2"), "true");
+    assert_eq!(run("1+1 != 2"), "false");
+    assert_eq!(run("(3>2) * 5"), "5");
+    assert_eq!(run("[1 < 2, 3]"), "[true, 3]");
+  }
+
+  #[test]
+  fn test_quantity_results() {
+    let units = Options {
+      units: true,
+      precision: Some(2),
+      ..Options::default()
+    };
+    let mut evaluator = new_evaluator(&units);
+    let mut line = |line| evaluate_line(&mut evaluator, line, &units).unwrap();
+    assert_eq!(line("3 m / 2 s"), "1.50 m/s");
+    assert_eq!(line("2 kg * 9.81 m/s^2"), "19.62 kg m/s^2");
+  }
+
+  #[test]
+  fn test_text_results() {
+    assert_eq!(run("roman(2024)"), "MMXXIV");
+    assert_eq!(run("[roman(4), 4]"), "[IV, 4]");
+  }
+
+  #[test]
+  fn test_periodic_results() {
+    let options = Options {
+      periodic: true,
+      ..Options::default()
+    };
+    let run = |expression| {
+      let mut evaluator = new_evaluator(&options);
+      evaluate_line(&mut evaluator, expression, &options).unwrap()
+    };
+    assert_eq!(run("1/7"), "0.(142857)");
+    assert_eq!(run("1/3 + 1/4"), "0.58(3)");
+    assert_eq!(run("[1/4, 2]"), "[0.25, 2]");
+    assert_eq!(run("sqrt(2)"), "1.4142135623730951");
+  }
+
+  #[test]
+  fn test_list_results() {
+    assert_eq!(run("[1, 2/4, 3]"), "[1, 0.5, 3]");
+    assert_eq!(run("[[1], []]"), "[[1], []]");
+  }
+
+  #[test]
+  fn test_csv_table() {
+    let path = env::temp_dir().join(format!("calculator-test-{}.csv", std::process::id()));
+    let options = Options {
+      table: Some(TableRange {
+        variable: "x".to_string(),
+        start: 0.0,
+        end: 1.0,
+        step: 0.5,
+      }),
+      csv: Some(path.to_string_lossy().into_owned()),
+      ..Options::default()
+    };
+    let output = evaluate_line(&mut Evaluator::new(), "x^2", &options).unwrap();
+    emit(&output, &options).unwrap();
+    let written = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(written, "x,value\n0,0\n0.5,0.25\n1,1\n");
+
+    let unwritable = Options {
+      csv: Some(
+        env::temp_dir()
+          .join("missing/table.csv")
+          .to_string_lossy()
+          .into_owned(),
+      ),
+      ..options
+    };
+    assert!(emit(&output, &unwritable).is_err());
+  }
+
+  #[test]
+  fn test_aligned_table() {
+    let mut options = Options {
+      table: Some(TableRange {
+        variable: "x".to_string(),
+        start: 0.0,
+        end: 10.0,
+        step: 2.5,
+      }),
+      ..Options::default()
+    };
+    let table = evaluate_line(&mut Evaluator::new(), "x^3 - 1", &options).unwrap();
+    assert_eq!(
+      table,
+      "   x    value\n 0.0   -1.000\n 2.5   14.625\n 5.0  124.000\n 7.5  420.875\n10.0  999.000"
+    );
+    let widths: Vec<usize> = table.lines().map(str::len).collect();
+    assert!(widths.iter().all(|&width| width == widths[0]));
+
+    options.column_width = Some(6);
+    options.decimal_point = Some(',');
+    assert_eq!(
+      evaluate_line(&mut Evaluator::new(), "1 / (x - 5)", &options).unwrap(),
+      "     x   value\n   0,0    -0,2\n   2,5    -0,4\n   5,0     inf\n   7,5     0,4\n  10,0     0,2"
+    );
+  }
+
+  #[test]
+  fn test_histogram() {
+    let options = Options {
+      table: Some(TableRange {
+        variable: "x".to_string(),
+        start: 0.0,
+        end: 5.0,
+        step: 1.0,
+      }),
+      histogram: Some(2),
+      ..Options::default()
+    };
+    assert_eq!(
+      evaluate_line(&mut Evaluator::new(), "x^2", &options).unwrap(),
+      format!(
+        "0 to 12.5   4  {}\n12.5 to 25  2  {}",
+        "#".repeat(40),
+        "#".repeat(20)
+      )
+    );
+  }
+
+  #[test]
+  fn test_csv_escaping() {
+    assert_eq!(csv_field("0.5"), "0.5");
+    assert_eq!(csv_field("1,5"), "\"1,5\"");
+    assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+  }
+
+  #[test]
+  fn test_edit_previous() {
+    let previous = Some("2 * sin(30) + 1");
+    assert_eq!(
+      edit_previous(previous, ""),
+      Ok("2 * sin(30) + 1".to_string())
+    );
+    assert_eq!(
+      edit_previous(previous, "30 -> 45"),
+      Ok("2 * sin(45) + 1".to_string())
+    );
+    assert_eq!(
+      edit_previous(previous, "sin->cos"),
+      Ok("2 * cos(30) + 1".to_string())
+    );
+    assert_eq!(
+      edit_previous(previous, "+ 1 ->"),
+      Ok("2 * sin(30) ".to_string())
+    );
+    assert!(edit_previous(previous, "tan -> cos").is_err());
+    assert!(edit_previous(previous, "30 45").is_err());
+    assert!(edit_previous(None, "").is_err());
+  }
+
+  #[test]
+  fn test_remember_expression() {
+    let mut previous = None;
+    let mut remember = |line| remember_expression(&mut previous, line).map(|_| previous.clone());
+    assert!(remember("edit").is_err());
+    assert_eq!(remember("2 * 3"), Ok(Some("2 * 3".to_string())));
+    assert_eq!(remember("4 + 1"), Ok(Some("4 + 1".to_string())));
+    assert_eq!(remember("edit 4 -> 5"), Ok(Some("5 + 1".to_string())));
+    assert_eq!(remember("edit 1 -> 2"), Ok(Some("5 + 2".to_string())));
+    assert!(remember("edit 9 -> 8").is_err());
+    assert_eq!(remember("edit"), Ok(Some("5 + 2".to_string())));
+    assert_eq!(remember("editor"), Ok(Some("editor".to_string())));
+  }
+
+  #[test]
+  fn test_complete_line() {
+    let evaluator = Evaluator::new();
+    let mut pending = String::new();
+    let mut complete = |line: &str| complete_line(&evaluator, &mut pending, line.to_string());
+    assert_eq!(complete("5 +"), None);
+    assert_eq!(complete("3"), Some("5 +\n3".to_string()));
+    assert_eq!(complete("2 * (1 -"), None);
+    assert_eq!(complete("/* note */"), None);
+    assert_eq!(complete("4)"), Some("2 * (1 -\n/* note */\n4)".to_string()));
+    assert_eq!(complete("1 /* open"), None);
+    assert_eq!(
+      complete("closed */ + 1"),
+      Some("1 /* open\nclosed */ + 1".to_string())
+    );
+    assert_eq!(complete("+1"), Some("+1".to_string()));
+
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.evaluate("2 * (1 -\n/* note */\n4)"), Ok(-6.0));
+  }
+
+  #[test]
+  fn test_config() {
+    let config = new_evaluator(&Options::default()).config.to_string();
+    assert!(config.starts_with("mode = float\n"));
+    assert!(config.contains("\nangle_input_unit = rad\n"));
+    assert!(config.contains("\nwrap = none\n"));
+
+    let options = Options {
+      angle_input_unit: AngleUnit::Degrees,
+      wrap_bits: Some(8),
+      timeout_ms: Some(250),
+      max_operations: Some(1000),
+      max_magnitude: Some(1e6),
+      ..Options::default()
+    };
+    let config = new_evaluator(&options).config.to_string();
+    assert!(config.contains("\nangle_input_unit = deg\n"));
+    assert!(config.contains("\nangle_output_unit = rad\n"));
+    assert!(config.contains("\nwrap = u8\n"));
+    assert!(config.contains("\ntimeout = 250ms\n"));
+    assert!(config.contains("\nmax_operations = 1000\n"));
+    assert!(config.contains("\nmax_magnitude = 1000000\n"));
+    assert!(config.ends_with("\nsandbox = false"));
+  }
+
+  #[test]
+  fn test_point_out() {
+    let error = CalcError::InvalidNumber {
+      number: "1.2.3".to_string(),
+      column: 4,
+      span: 0..5,
+    };
+    assert_eq!(point_out("1.2.3", &error), "  1.2.3\n     ^");
+    assert_eq!(point_out("(1", &CalcError::MismatchedParentheses), "  (1");
+  }
+
+  #[test]
+  fn test_argument_wins_over_environment() {
+    assert_eq!(
+      select_expressions(vec!["1+1".to_string()], Some("2*3".to_string())),
+      vec!["1+1".to_string()]
+    );
+  }
+
+  #[test]
+  fn test_environment_fallback() {
+    assert_eq!(
+      select_expressions(vec![], Some("2*3".to_string())),
+      vec!["2*3".to_string()]
+    );
+  }
+
+  #[test]
+  fn test_stdin_fallback() {
+    assert!(select_expressions(vec![], None).is_empty());
+    assert!(select_expressions(vec![], Some(" ".to_string())).is_empty());
+  }
+
+  #[test]
+  fn test_multiple_arguments() {
+    let run_all = |expressions: &[&str]| {
+      let expressions: Vec<String> = expressions.iter().map(|e| e.to_string()).collect();
+      let mut outputs = Vec::new();
+      let succeeded = evaluate_arguments(&expressions, &Options::default(), |output| {
+        outputs.push(output.to_string());
+        Ok(())
+      });
+      (succeeded, outputs)
+    };
+    assert_eq!(
+      run_all(&["1+1", "2*3"]),
+      (true, vec!["2".to_string(), "6".to_string()])
+    );
+    assert_eq!(
+      run_all(&["x = 4", "x^2"]),
+      (true, vec!["4".to_string(), "16".to_string()])
+    );
+    assert_eq!(run_all(&["1+", "3"]), (false, vec!["3".to_string()]));
+  }
+
+  #[test]
+  fn test_files() {
+    let directory = env::temp_dir();
+    let path = |name: &str| {
+      directory
+        .join(format!("calculator-{}-{}.txt", std::process::id(), name))
+        .to_string_lossy()
+        .into_owned()
+    };
+    let (first, second) = (path("first"), path("second"));
+    fs::write(&first, "x = 2\n\n/* squared */ x^2\n1 +\n2\n").unwrap();
+    fs::write(&second, "x\n3 * 4\n").unwrap();
+    let run_all = |paths: &[String]| {
+      let mut outputs = Vec::new();
+      let succeeded = evaluate_files(paths, &Options::default(), |output| {
+        outputs.push(output.to_string());
+        Ok(())
+      });
+      (succeeded, outputs)
+    };
+    let (succeeded, outputs) = run_all(&[first.clone(), second.clone()]);
+    let alone = run_all(std::slice::from_ref(&first));
+    fs::remove_file(&first).unwrap();
+    fs::remove_file(&second).unwrap();
+
+    // `x` isn't set in the second file, but the line after it is still evaluated.
+    assert!(!succeeded);
+    assert_eq!(
+      outputs,
+      [
+        format!("{}: 2", first),
+        format!("{}: 4", first),
+        format!("{}: 3", first),
+        format!("{}: 12", second)
+      ]
+    );
+    assert_eq!(
+      alone,
+      (
+        true,
+        vec!["2".to_string(), "4".to_string(), "3".to_string()]
+      )
+    );
+    assert!(!run_all(&[first]).0);
+  }
+
+  #[test]
+  fn test_watch() {
+    let path = env::temp_dir()
+      .join(format!("calculator-{}-watched.txt", std::process::id()))
+      .to_string_lossy()
+      .into_owned();
+    fs::write(&path, "1 + 1\n").unwrap();
+    // Each round changes the file before it is checked again: the same contents, new ones,
+    // none at all while it is deleted, and new ones again.
+    let mut round = 0;
+    let mut results = Vec::new();
+    watch(
+      &path,
+      Duration::ZERO,
+      || {
+        round += 1;
+        match round {
+          2 => fs::write(&path, "1 + 1\n").unwrap(),
+          3 => fs::write(&path, "2 * 3\n").unwrap(),
+          4 => fs::remove_file(&path).unwrap(),
+          5 => fs::write(&path, "x = 4\nx^2\n").unwrap(),
+          _ => {}
+        }
+        round <= 6
+      },
+      |contents| {
+        evaluate_contents(contents, &Options::default(), str::to_string, |output| {
+          results.push(output.to_string());
+          Ok(())
+        });
+      },
+    );
+    fs::remove_file(&path).unwrap();
+    assert_eq!(results, ["2", "6", "4", "16"]);
+  }
+
+  #[test]
+  fn test_map_lines() {
+    let map = |template, input: &str| {
+      let mut results = Vec::new();
+      let succeeded = map_lines(template, input.as_bytes(), &Options::default(), |output| {
+        results.push(output.to_string());
+        Ok(())
+      });
+      (succeeded, results)
+    };
+    assert_eq!(
+      map("_ * 2", "1\n2.5\n\n  -3  \n"),
+      (
+        true,
+        vec!["2".to_string(), "5".to_string(), "-6".to_string()]
+      )
+    );
+    assert_eq!(
+      map("sqrt(_)", "16\nfour\n-1\n9\n"),
+      (
+        false,
+        vec!["4".to_string(), "NaN".to_string(), "3".to_string()]
+      )
+    );
+    assert_eq!(map("_ +", "1\n"), (false, vec![]));
+    assert_eq!(map("_ * y", "1\n"), (false, vec![]));
+  }
+
+  #[test]
+  fn test_json() {
+    let json = Options {
+      json: true,
+      ..Options::default()
+    };
+    let mut evaluator = Evaluator::new();
+    let mut line = |expression| evaluate_line(&mut evaluator, expression, &json).unwrap();
+    assert_eq!(line("2 + 2"), "{\"result\":\"4\"}");
+    assert_eq!(line("3 > 2"), "{\"result\":\"true\"}");
+    let error = line("7 // 0");
+    assert_eq!(
+      error,
+      "{\"error\":\"Division by zero\",\"error_code\":\"E_DIV_ZERO\"}"
+    );
+    assert_eq!(
+      line("(1"),
+      "{\"error\":\"Mismatched parentheses\",\"error_code\":\"E_PARSE\"}"
+    );
+    assert!(passed("{\"result\":\"4\"}", &json));
+    assert!(!passed(&error, &json));
+    let check = Options {
+      check: true,
+      ..json
+    };
+    assert!(!passed("{\"result\":\"false\"}", &check));
+    assert!(passed("{\"result\":\"true\"}", &check));
+  }
+
+  #[test]
+  fn test_journal() {
+    let path = env::temp_dir().join(format!("calculator-{}-journal.txt", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let options = Options {
+      journal: Some(open_journal(path.to_str().unwrap()).unwrap()),
+      ..Options::default()
+    };
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+      evaluate_line(&mut evaluator, "2 + 2", &options),
+      Ok("4".to_string())
+    );
+    assert!(evaluate_line(&mut evaluator, "y * 2", &options).is_err());
+    // Opening the journal again appends to it.
+    let reopened = Options {
+      journal: Some(open_journal(path.to_str().unwrap()).unwrap()),
+      ..Options::default()
+    };
+    evaluate_line(&mut evaluator, "x = 3", &reopened).unwrap();
+    let contents = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let entries: Vec<Vec<&str>> = contents
+      .lines()
+      .map(|line| line.splitn(3, '\t').collect())
+      .collect();
+    assert_eq!(entries.len(), 3);
+    assert!(entries.iter().all(|entry| entry[0].ends_with('Z')));
+    assert_eq!(entries[0][1..], ["2 + 2", "4"]);
+    assert_eq!(entries[1][1..], ["y * 2", "Error: Unknown variable 'y'"]);
+    assert_eq!(entries[2][1..], ["x = 3", "3"]);
+
+    assert!(open_journal("/nonexistent/journal.txt").is_err());
+    let time = |seconds| UNIX_EPOCH + Duration::from_secs(seconds);
+    assert_eq!(utc_timestamp(time(0)), "1970-01-01T00:00:00Z");
+    assert_eq!(utc_timestamp(time(1_000_000_000)), "2001-09-09T01:46:40Z");
+    assert_eq!(utc_timestamp(time(1_709_296_205)), "2024-03-01T12:30:05Z");
+    assert_eq!(utc_timestamp(time(951_782_400)), "2000-02-29T00:00:00Z");
   }
 
   #[test]
-  fn test_parentheses_handling() {
-    assert_eq!(evaluate_expression("(2+3)*4"), 20.0);
-    assert_eq!(evaluate_expression("2*(3+4)"), 14.0);
-    assert_eq!(evaluate_expression("2*(3+4)-5"), 9.0);
+  fn test_polish() {
+    let polish = Options {
+      polish: true,
+      ..Options::default()
+    };
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+      evaluate_line(&mut evaluator, "+ 2 * 3 4", &polish),
+      Ok("14".to_string())
+    );
+    assert_eq!(
+      evaluate_line(&mut evaluator, "/ 1 4", &polish),
+      Ok("0.25".to_string())
+    );
+    assert!(evaluate_line(&mut evaluator, "2 + 3", &polish).is_err());
   }
 
   #[test]
-  fn test_edge_cases() {
-    assert_eq!(evaluate_expression("0+0"), 0.0);
-    assert_eq!(evaluate_expression("0*1000"), 0.0);
-    assert_eq!(evaluate_expression("1000000/1"), 1000000.0);
-    assert_eq!(evaluate_expression("1/1000000"), 0.000001);
+  fn test_check() {
+    let check = Options {
+      check: true,
+      ..Options::default()
+    };
+    let run_all = |expressions: &[&str]| {
+      let expressions: Vec<String> = expressions.iter().map(|e| e.to_string()).collect();
+      let mut outputs = Vec::new();
+      let succeeded = evaluate_arguments(&expressions, &check, |output| {
+        outputs.push(output.to_string());
+        Ok(())
+      });
+      (succeeded, outputs)
+    };
+    assert_eq!(run_all(&["2+2 = 4"]), (true, vec!["true".to_string()]));
+    assert_eq!(run_all(&["2+2 == 5"]), (false, vec!["false".to_string()]));
+    assert_eq!(
+      run_all(&["1 = 1", "1 = 2"]),
+      (false, vec!["true".to_string(), "false".to_string()])
+    );
   }
 }
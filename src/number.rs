@@ -0,0 +1,185 @@
+use crate::error::CalcError;
+use crate::rational::Rational;
+use crate::token::{Operator, Token};
+
+/// A kind of number [`eval_rpn_with`] can do arithmetic in, so the same tokens can be evaluated
+/// with floats, exact fractions, or a type of the caller's own.
+pub trait Number: Sized {
+  /// The number a literal in the expression stands for.
+  fn from_literal(literal: f64) -> Result<Self, CalcError>;
+  fn add(self, other: Self) -> Result<Self, CalcError>;
+  fn sub(self, other: Self) -> Result<Self, CalcError>;
+  fn mul(self, other: Self) -> Result<Self, CalcError>;
+  fn div(self, other: Self) -> Result<Self, CalcError>;
+  fn pow(self, exponent: Self) -> Result<Self, CalcError>;
+  fn neg(self) -> Result<Self, CalcError>;
+}
+
+/// Plain IEEE arithmetic, where dividing by zero gives an infinity rather than an error.
+impl Number for f64 {
+  fn from_literal(literal: f64) -> Result<f64, CalcError> {
+    Ok(literal)
+  }
+
+  fn add(self, other: f64) -> Result<f64, CalcError> {
+    Ok(self + other)
+  }
+
+  fn sub(self, other: f64) -> Result<f64, CalcError> {
+    Ok(self - other)
+  }
+
+  fn mul(self, other: f64) -> Result<f64, CalcError> {
+    Ok(self * other)
+  }
+
+  fn div(self, other: f64) -> Result<f64, CalcError> {
+    Ok(self / other)
+  }
+
+  fn pow(self, exponent: f64) -> Result<f64, CalcError> {
+    Ok(self.powf(exponent))
+  }
+
+  fn neg(self) -> Result<f64, CalcError> {
+    Ok(-self)
+  }
+}
+
+/// Exact arithmetic, failing where the result isn't a fraction of 64-bit integers.
+impl Number for Rational {
+  fn from_literal(literal: f64) -> Result<Rational, CalcError> {
+    Rational::from_decimal(literal)
+      .ok_or_else(|| CalcError::Unsupported(format!("{} isn't an exact fraction", literal)))
+  }
+
+  fn add(self, other: Rational) -> Result<Rational, CalcError> {
+    exact(self.checked_add(other))
+  }
+
+  fn sub(self, other: Rational) -> Result<Rational, CalcError> {
+    exact(self.checked_sub(other))
+  }
+
+  fn mul(self, other: Rational) -> Result<Rational, CalcError> {
+    exact(self.checked_mul(other))
+  }
+
+  fn div(self, other: Rational) -> Result<Rational, CalcError> {
+    if other.numerator() == 0 {
+      return Err(CalcError::DivisionByZero);
+    }
+    exact(self.checked_div(other))
+  }
+
+  fn pow(self, exponent: Rational) -> Result<Rational, CalcError> {
+    if !exponent.is_integer() {
+      return Err(CalcError::Unsupported(format!(
+        "an exact power needs an integer exponent, not {}",
+        exponent
+      )));
+    }
+    if self.numerator() == 0 && exponent.numerator() < 0 {
+      return Err(CalcError::DivisionByZero);
+    }
+    exact(self.checked_pow(exponent.numerator()))
+  }
+
+  fn neg(self) -> Result<Rational, CalcError> {
+    exact(self.checked_neg())
+  }
+}
+
+fn exact(result: Option<Rational>) -> Result<Rational, CalcError> {
+  result.ok_or_else(|| CalcError::Unsupported("the exact result is too large".to_string()))
+}
+
+/// Evaluate tokens in reverse polish notation, as returned by [`to_rpn`](crate::to_rpn), in any
+/// [`Number`]. Only numbers and the arithmetic operators `+`, `-`, `*`, `/`, `^` and unary minus
+/// are supported, since they are all a backend has to provide:
+///
+/// ```
+/// use calculator::{eval_rpn_with, to_rpn, tokenize, Rational};
+///
+/// let rpn = to_rpn(tokenize("1/3 + 1/6").unwrap()).unwrap();
+/// assert_eq!(eval_rpn_with::<Rational>(rpn.clone()), Ok(Rational::new(1, 2).unwrap()));
+/// assert_eq!(eval_rpn_with::<f64>(rpn), Ok(1.0 / 3.0 + 1.0 / 6.0));
+/// ```
+pub fn eval_rpn_with<T: Number>(rpn: Vec<Token>) -> Result<T, CalcError> {
+  let mut stack: Vec<T> = Vec::new();
+  for token in rpn {
+    let number = match token {
+      Token::Number(literal) => T::from_literal(literal)?,
+      Token::Operator(Operator::Negate) => {
+        stack.pop().ok_or(CalcError::InvalidExpression)?.neg()?
+      }
+      Token::Operator(operator) => {
+        let right = stack.pop().ok_or(CalcError::InvalidExpression)?;
+        let left = stack.pop().ok_or(CalcError::InvalidExpression)?;
+        match operator {
+          Operator::Add => left.add(right)?,
+          Operator::Subtract => left.sub(right)?,
+          Operator::Multiply => left.mul(right)?,
+          Operator::Divide => left.div(right)?,
+          Operator::Power => left.pow(right)?,
+          _ => return Err(unsupported(operator.symbol())),
+        }
+      }
+      Token::Constant(name) | Token::Variable(name) | Token::Call(name, _) => {
+        return Err(unsupported(&name))
+      }
+      _ => return Err(unsupported("anything but numbers and arithmetic")),
+    };
+    stack.push(number);
+  }
+  let result = stack.pop().ok_or(CalcError::NoExpression)?;
+  if !stack.is_empty() {
+    return Err(CalcError::InvalidExpression);
+  }
+  Ok(result)
+}
+
+fn unsupported(what: &str) -> CalcError {
+  CalcError::Unsupported(format!(
+    "'{}' can't be evaluated with a number backend",
+    what
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{eval_rpn_with, to_rpn, tokenize, CalcError, Number, Rational};
+
+  fn evaluate<T: Number>(expression: &str) -> Result<T, CalcError> {
+    eval_rpn_with(to_rpn(tokenize(expression)?)?)
+  }
+
+  #[test]
+  fn test_backends() {
+    let expression = "(1/3 + 1/6) * 2^-1 - -0.25";
+    assert_eq!(evaluate::<f64>(expression), Ok(0.5));
+    assert_eq!(evaluate(expression), Ok(Rational::new(1, 2).unwrap()));
+    assert_eq!(evaluate::<f64>("1/3"), Ok(1.0 / 3.0));
+    assert_eq!(evaluate("1/3"), Ok(Rational::new(1, 3).unwrap()));
+
+    assert_eq!(evaluate::<f64>("1/0"), Ok(f64::INFINITY));
+    assert_eq!(evaluate::<Rational>("1/0"), Err(CalcError::DivisionByZero));
+    assert_eq!(evaluate::<f64>("4^0.5"), Ok(2.0));
+    assert!(matches!(
+      evaluate::<Rational>("4^0.5"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert!(matches!(
+      evaluate::<Rational>("2^100"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert!(matches!(
+      evaluate::<f64>("sqrt(4)"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert!(matches!(
+      evaluate::<f64>("1 < 2"),
+      Err(CalcError::Unsupported(_))
+    ));
+  }
+}
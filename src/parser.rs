@@ -0,0 +1,177 @@
+use crate::error::{CalcError, OperandSide};
+use crate::token::{Bracket, Parenthesis, Token};
+
+/// Convert a vector of tokens into reverse polish notation.
+/// ([Shunting Yard](https://aquarchitect.github.io/swift-algorithm-club/Shunting%20Yard/))
+pub(crate) fn shunting_yard(tokens: Vec<Token>) -> Result<Vec<Token>, CalcError> {
+  check_operands(&tokens)?;
+  let mut output: Vec<Token> = Vec::new();
+  let mut stack: Vec<Token> = Vec::new();
+  // One entry per open parenthesis or bracket: the number of arguments or items so far if it
+  // belongs to a call or list.
+  let mut arguments: Vec<Option<usize>> = Vec::new();
+  let mut previous: Option<Token> = None;
+
+  for token in tokens {
+    let preceding = previous.replace(token.clone());
+    match token.clone() {
+      Token::Operator(operator) if operator.arity() == 1 => stack.push(token),
+      Token::Operator(operator) => {
+        while let Some(Token::Operator(top)) = stack.last() {
+          if (operator.associativity().is_left() && operator.precedence() <= top.precedence())
+            || (operator.associativity().is_right() && operator.precedence() < top.precedence())
+          {
+            output.push(stack.pop().unwrap());
+          } else {
+            break;
+          }
+        }
+        stack.push(token);
+      }
+      Token::Parenthesis(paren) => match paren {
+        Parenthesis::Left => {
+          let call = matches!(preceding, Some(Token::Function(_)));
+          arguments.push(call.then_some(1));
+          stack.push(token);
+        }
+        Parenthesis::Right => {
+          if close(&mut stack, &mut output)? != Token::Parenthesis(Parenthesis::Left) {
+            return Err(CalcError::MismatchedParentheses);
+          }
+          if let Some(count) = arguments.pop().flatten() {
+            let Some(Token::Function(name)) = stack.pop() else {
+              unreachable!("a call parenthesis always follows its function");
+            };
+            let empty = preceding == Some(Token::Parenthesis(Parenthesis::Left));
+            output.push(Token::Call(name, if empty { 0 } else { count }));
+          }
+        }
+      },
+      Token::Bracket(bracket, Parenthesis::Left) => {
+        arguments.push((bracket == Bracket::List).then_some(1));
+        stack.push(token);
+      }
+      Token::Bracket(bracket, Parenthesis::Right) => {
+        if close(&mut stack, &mut output)? != Token::Bracket(bracket, Parenthesis::Left) {
+          return Err(CalcError::MismatchedParentheses);
+        }
+        let count = arguments.pop().flatten();
+        match bracket.function() {
+          Some(function) => output.push(Token::Call(function.to_string(), 1)),
+          None if bracket == Bracket::Index => output.push(Token::Index),
+          None if preceding == Some(Token::Bracket(bracket, Parenthesis::Left)) => {
+            output.push(Token::List(0))
+          }
+          None => output.push(Token::List(count.unwrap_or(1))),
+        }
+      }
+      Token::Separator => match arguments.last_mut() {
+        Some(Some(count)) => {
+          *count += 1;
+          pop_until_left_parenthesis(&mut stack, &mut output)?;
+        }
+        _ => return Err(CalcError::MisplacedSeparator),
+      },
+      // Assignment binds loosest and groups to the right, so `a = b = 5` assigns `b` first.
+      Token::Assign(_) => {
+        while let Some(Token::Operator(_)) = stack.last() {
+          output.push(stack.pop().unwrap());
+        }
+        stack.push(token);
+      }
+      Token::Function(_) => stack.push(token),
+      Token::Number(_)
+      | Token::Constant(_)
+      | Token::Variable(_)
+      | Token::Text(_)
+      | Token::Unit(_)
+      | Token::Call(..)
+      | Token::List(_)
+      | Token::Index => output.push(token),
+    }
+  }
+
+  while let Some(token) = stack.pop() {
+    if matches!(token, Token::Parenthesis(_) | Token::Bracket(..)) {
+      return Err(CalcError::MismatchedParentheses);
+    }
+    output.push(token);
+  }
+
+  Ok(output)
+}
+
+/// Move everything above the innermost open parenthesis or bracket to the output, leaving it.
+fn pop_until_left_parenthesis(
+  stack: &mut Vec<Token>,
+  output: &mut Vec<Token>,
+) -> Result<(), CalcError> {
+  loop {
+    match stack.last() {
+      None => return Err(CalcError::MismatchedParentheses),
+      Some(Token::Parenthesis(Parenthesis::Left)) | Some(Token::Bracket(_, Parenthesis::Left)) => {
+        return Ok(())
+      }
+      Some(_) => output.push(stack.pop().unwrap()),
+    }
+  }
+}
+
+/// Pop everything up to and including the innermost open parenthesis or bracket, returning it so
+/// the caller can check it matches what closes it.
+fn close(stack: &mut Vec<Token>, output: &mut Vec<Token>) -> Result<Token, CalcError> {
+  pop_until_left_parenthesis(stack, output)?;
+  Ok(stack.pop().unwrap())
+}
+
+/// Make sure every operator has its operands before converting, since reverse polish notation no
+/// longer shows which side of an operator is missing.
+fn check_operands(tokens: &[Token]) -> Result<(), CalcError> {
+  for (i, token) in tokens.iter().enumerate() {
+    let Token::Operator(operator) = token else {
+      continue;
+    };
+    let missing = |side| CalcError::MissingOperand {
+      operator: operator.symbol().to_string(),
+      side,
+    };
+    let previous = i.checked_sub(1).map(|previous| &tokens[previous]);
+    if operator.arity() == 2 && !ends_operand(previous) {
+      return Err(missing(OperandSide::Left));
+    }
+    if !starts_operand(tokens.get(i + 1)) {
+      return Err(missing(OperandSide::Right));
+    }
+  }
+  Ok(())
+}
+
+fn ends_operand(token: Option<&Token>) -> bool {
+  matches!(
+    token,
+    Some(Token::Number(_))
+      | Some(Token::Constant(_))
+      | Some(Token::Variable(_))
+      | Some(Token::Text(_))
+      | Some(Token::Unit(_))
+      | Some(Token::Parenthesis(Parenthesis::Right))
+      | Some(Token::Bracket(_, Parenthesis::Right))
+  )
+}
+
+fn starts_operand(token: Option<&Token>) -> bool {
+  match token {
+    Some(Token::Operator(operator)) => operator.arity() == 1,
+    _ => matches!(
+      token,
+      Some(Token::Number(_))
+        | Some(Token::Constant(_))
+        | Some(Token::Variable(_))
+        | Some(Token::Text(_))
+        | Some(Token::Unit(_))
+        | Some(Token::Function(_))
+        | Some(Token::Parenthesis(Parenthesis::Left))
+        | Some(Token::Bracket(_, Parenthesis::Left))
+    ),
+  }
+}
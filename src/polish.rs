@@ -0,0 +1,108 @@
+use crate::ast::Expr;
+use crate::error::{CalcError, OperandSide};
+use crate::evaluator::Evaluator;
+use crate::token::Operator;
+use crate::value::Value;
+
+impl Evaluator {
+  /// Evaluate an expression in Polish notation, with every operator before its operands and the
+  /// tokens separated by spaces, so `+ 2 * 3 4` is `2 + 3 * 4`. `-` always subtracts, and `~`
+  /// is the only unary operator; anything that isn't an operator, like `-3`, `pi` or `sqrt(2)`,
+  /// is read as an ordinary expression.
+  pub fn evaluate_prefix(&mut self, expression: &str) -> Result<Value, CalcError> {
+    let expr = self.parse_prefix(expression)?;
+    self.evaluate_expr(&expr)
+  }
+
+  /// Parse an expression in Polish notation into a tree without evaluating it.
+  pub(crate) fn parse_prefix(&self, expression: &str) -> Result<Expr, CalcError> {
+    self.check_length(expression)?;
+    let mut words = expression.split_whitespace();
+    let expr = match words.next() {
+      Some(word) => self.prefix_operand(word, &mut words)?,
+      None => return Err(CalcError::NoExpression),
+    };
+    // Operands left over once the first operator has all of its own.
+    if words.next().is_some() {
+      return Err(CalcError::InvalidExpression);
+    }
+    Ok(expr)
+  }
+
+  /// The expression starting at `word`: an operator followed by as many operands as it takes,
+  /// themselves read from `words`, or a single operand.
+  fn prefix_operand<'a>(
+    &self,
+    word: &str,
+    words: &mut impl Iterator<Item = &'a str>,
+  ) -> Result<Expr, CalcError> {
+    let Some(info) = Operator::all().iter().find(|info| info.symbol == word) else {
+      return self.parse(word);
+    };
+    let mut operand = |side| {
+      let word = words.next().ok_or_else(|| CalcError::MissingOperand {
+        operator: info.symbol.to_string(),
+        side,
+      })?;
+      self.prefix_operand(word, words).map(Box::new)
+    };
+    if info.arity == 1 {
+      return Ok(Expr::Unary(info.operator, operand(OperandSide::Right)?));
+    }
+    let left = operand(OperandSide::Left)?;
+    let right = operand(OperandSide::Right)?;
+    Ok(Expr::Binary(info.operator, left, right))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{CalcError, Evaluator, OperandSide, Value};
+
+  fn evaluate(expression: &str) -> Result<Value, CalcError> {
+    Evaluator::new().evaluate_prefix(expression)
+  }
+
+  #[test]
+  fn test_prefix_notation() {
+    assert_eq!(evaluate("+ 2 * 3 4"), Ok(Value::Integer(14)));
+    assert_eq!(evaluate("* + 2 3 4"), Ok(Value::Integer(20)));
+    assert_eq!(evaluate("- 10 / 9 3"), Ok(Value::Integer(7)));
+    assert_eq!(evaluate("^ 2 ^ 3 2"), Ok(Value::Integer(512)));
+    assert_eq!(evaluate("- * 2 + 1 4 // 7 2"), Ok(Value::Integer(7)));
+    assert_eq!(evaluate("  *  -3   2 "), Ok(Value::Integer(-6)));
+    assert_eq!(evaluate("~ 5"), Ok(Value::Integer(-6)));
+    assert_eq!(evaluate("< 1 2"), Ok(Value::Boolean(true)));
+    assert_eq!(evaluate("42"), Ok(Value::Integer(42)));
+    assert_eq!(
+      evaluate("* 2 pi"),
+      Ok(Value::Float(2.0 * std::f64::consts::PI))
+    );
+    assert_eq!(evaluate("+ sqrt(16) 1"), Ok(Value::Integer(5)));
+
+    let mut evaluator = Evaluator::new();
+    evaluator.evaluate("x = 3").unwrap();
+    assert_eq!(evaluator.evaluate_prefix("* x x"), Ok(Value::Integer(9)));
+  }
+
+  #[test]
+  fn test_malformed_prefix_notation() {
+    let missing = |operator: &str, side| {
+      Err(CalcError::MissingOperand {
+        operator: operator.to_string(),
+        side,
+      })
+    };
+    assert_eq!(evaluate("+ 2"), missing("+", OperandSide::Right));
+    assert_eq!(evaluate("+"), missing("+", OperandSide::Left));
+    assert_eq!(evaluate("+ 1 * 2"), missing("*", OperandSide::Right));
+    assert_eq!(evaluate("~"), missing("~", OperandSide::Right));
+    assert_eq!(evaluate("+ 1 2 3"), Err(CalcError::InvalidExpression));
+    assert_eq!(evaluate("1 2"), Err(CalcError::InvalidExpression));
+    assert_eq!(evaluate("   "), Err(CalcError::NoExpression));
+    assert_eq!(
+      evaluate("+ 1 y"),
+      Err(CalcError::UnknownVariable("y".to_string()))
+    );
+  }
+}
@@ -0,0 +1,112 @@
+use crate::ast::Expr;
+use crate::error::CalcError;
+use crate::evaluator::Evaluator;
+use crate::token::Operator;
+use crate::value::Value;
+
+impl Evaluator {
+  /// Store the rate `convert` multiplies by for `pair`, like `USD_EUR`, replacing any previous one.
+  pub fn set_rate(&mut self, pair: &str, rate: f64) {
+    self.rates.insert(pair.to_string(), rate);
+  }
+
+  /// Look up a rate stored with [`Evaluator::set_rate`].
+  pub fn rate(&self, pair: &str) -> Option<f64> {
+    self.rates.get(pair).copied()
+  }
+
+  /// Evaluate `convert(amount, "PAIR")`.
+  pub(crate) fn convert(&mut self, amount: &Expr, pair: &str) -> Result<Value, CalcError> {
+    let rate = self
+      .rate(pair)
+      .ok_or_else(|| CalcError::UnknownRate(pair.to_string()))?;
+    let amount = self.eval(amount)?;
+    let rate = self.number(rate);
+    self.apply(Operator::Multiply, amount, rate)
+  }
+}
+
+/// Read a rate table with one `PAIR,RATE` line per rate, like `USD_EUR,0.92`. Blank lines are
+/// skipped, and so is a first line whose rate isn't a number, taking it for a header.
+pub fn parse_rates(csv: &str) -> Result<Vec<(String, f64)>, CalcError> {
+  let mut rates = Vec::new();
+  for (i, line) in csv.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() {
+      continue;
+    }
+    let invalid = |reason: &str| CalcError::InvalidRates {
+      line: i + 1,
+      reason: reason.to_string(),
+    };
+    let (pair, rate) = line
+      .split_once(',')
+      .ok_or_else(|| invalid("expected PAIR,RATE"))?;
+    let pair = pair.trim();
+    if pair.is_empty() {
+      return Err(invalid("the pair is missing"));
+    }
+    match rate.trim().parse::<f64>() {
+      Ok(rate) if rate.is_finite() => rates.push((pair.to_string(), rate)),
+      Ok(_) => return Err(invalid("the rate must be finite")),
+      Err(_) if i == 0 => {}
+      Err(_) => return Err(invalid("the rate must be a number")),
+    }
+  }
+  Ok(rates)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{parse_rates, CalcError, Evaluator};
+
+  fn evaluator() -> Evaluator {
+    let mut evaluator = Evaluator::new();
+    for (pair, rate) in parse_rates("pair,rate\nUSD_EUR,0.5\n\nEUR_GBP, 0.8\n").unwrap() {
+      evaluator.set_rate(&pair, rate);
+    }
+    evaluator
+  }
+
+  #[test]
+  fn test_convert() {
+    let mut evaluator = evaluator();
+    assert_eq!(evaluator.evaluate("convert(10, \"USD_EUR\")"), Ok(5.0));
+    assert_eq!(
+      evaluator.evaluate("convert(convert(10, \"USD_EUR\"), \"EUR_GBP\") + 1"),
+      Ok(5.0)
+    );
+    assert_eq!(
+      evaluator.evaluate("convert(1, \"USD_JPY\")"),
+      Err(CalcError::UnknownRate("USD_JPY".to_string()))
+    );
+    assert!(matches!(
+      evaluator.evaluate("convert(1, USD_EUR)"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert!(matches!(
+      evaluator.evaluate("\"USD_EUR\" * 2"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert_eq!(
+      evaluator.evaluate("convert(1, \"USD_EUR"),
+      Err(CalcError::UnterminatedText {
+        column: 12,
+        span: 11..19
+      })
+    );
+    assert_eq!(evaluator.validate("convert(2, \"nope\")"), Ok(()));
+  }
+
+  #[test]
+  fn test_invalid_rates() {
+    let invalid = |csv| match parse_rates(csv) {
+      Err(CalcError::InvalidRates { line, .. }) => line,
+      other => panic!("expected an error, got {:?}", other),
+    };
+    assert_eq!(invalid("USD_EUR,0.5\nUSD_GBP"), 2);
+    assert_eq!(invalid("USD_EUR,0.5\nUSD_GBP,x"), 2);
+    assert_eq!(invalid(",0.5"), 1);
+    assert_eq!(invalid("USD_EUR,inf"), 1);
+  }
+}
@@ -0,0 +1,312 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+/// The longest repeating block [`Rational::periodic_decimal`] will write out.
+const MAX_PERIOD: usize = 1000;
+
+/// An exact fraction kept in lowest terms with a positive denominator.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Rational {
+  numerator: i64,
+  denominator: i64,
+}
+
+impl Rational {
+  /// Build a reduced fraction, or `None` when the denominator is zero or reducing overflows.
+  pub fn new(numerator: i64, denominator: i64) -> Option<Rational> {
+    if denominator == 0 {
+      return None;
+    }
+    let divisor = gcd(numerator, denominator);
+    let (mut numerator, mut denominator) = (numerator / divisor, denominator / divisor);
+    if denominator < 0 {
+      numerator = numerator.checked_neg()?;
+      denominator = denominator.checked_neg()?;
+    }
+    Some(Rational {
+      numerator,
+      denominator,
+    })
+  }
+
+  pub fn from_integer(integer: i64) -> Rational {
+    Rational {
+      numerator: integer,
+      denominator: 1,
+    }
+  }
+
+  /// Recover the exact fraction a decimal literal was written as, using the shortest
+  /// representation that round-trips to `value` (so `0.1` becomes 1/10, not its binary expansion).
+  pub fn from_decimal(value: f64) -> Option<Rational> {
+    if !value.is_finite() {
+      return None;
+    }
+    let literal = value.to_string();
+    let (integer, fraction) = literal.split_once('.').unwrap_or((&literal, ""));
+    let denominator = 10i64.checked_pow(fraction.len() as u32)?;
+    let numerator: i64 = format!("{}{}", integer, fraction).parse().ok()?;
+    Rational::new(numerator, denominator)
+  }
+
+  /// Find the simplest fraction within `tolerance` of `value` using its
+  /// [continued fraction](https://en.wikipedia.org/wiki/Continued_fraction#Best_rational_approximations)
+  /// convergents, giving up once the denominator would exceed `max_denominator`.
+  pub fn approximate(value: f64, tolerance: f64, max_denominator: i64) -> Option<Rational> {
+    if !value.is_finite() {
+      return None;
+    }
+    let (mut previous_numerator, mut numerator) = (0i64, 1i64);
+    let (mut previous_denominator, mut denominator) = (1i64, 0i64);
+    let mut remainder = value;
+    loop {
+      let term = remainder.floor();
+      if term.abs() >= i64::MAX as f64 {
+        return None;
+      }
+      let term = term as i64;
+      (previous_numerator, numerator) = (
+        numerator,
+        term
+          .checked_mul(numerator)?
+          .checked_add(previous_numerator)?,
+      );
+      (previous_denominator, denominator) = (
+        denominator,
+        term
+          .checked_mul(denominator)?
+          .checked_add(previous_denominator)?,
+      );
+      if denominator > max_denominator {
+        return None;
+      }
+      if (value - numerator as f64 / denominator as f64).abs() <= tolerance {
+        return Rational::new(numerator, denominator);
+      }
+      remainder = 1.0 / (remainder - term as f64);
+    }
+  }
+
+  pub fn numerator(&self) -> i64 {
+    self.numerator
+  }
+
+  pub fn denominator(&self) -> i64 {
+    self.denominator
+  }
+
+  pub fn is_integer(&self) -> bool {
+    self.denominator == 1
+  }
+
+  /// The largest integer not greater than the fraction.
+  pub fn floor(self) -> i64 {
+    self.numerator.div_euclid(self.denominator)
+  }
+
+  pub fn to_f64(self) -> f64 {
+    self.numerator as f64 / self.denominator as f64
+  }
+
+  /// The exact decimal expansion, with the repeating block in parentheses like `0.(142857)` for
+  /// 1/7, or `None` when the digits before or in the block run beyond [`MAX_PERIOD`].
+  pub fn periodic_decimal(self) -> Option<String> {
+    let denominator = self.denominator as i128;
+    let numerator = (self.numerator as i128).abs();
+    let sign = if self.numerator < 0 { "-" } else { "" };
+    let integer = numerator / denominator;
+    let mut remainder = numerator % denominator;
+    if remainder == 0 {
+      return Some(format!("{}{}", sign, integer));
+    }
+    // Long division: the digits start repeating as soon as a remainder comes back.
+    let mut digits = String::new();
+    let mut seen = HashMap::new();
+    while remainder != 0 {
+      if let Some(&start) = seen.get(&remainder) {
+        return Some(format!(
+          "{}{}.{}({})",
+          sign,
+          integer,
+          &digits[..start],
+          &digits[start..]
+        ));
+      }
+      if digits.len() >= MAX_PERIOD {
+        return None;
+      }
+      seen.insert(remainder, digits.len());
+      remainder *= 10;
+      digits.push(char::from(b'0' + (remainder / denominator) as u8));
+      remainder %= denominator;
+    }
+    Some(format!("{}{}.{}", sign, integer, digits))
+  }
+
+  pub fn checked_neg(self) -> Option<Rational> {
+    Rational::new(self.numerator.checked_neg()?, self.denominator)
+  }
+
+  pub fn checked_add(self, other: Rational) -> Option<Rational> {
+    Rational::new(
+      self
+        .numerator
+        .checked_mul(other.denominator)?
+        .checked_add(other.numerator.checked_mul(self.denominator)?)?,
+      self.denominator.checked_mul(other.denominator)?,
+    )
+  }
+
+  pub fn checked_sub(self, other: Rational) -> Option<Rational> {
+    self.checked_add(other.checked_neg()?)
+  }
+
+  pub fn checked_mul(self, other: Rational) -> Option<Rational> {
+    Rational::new(
+      self.numerator.checked_mul(other.numerator)?,
+      self.denominator.checked_mul(other.denominator)?,
+    )
+  }
+
+  /// `None` on a zero divisor as well as on overflow.
+  pub fn checked_div(self, other: Rational) -> Option<Rational> {
+    self.checked_mul(Rational::new(other.denominator, other.numerator)?)
+  }
+
+  pub fn checked_pow(self, exponent: i64) -> Option<Rational> {
+    let power = u32::try_from(exponent.checked_abs()?).ok()?;
+    let raised = Rational::new(
+      self.numerator.checked_pow(power)?,
+      self.denominator.checked_pow(power)?,
+    )?;
+    if exponent < 0 {
+      Rational::new(raised.denominator, raised.numerator)
+    } else {
+      Some(raised)
+    }
+  }
+}
+
+impl Ord for Rational {
+  fn cmp(&self, other: &Rational) -> Ordering {
+    // Denominators are positive, so cross-multiplying keeps the order. Widening can't overflow.
+    let left = self.numerator as i128 * other.denominator as i128;
+    let right = other.numerator as i128 * self.denominator as i128;
+    left.cmp(&right)
+  }
+}
+
+impl PartialOrd for Rational {
+  fn partial_cmp(&self, other: &Rational) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl fmt::Display for Rational {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.is_integer() {
+      write!(f, "{}", self.numerator)
+    } else {
+      write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+  }
+}
+
+/// [Euclidean algorithm](https://en.wikipedia.org/wiki/Euclidean_algorithm), always positive.
+fn gcd(mut a: i64, mut b: i64) -> i64 {
+  while b != 0 {
+    (a, b) = (b, a % b);
+  }
+  if a == 0 {
+    1
+  } else {
+    a.abs()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_reduction() {
+    assert_eq!(Rational::new(4, 8), Rational::new(1, 2));
+    assert_eq!(Rational::new(3, -6), Rational::new(-1, 2));
+    assert_eq!(Rational::new(1, 0), None);
+  }
+
+  #[test]
+  fn test_from_decimal() {
+    assert_eq!(Rational::from_decimal(0.1), Rational::new(1, 10));
+    assert_eq!(Rational::from_decimal(2.5), Rational::new(5, 2));
+    assert_eq!(
+      Rational::from_decimal(-3.0),
+      Some(Rational::from_integer(-3))
+    );
+  }
+
+  #[test]
+  fn test_periodic_decimal() {
+    let periodic = |numerator, denominator| {
+      Rational::new(numerator, denominator)
+        .unwrap()
+        .periodic_decimal()
+    };
+    assert_eq!(periodic(1, 4), Some("0.25".to_string()));
+    assert_eq!(periodic(-7, 8), Some("-0.875".to_string()));
+    assert_eq!(periodic(6, 3), Some("2".to_string()));
+    assert_eq!(periodic(1, 3), Some("0.(3)".to_string()));
+    assert_eq!(periodic(1, 7), Some("0.(142857)".to_string()));
+    assert_eq!(periodic(1, 6), Some("0.1(6)".to_string()));
+    assert_eq!(periodic(-22, 7), Some("-3.(142857)".to_string()));
+    assert_eq!(periodic(1, 1_000_003), None);
+  }
+
+  #[test]
+  fn test_approximate() {
+    assert_eq!(Rational::approximate(0.5, 1e-6, 1000), Rational::new(1, 2));
+    assert_eq!(
+      Rational::approximate(0.333333, 1e-6, 1000),
+      Rational::new(1, 3)
+    );
+    assert_eq!(
+      Rational::approximate(-1.0 / 3.0, 1e-6, 1000),
+      Rational::new(-1, 3)
+    );
+    assert_eq!(
+      Rational::approximate(std::f64::consts::PI, 1e-2, 1000),
+      Rational::new(22, 7)
+    );
+    assert_eq!(
+      Rational::approximate(std::f64::consts::PI, 1e-6, 1000),
+      Rational::new(355, 113)
+    );
+    assert_eq!(
+      Rational::approximate(std::f64::consts::PI, 1e-12, 1000),
+      None
+    );
+  }
+
+  #[test]
+  fn test_arithmetic() {
+    let third = Rational::new(1, 3).unwrap();
+    let half = Rational::new(1, 2).unwrap();
+    assert_eq!(third.checked_add(half), Rational::new(5, 6));
+    assert_eq!(third.checked_sub(half), Rational::new(-1, 6));
+    assert_eq!(third.checked_mul(half), Rational::new(1, 6));
+    assert_eq!(third.checked_div(half), Rational::new(2, 3));
+    assert_eq!(half.checked_pow(-2), Some(Rational::from_integer(4)));
+    assert_eq!(half.checked_div(Rational::from_integer(0)), None);
+  }
+
+  #[test]
+  fn test_ordering() {
+    let third = Rational::new(1, 3).unwrap();
+    let half = Rational::new(1, 2).unwrap();
+    assert!(third < half);
+    assert!(Rational::new(-1, 2).unwrap() < third);
+    assert_eq!(half.max(third), half);
+    assert!(Rational::from_integer(i64::MAX) > Rational::new(i64::MAX - 1, 1).unwrap());
+  }
+}
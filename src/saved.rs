@@ -0,0 +1,84 @@
+use crate::error::CalcError;
+use crate::evaluator::Evaluator;
+use crate::value::Value;
+
+impl Evaluator {
+  /// Save the text of an expression under `name`, like `area` for `pi*r^2`, for
+  /// [`Evaluator::use_saved`] to evaluate later. Unlike a function from [`Evaluator::define`] it
+  /// takes no arguments and reads its variables only when it is used. It has to parse, but the
+  /// variables and functions it refers to don't have to exist yet.
+  pub fn save(&mut self, name: &str, expression: &str) -> Result<(), CalcError> {
+    let mut chars = name.chars();
+    let is_name = chars
+      .next()
+      .is_some_and(|first| first.is_alphabetic() || first == '_')
+      && chars.all(|c| c.is_alphanumeric() || c == '_');
+    if !is_name {
+      return Err(CalcError::InvalidAssignment);
+    }
+    self.parse(expression)?;
+    self.saved.insert(name.to_string(), expression.to_string());
+    Ok(())
+  }
+
+  /// Evaluate the expression saved as `name` with the current variables.
+  pub fn use_saved(&mut self, name: &str) -> Result<Value, CalcError> {
+    let expression = self
+      .saved
+      .get(name)
+      .cloned()
+      .ok_or_else(|| CalcError::UnknownSaved(name.to_string()))?;
+    self.evaluate_typed(&expression)
+  }
+
+  /// The text of the expression saved as `name`, if there is one.
+  pub fn saved(&self, name: &str) -> Option<&str> {
+    self.saved.get(name).map(String::as_str)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{CalcError, Evaluator, Value};
+
+  #[test]
+  fn test_saved_expressions() {
+    let mut evaluator = Evaluator::new();
+    evaluator.save("area", "pi*r^2").unwrap();
+    assert_eq!(evaluator.saved("area"), Some("pi*r^2"));
+    assert_eq!(
+      evaluator.use_saved("area"),
+      Err(CalcError::UnknownVariable("r".to_string()))
+    );
+    evaluator.evaluate("r = 1").unwrap();
+    assert_eq!(
+      evaluator.use_saved("area"),
+      Ok(Value::Float(std::f64::consts::PI))
+    );
+    evaluator.evaluate("r = 2").unwrap();
+    assert_eq!(
+      evaluator.use_saved("area"),
+      Ok(Value::Float(4.0 * std::f64::consts::PI))
+    );
+
+    evaluator.save("area", "r * r").unwrap();
+    assert_eq!(evaluator.use_saved("area"), Ok(Value::Integer(4)));
+    assert_eq!(evaluator.evaluate("ans + 1"), Ok(5.0));
+  }
+
+  #[test]
+  fn test_saved_expression_errors() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+      evaluator.use_saved("area"),
+      Err(CalcError::UnknownSaved("area".to_string()))
+    );
+    assert_eq!(evaluator.save("2x", "1"), Err(CalcError::InvalidAssignment));
+    assert_eq!(evaluator.save("", "1"), Err(CalcError::InvalidAssignment));
+    assert_eq!(
+      evaluator.save("broken", "(1 + 2"),
+      Err(CalcError::MismatchedParentheses)
+    );
+    assert_eq!(evaluator.saved("broken"), None);
+  }
+}
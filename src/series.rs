@@ -0,0 +1,314 @@
+use crate::ast::Expr;
+use crate::error::CalcError;
+use crate::evaluator::Evaluator;
+use crate::functions::Arity;
+use crate::token::Operator;
+use crate::value::Value;
+
+/// Functions of the form `name(variable, a, b, body)` that evaluate `body` repeatedly with
+/// `variable` bound:
+/// - `sum` and `prod` combine `body` for every integer `variable` from `a` to `b` inclusive.
+/// - `iterate` starts with `variable = a` and replaces it with `body` `b` times.
+///
+/// `sum` and `prod` also combine the numbers of a single list argument, as in `sum([1, 2, 3])`.
+pub(crate) const SERIES: &[&str] = &["sum", "prod", "iterate"];
+
+/// Every series takes the variable, two numbers and the body.
+pub(crate) const SERIES_ARITY: usize = 4;
+
+/// The arguments a series accepts, counting the single list of `sum` and `prod`.
+pub(crate) fn series_arity(name: &str) -> Arity {
+  match name {
+    "iterate" => Arity::Exactly(SERIES_ARITY),
+    _ => Arity::Either(1, SERIES_ARITY),
+  }
+}
+
+impl Evaluator {
+  /// Evaluate a series, binding the index variable for the duration of the loop only.
+  pub(crate) fn series(&mut self, name: &str, arguments: &[Expr]) -> Result<Value, CalcError> {
+    if !is_aggregate(name, arguments) {
+      self.allow_loop(name)?;
+    }
+    let (operator, identity) = match name {
+      "sum" => (Operator::Add, 0.0),
+      "prod" => (Operator::Multiply, 1.0),
+      "iterate" => return self.iterate(arguments),
+      _ => return Err(CalcError::UnknownFunction(name.to_string())),
+    };
+    if let [list] = arguments {
+      let items = self.eval(list)?.flatten();
+      let identity = self.number(identity);
+      return items
+        .into_iter()
+        .try_fold(identity, |total, item| self.apply(operator, total, item));
+    }
+    let (index, start, end, body) = series_arguments(name, arguments)?;
+    let start = self.bound(name, start)?;
+    let end = self.bound(name, end)?;
+
+    self.scoped(index, |evaluator| {
+      (start..=end).try_fold(evaluator.number(identity), |total, i| {
+        evaluator.check_deadline()?;
+        evaluator.bind(index, evaluator.number(i as f64));
+        let term = evaluator.eval(body)?;
+        evaluator.apply(operator, total, term)
+      })
+    })
+  }
+
+  /// Evaluate `iterate(x, initial, count, body)`, feeding each result back in as `x`.
+  fn iterate(&mut self, arguments: &[Expr]) -> Result<Value, CalcError> {
+    let (variable, initial, count, body) = series_arguments("iterate", arguments)?;
+    let initial = self.eval(initial)?;
+    let count = self.bound("iterate", count)?;
+    if count < 0 {
+      return Err(CalcError::DomainError {
+        function: "iterate".to_string(),
+        argument: count as f64,
+      });
+    }
+
+    self.scoped(variable, |evaluator| {
+      (0..count).try_fold(initial, |current, _| {
+        evaluator.check_deadline()?;
+        evaluator.bind(variable, current);
+        evaluator.eval(body)
+      })
+    })
+  }
+
+  /// Fail in a sandbox, which turns off every series but `sum` and `prod` of a list.
+  pub(crate) fn allow_loop(&self, name: &str) -> Result<(), CalcError> {
+    match name {
+      "iterate" => self.allow("'iterate'"),
+      _ => self.allow(&format!("'{}' over a range", name)),
+    }
+  }
+
+  /// Evaluate one of the bounds of a series, which has to be an integer.
+  fn bound(&mut self, name: &str, bound: &Expr) -> Result<i64, CalcError> {
+    match self.eval(bound)?.classify() {
+      Value::Integer(bound) => Ok(bound),
+      other => Err(CalcError::DomainError {
+        function: name.to_string(),
+        argument: other.to_f64(),
+      }),
+    }
+  }
+}
+
+/// Whether a call to a series is `sum` or `prod` of a list rather than a loop.
+pub(crate) fn is_aggregate(name: &str, arguments: &[Expr]) -> bool {
+  name != "iterate" && arguments.len() == 1
+}
+
+/// Split the arguments of a series into the index variable, the bounds and the body.
+pub(crate) fn series_arguments<'a>(
+  name: &str,
+  arguments: &'a [Expr],
+) -> Result<(&'a str, &'a Expr, &'a Expr, &'a Expr), CalcError> {
+  let [index, start, end, body] = arguments else {
+    return Err(series_arity(name).mismatch(name, arguments.len()));
+  };
+  let Expr::Variable(index) = index else {
+    return Err(CalcError::Unsupported(format!(
+      "the index of '{}' must be a variable",
+      name
+    )));
+  };
+  Ok((index, start, end, body))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use crate::{
+    evaluate, evaluate_typed, CalcError, Config, Evaluator, NumberMode, Rational, Value,
+  };
+
+  #[test]
+  fn test_sum() {
+    assert_eq!(evaluate("sum(i, 1, 4, i^2)"), Ok(30.0));
+    assert_eq!(evaluate("sum(k, 1, 100, k)"), Ok(5050.0));
+    assert_eq!(evaluate("sum(i, 0, 10, 2^-i)"), Ok(2.0 - 2f64.powi(-10)));
+    assert_eq!(evaluate("sum(i, -2, 2, i)"), Ok(0.0));
+    assert_eq!(evaluate("sum(i, 3, 1, i)"), Ok(0.0));
+  }
+
+  #[test]
+  fn test_prod() {
+    assert_eq!(evaluate("prod(i, 1, 4, i)"), Ok(24.0));
+    assert_eq!(evaluate("prod(i, 1, 3, 2*i + 1)"), Ok(105.0));
+    assert_eq!(evaluate("prod(k, 1, 5, 1 + 1/k)"), Ok(6.0));
+    assert_eq!(evaluate("prod(i, 1, 0, i)"), Ok(1.0));
+    assert_eq!(evaluate("prod(i, 5, 1, 0)"), Ok(1.0));
+  }
+
+  #[test]
+  fn test_sum_and_prod_of_lists() {
+    assert_eq!(evaluate("sum([1, 2, 3])"), Ok(6.0));
+    assert_eq!(evaluate("prod([2, [3, 4]])"), Ok(24.0));
+    assert_eq!(evaluate("sum([])"), Ok(0.0));
+    assert_eq!(evaluate("sum(5)"), Ok(5.0));
+    assert_eq!(evaluate_typed("sum([1/2, 1/2])"), Ok(Value::Integer(1)));
+  }
+
+  #[test]
+  fn test_iterate() {
+    assert_eq!(evaluate("iterate(x, 1, 10, x*2 + 1)"), Ok(2047.0));
+    assert_eq!(evaluate("iterate(x, 5, 0, x + 1)"), Ok(5.0));
+    // Newton's method for the square root of 2.
+    let root = evaluate("iterate(x, 1, 20, (x + 2/x) / 2)").unwrap();
+    assert!((root - 2f64.sqrt()).abs() < 1e-15);
+    assert_eq!(
+      evaluate("iterate(x, 1, -1, x)"),
+      Err(CalcError::DomainError {
+        function: "iterate".to_string(),
+        argument: -1.0
+      })
+    );
+  }
+
+  #[test]
+  fn test_sum_is_exact_in_fraction_mode() {
+    let mut evaluator = Evaluator::with_config(Config {
+      mode: NumberMode::Fraction,
+      ..Config::default()
+    });
+    assert_eq!(
+      evaluator.evaluate_typed("sum(n, 1, 3, 1/n)"),
+      Ok(Value::Rational(Rational::new(11, 6).unwrap()))
+    );
+  }
+
+  #[test]
+  fn test_sum_index_is_scoped() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.evaluate("x = 5"), Ok(5.0));
+    assert_eq!(evaluator.evaluate("sum(x, 1, 3, x * 2)"), Ok(12.0));
+    assert_eq!(evaluator.evaluate("x"), Ok(5.0));
+    assert_eq!(evaluator.evaluate("sum(j, 1, 3, j)"), Ok(6.0));
+    assert_eq!(
+      evaluator.evaluate("j"),
+      Err(CalcError::UnknownVariable("j".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_sandbox() {
+    let mut sandbox = Evaluator::with_config(Config {
+      sandbox: true,
+      ..Config::default()
+    });
+    assert_eq!(
+      sandbox.evaluate("sum(i, 1, 4, i)"),
+      Err(CalcError::FeatureDisabled("'sum' over a range".to_string()))
+    );
+    assert_eq!(
+      sandbox.evaluate("iterate(x, 1, 3, 2x)"),
+      Err(CalcError::FeatureDisabled("'iterate'".to_string()))
+    );
+    assert_eq!(
+      sandbox.validate("1 + prod(i, 1, 4, i)"),
+      Err(CalcError::FeatureDisabled(
+        "'prod' over a range".to_string()
+      ))
+    );
+    assert_eq!(
+      sandbox.define("f(x) = x"),
+      Err(CalcError::FeatureDisabled("Defining functions".to_string()))
+    );
+    assert_eq!(sandbox.evaluate("sum([1, 2, 3]) + sqrt(16)"), Ok(10.0));
+    assert_eq!(
+      sandbox.evaluate(&"1+".repeat(600)),
+      Err(CalcError::InputTooLong {
+        length: 1200,
+        limit: crate::SANDBOX_MAX_INPUT_LENGTH
+      })
+    );
+
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.evaluate("sum(i, 1, 4, i)"), Ok(10.0));
+    assert_eq!(evaluator.evaluate("iterate(x, 1, 3, 2x)"), Ok(8.0));
+    assert_eq!(evaluator.define("f(x) = x"), Ok(()));
+    assert!(evaluator
+      .evaluate(&"1+".repeat(600))
+      .is_err_and(|error| error.is_syntax_error()));
+  }
+
+  #[test]
+  fn test_timeout() {
+    let timeout = Duration::from_millis(10);
+    let mut evaluator = Evaluator::with_config(Config {
+      timeout: Some(timeout),
+      ..Config::default()
+    });
+    assert_eq!(
+      evaluator.evaluate("iterate(x, 0, 10^12, x + 1)"),
+      Err(CalcError::Timeout(timeout))
+    );
+    assert_eq!(
+      evaluator.evaluate("sum(i, 1, 10^12, i)"),
+      Err(CalcError::Timeout(timeout))
+    );
+    assert_eq!(evaluator.evaluate("sum(i, 1, 100, i)"), Ok(5050.0));
+  }
+
+  #[test]
+  fn test_operation_limit() {
+    let mut evaluator = Evaluator::with_config(Config {
+      max_operations: Some(100),
+      ..Config::default()
+    });
+    assert_eq!(
+      evaluator.evaluate("iterate(x, 0, 10^12, x + 1)"),
+      Err(CalcError::OperationLimitExceeded(100))
+    );
+    assert_eq!(
+      evaluator.evaluate("sum(i, 1, 60, 2*i)"),
+      Err(CalcError::OperationLimitExceeded(100))
+    );
+    assert_eq!(
+      evaluator.evaluate_with_stats("sum(i, 1, 50, 2*i)"),
+      Ok((2550.0, 100))
+    );
+    assert_eq!(evaluator.evaluate("1 + 2"), Ok(3.0));
+  }
+
+  #[test]
+  fn test_sum_errors() {
+    assert_eq!(
+      evaluate("sum(i, 1, 2.5, i)"),
+      Err(CalcError::DomainError {
+        function: "sum".to_string(),
+        argument: 2.5
+      })
+    );
+    assert!(matches!(
+      evaluate("sum(2, 1, 3, i)"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert_eq!(
+      evaluate("sum(i, 1, 3)"),
+      Err(CalcError::ArityChoiceMismatch {
+        function: "sum".to_string(),
+        expected: (1, 4),
+        found: 3
+      })
+    );
+    assert_eq!(
+      evaluate("prod(1, 2)").unwrap_err().to_string(),
+      "'prod' takes 1 or 4 argument(s) but was given 2"
+    );
+    assert_eq!(
+      evaluate("iterate(x, 1, 2)"),
+      Err(CalcError::ArityMismatch {
+        function: "iterate".to_string(),
+        expected: 4,
+        found: 3
+      })
+    );
+  }
+}
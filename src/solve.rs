@@ -0,0 +1,353 @@
+use std::fmt;
+
+use crate::ast::Expr;
+use crate::error::CalcError;
+use crate::evaluator::Evaluator;
+use crate::parser::shunting_yard;
+use crate::token::{Operator, Parenthesis, Token, PLACEHOLDER};
+use crate::value::Value;
+
+/// The value that makes an equation hold.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Solution {
+  pub variable: String,
+  pub value: f64,
+}
+
+impl fmt::Display for Solution {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} = {}", self.variable, Value::from_f64(self.value))
+  }
+}
+
+/// The values [`Evaluator::equivalent`] gives variables, a mix of signs, fractions and the
+/// special cases `0` and `1`.
+const SAMPLES: [f64; 8] = [-2.5, -1.0, 0.0, 0.5, 1.0, 1.75, 3.0, 10.0];
+
+/// A side of an equation reduced to `coefficient * x + constant`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Linear {
+  coefficient: f64,
+  constant: f64,
+}
+
+impl Linear {
+  fn constant(constant: f64) -> Linear {
+    Linear {
+      coefficient: 0.0,
+      constant,
+    }
+  }
+
+  fn is_constant(&self) -> bool {
+    self.coefficient == 0.0
+  }
+}
+
+impl Evaluator {
+  /// Solve a linear equation in a single variable, such as `2*x + 3 = 7`. The variable can also
+  /// be a single `?` placeholder, as in `2 * ? = 10`.
+  pub fn solve(&mut self, equation: &str) -> Result<Solution, CalcError> {
+    let tokens = self.tokenise(equation)?;
+    let placeholder = Token::Variable(PLACEHOLDER.to_string());
+    if tokens.iter().filter(|&token| *token == placeholder).count() > 1 {
+      return Err(unsupported("the placeholder '?' can only appear once"));
+    }
+    let (left, right) = split_equation(tokens)?;
+    let left = Expr::from_rpn(shunting_yard(left)?)?;
+    let right = Expr::from_rpn(shunting_yard(right)?)?;
+
+    // Move everything to the left so the equation reads `expr = 0`.
+    let expr = Expr::Binary(Operator::Subtract, Box::new(left), Box::new(right));
+    let variable = match expr.variables()[..] {
+      [variable] => variable.to_string(),
+      [] => return Err(unsupported("the equation has no variable to solve for")),
+      _ => {
+        return Err(unsupported(
+          "only equations in a single variable can be solved",
+        ))
+      }
+    };
+
+    let linear = self.linear(&expr)?;
+    if linear.is_constant() {
+      return Err(CalcError::NoUniqueSolution);
+    }
+    Ok(Solution {
+      variable,
+      value: -linear.constant / linear.coefficient,
+    })
+  }
+
+  /// Report whether an equation like `2+2 = 4` holds, comparing both sides like `==` does. Without
+  /// a top-level `=`, the expression has to be a comparison itself, like `2+2 == 4`.
+  pub fn holds(&mut self, equation: &str) -> Result<bool, CalcError> {
+    let tokens = self.tokenise(equation)?;
+    let expr = if tokens.contains(&Token::Assign(None)) {
+      let (left, right) = split_equation(tokens)?;
+      let left = Expr::from_rpn(shunting_yard(left)?)?;
+      let right = Expr::from_rpn(shunting_yard(right)?)?;
+      Expr::Binary(Operator::Equal, Box::new(left), Box::new(right))
+    } else {
+      Expr::from_rpn(shunting_yard(tokens)?)?
+    };
+    match self.evaluate_expr(&expr)? {
+      Value::Boolean(holds) => Ok(holds),
+      _ => Err(unsupported("only equations and comparisons can be checked")),
+    }
+  }
+
+  /// Report whether two expressions have the same value, comparing like `==` does. Variables that
+  /// aren't set are given several sample values, so `(x+1)^2` and `x^2+2*x+1` are equivalent.
+  /// That only shows they agree at the samples, though any two different polynomials of low
+  /// degree will disagree at one of them. A sample where both sides fail is skipped, but not
+  /// every one: expressions no sample gives a value for aren't equivalent. Mistakes that don't
+  /// depend on values, like an unknown function, are errors.
+  pub fn equivalent(&mut self, left: &str, right: &str) -> Result<bool, CalcError> {
+    let left = self.parse(left)?;
+    let right = self.parse(right)?;
+    let mut variables = left.variables();
+    for variable in right.variables() {
+      if !variables.contains(&variable) {
+        variables.push(variable);
+      }
+    }
+    variables.retain(|name| self.variable(name).is_none() && self.recall_name(name).is_none());
+    self.check(&left, &mut variables.clone())?;
+    self.check(&right, &mut variables.clone())?;
+    let rounds = if variables.is_empty() {
+      1
+    } else {
+      SAMPLES.len()
+    };
+
+    let mut compared = false;
+    for round in 0..rounds {
+      // Shift the samples for every further variable, so they don't all take the same value.
+      let bindings: Vec<(&str, Value)> = variables
+        .iter()
+        .enumerate()
+        .map(|(i, &name)| (name, self.number(SAMPLES[(round + 3 * i) % SAMPLES.len()])))
+        .collect();
+      let left = self.bind_all(&bindings, &left);
+      let right = self.bind_all(&bindings, &right);
+      match (left, right) {
+        (Err(_), Err(_)) => continue,
+        (Ok(left), Ok(right)) => {
+          compared = true;
+          if left == right || (left.to_f64().is_nan() && right.to_f64().is_nan()) {
+            continue;
+          }
+          if self.apply(Operator::Equal, left, right)? != Value::Boolean(true) {
+            return Ok(false);
+          }
+        }
+        _ => return Ok(false),
+      }
+    }
+    Ok(compared)
+  }
+
+  /// Reduce an expression in a single variable to its linear form, folding everything that
+  /// doesn't depend on the variable into constants.
+  fn linear(&mut self, expr: &Expr) -> Result<Linear, CalcError> {
+    if expr.variables().is_empty() {
+      return Ok(Linear::constant(self.eval(expr)?.to_f64()));
+    }
+
+    match expr {
+      Expr::Variable(_) => Ok(Linear {
+        coefficient: 1.0,
+        constant: 0.0,
+      }),
+      Expr::Unary(Operator::Negate, operand) => {
+        let operand = self.linear(operand)?;
+        Ok(Linear {
+          coefficient: -operand.coefficient,
+          constant: -operand.constant,
+        })
+      }
+      Expr::Binary(operator, left, right) => {
+        let left = self.linear(left)?;
+        let right = self.linear(right)?;
+        match operator {
+          Operator::Add => Ok(Linear {
+            coefficient: left.coefficient + right.coefficient,
+            constant: left.constant + right.constant,
+          }),
+          Operator::Subtract => Ok(Linear {
+            coefficient: left.coefficient - right.coefficient,
+            constant: left.constant - right.constant,
+          }),
+          Operator::Multiply if left.is_constant() => Ok(Linear {
+            coefficient: left.constant * right.coefficient,
+            constant: left.constant * right.constant,
+          }),
+          Operator::Multiply if right.is_constant() => Ok(Linear {
+            coefficient: left.coefficient * right.constant,
+            constant: left.constant * right.constant,
+          }),
+          Operator::Divide if right.is_constant() && right.constant == 0.0 => {
+            Err(CalcError::DivisionByZero)
+          }
+          Operator::Divide if right.is_constant() => Ok(Linear {
+            coefficient: left.coefficient / right.constant,
+            constant: left.constant / right.constant,
+          }),
+          _ => Err(unsupported("the equation is not linear")),
+        }
+      }
+      _ => Err(unsupported("the equation is not linear")),
+    }
+  }
+}
+
+/// Split equation tokens at the single top-level `=`.
+fn split_equation(tokens: Vec<Token>) -> Result<(Vec<Token>, Vec<Token>), CalcError> {
+  let mut depth = 0;
+  let mut equals = None;
+  for (index, token) in tokens.iter().enumerate() {
+    match token {
+      Token::Parenthesis(Parenthesis::Left) | Token::Bracket(_, Parenthesis::Left) => depth += 1,
+      Token::Parenthesis(Parenthesis::Right) | Token::Bracket(_, Parenthesis::Right) => depth -= 1,
+      Token::Assign(None) if depth == 0 && equals.is_none() => equals = Some(index),
+      Token::Assign(_) => return Err(unsupported("an equation needs exactly one '='")),
+      _ => {}
+    }
+  }
+
+  let equals = equals.ok_or_else(|| unsupported("an equation needs exactly one '='"))?;
+  let mut left = tokens;
+  let right = left.split_off(equals + 1);
+  left.pop();
+  Ok((left, right))
+}
+
+fn unsupported(reason: &str) -> CalcError {
+  CalcError::Unsupported(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn solve(equation: &str) -> Result<Solution, CalcError> {
+    Evaluator::new().solve(equation)
+  }
+
+  fn solution(variable: &str, value: f64) -> Result<Solution, CalcError> {
+    Ok(Solution {
+      variable: variable.to_string(),
+      value,
+    })
+  }
+
+  #[test]
+  fn test_linear_equations() {
+    assert_eq!(solve("2*x + 3 = 7"), solution("x", 2.0));
+    assert_eq!(solve("7 = 3 + x*2"), solution("x", 2.0));
+    assert_eq!(solve("y/4 - 1 = 2"), solution("y", 12.0));
+    assert_eq!(solve("3*(t - 1) = t + 5"), solution("t", 4.0));
+    assert_eq!(solve("-(n + 1) = sqrt(16)"), solution("n", -5.0));
+  }
+
+  #[test]
+  fn test_placeholders() {
+    assert_eq!(solve("2 * ? = 10"), solution("?", 5.0));
+    assert_eq!(solve("? + 3 = 10"), solution("?", 7.0));
+    assert_eq!(solve("10 - ? = 4"), solution("?", 6.0));
+    assert_eq!(solve("? / 4 = 2.5"), solution("?", 10.0));
+    assert_eq!(solve("12 = 3?"), solution("?", 4.0));
+    assert_eq!(solve("(? - 1) * 2 = 8").unwrap().to_string(), "? = 5");
+    assert!(matches!(
+      solve("? + ? = 10"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert!(matches!(
+      solve("? + x = 10"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert_eq!(
+      Evaluator::new().evaluate("? + 1"),
+      Err(CalcError::UnknownVariable("?".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_check() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.holds("2+2 = 4"), Ok(true));
+    assert_eq!(evaluator.holds("2+2 == 4"), Ok(true));
+    assert_eq!(evaluator.holds("2+2 = 5"), Ok(false));
+    assert_eq!(evaluator.holds("0.1 + 0.2 = 0.3"), Ok(true));
+    assert_eq!(evaluator.holds("(1 < 2) = 1"), Ok(true));
+    assert_eq!(evaluator.holds("3 > 4"), Ok(false));
+    assert!(matches!(
+      evaluator.holds("2+2"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert!(matches!(
+      evaluator.holds("1 = 1 = 1"),
+      Err(CalcError::Unsupported(_))
+    ));
+  }
+
+  #[test]
+  fn test_equivalent() {
+    let mut evaluator = Evaluator::new();
+    let mut equivalent = |left, right| evaluator.equivalent(left, right).unwrap();
+    assert!(equivalent("(x+1)^2", "x^2+2*x+1"));
+    assert!(equivalent("(a+b)*(a-b)", "a^2 - b^2"));
+    assert!(equivalent("sin(x)^2 + cos(x)^2", "1"));
+    assert!(equivalent("0.1 + 0.2", "0.3"));
+    assert!(equivalent("abs(x)", "sqrt(x^2)"));
+    assert!(!equivalent("(x+1)^2", "x^2+1"));
+    assert!(!equivalent("a - b", "b - a"));
+    assert!(!equivalent("2 + 2", "5"));
+    assert!(!equivalent("1 // x", "1"));
+
+    evaluator.evaluate("y = 3").unwrap();
+    assert_eq!(evaluator.equivalent("y * 2", "6"), Ok(true));
+    assert_eq!(evaluator.variable("y"), Some(Value::Float(3.0)));
+    assert_eq!(
+      evaluator.evaluate("equivalent(\"x * 2\", \"x + x\") + 1"),
+      Ok(2.0)
+    );
+    assert!(evaluator.equivalent("(x", "x").is_err());
+  }
+
+  #[test]
+  fn test_equivalent_errors() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+      evaluator.equivalent("nope(1)", "other(2)"),
+      Err(CalcError::UnknownFunction("nope".to_string()))
+    );
+    assert_eq!(
+      evaluator.equivalent("1//0", "sqrt(4, 5)"),
+      Err(CalcError::ArityMismatch {
+        function: "sqrt".to_string(),
+        expected: 1,
+        found: 2
+      })
+    );
+    // Both sides fail for every sample, so nothing shows they agree.
+    assert_eq!(evaluator.equivalent("1//0", "2//0"), Ok(false));
+    assert_eq!(evaluator.equivalent("1 // (x - x)", "1 // 0"), Ok(false));
+  }
+
+  #[test]
+  fn test_solution_display() {
+    assert_eq!(solve("2*x + 3 = 7").unwrap().to_string(), "x = 2");
+  }
+
+  #[test]
+  fn test_unsupported_equations() {
+    assert!(matches!(solve("x*x = 4"), Err(CalcError::Unsupported(_))));
+    assert!(matches!(solve("x + y = 4"), Err(CalcError::Unsupported(_))));
+    assert!(matches!(solve("2 / x = 4"), Err(CalcError::Unsupported(_))));
+    assert!(matches!(solve("x + 1"), Err(CalcError::Unsupported(_))));
+    assert!(matches!(solve("x = 1 = 2"), Err(CalcError::Unsupported(_))));
+    assert_eq!(solve("x - x = 1"), Err(CalcError::NoUniqueSolution));
+  }
+}
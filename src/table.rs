@@ -0,0 +1,305 @@
+use crate::ast::Expr;
+use crate::error::CalcError;
+use crate::evaluator::Evaluator;
+use crate::value::Value;
+
+/// Allowance for rounding errors when deciding whether the last step lands on the end of a range.
+const STEP_TOLERANCE: f64 = 1e-9;
+
+/// Functions of the form `name(body, variable, start, end, step)` that sample `body` like
+/// [`Evaluator::table`] and give its largest or smallest result, like
+/// `rangemax(sin(x), x, 0, 3, 0.01)`.
+pub(crate) const RANGE_EXTREMA: &[&str] = &["rangemax", "rangemin"];
+
+/// The body, the variable, and the start, end and step of the range.
+pub(crate) const RANGE_EXTREMUM_ARITY: usize = 5;
+
+impl Evaluator {
+  /// Evaluate an expression for every value of `variable` from `start` to `end` inclusive,
+  /// `step` apart, returning `(variable, result)` pairs. The expression is parsed only once, and
+  /// the variable keeps its previous value afterwards.
+  pub fn table(
+    &mut self,
+    expression: &str,
+    variable: &str,
+    start: f64,
+    end: f64,
+    step: f64,
+  ) -> Result<Vec<(f64, f64)>, CalcError> {
+    check_range(start, end, step)?;
+    let expr = self.parse(expression)?;
+    self.start_clock();
+    self.sample(&expr, variable, start, end, step)
+  }
+
+  /// Evaluate a call to one of the [`RANGE_EXTREMA`].
+  pub(crate) fn range_extremum(
+    &mut self,
+    name: &str,
+    arguments: &[Expr],
+  ) -> Result<Value, CalcError> {
+    self.allow_loop(name)?;
+    let (body, variable, bounds) = range_arguments(name, arguments)?;
+    let [start, end, step] = bounds.map(|bound| self.eval(bound).map(|value| value.to_f64()));
+    let (start, end, step) = (start?, end?, step?);
+    check_range(start, end, step)?;
+    let results = self.sample(body, variable, start, end, step)?;
+    let results = results.into_iter().map(|(_, result)| result);
+    let extremum = match name {
+      "rangemax" => results.fold(f64::NEG_INFINITY, f64::max),
+      _ => results.fold(f64::INFINITY, f64::min),
+    };
+    Ok(self.number(extremum))
+  }
+
+  /// Evaluate `expr` for every value of `variable` in a range [`check_range`] accepted.
+  fn sample(
+    &mut self,
+    expr: &Expr,
+    variable: &str,
+    start: f64,
+    end: f64,
+    step: f64,
+  ) -> Result<Vec<(f64, f64)>, CalcError> {
+    // Multiplying instead of adding up the steps keeps rounding errors from accumulating.
+    let steps = ((end - start) / step + STEP_TOLERANCE).floor() as u64;
+    self.scoped(variable, |evaluator| {
+      (0..=steps)
+        .map(|i| {
+          evaluator.check_deadline()?;
+          let x = start + i as f64 * step;
+          evaluator.bind(variable, evaluator.number(x));
+          Ok((x, evaluator.eval(expr)?.to_f64()))
+        })
+        .collect()
+    })
+  }
+
+  /// Evaluate an expression over a range like [`Evaluator::table`] and count how many results
+  /// fall in each of `buckets` equal bins between the smallest and largest. Results that aren't
+  /// finite are left out.
+  pub fn histogram(
+    &mut self,
+    expression: &str,
+    variable: &str,
+    start: f64,
+    end: f64,
+    step: f64,
+    buckets: usize,
+  ) -> Result<Histogram, CalcError> {
+    if buckets == 0 {
+      return Err(CalcError::InvalidRange(
+        "a histogram needs at least one bucket".to_string(),
+      ));
+    }
+    let values: Vec<f64> = self
+      .table(expression, variable, start, end, step)?
+      .into_iter()
+      .map(|(_, value)| value)
+      .filter(|value| value.is_finite())
+      .collect();
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let mut counts = vec![0; buckets];
+    for value in values {
+      let position = match max - min {
+        0.0 => 0.0,
+        width => (value - min) / width * buckets as f64,
+      };
+      // The largest result belongs in the last bucket rather than one past it.
+      counts[(position as usize).min(buckets - 1)] += 1;
+    }
+    Ok(Histogram { min, max, counts })
+  }
+}
+
+/// Fail unless a range has a positive step and finite bounds in order.
+fn check_range(start: f64, end: f64, step: f64) -> Result<(), CalcError> {
+  if step <= 0.0 || !step.is_finite() {
+    return Err(CalcError::InvalidRange(format!(
+      "the step must be positive, not {}",
+      step
+    )));
+  }
+  if !start.is_finite() || !end.is_finite() {
+    return Err(CalcError::InvalidRange(
+      "the bounds must be finite".to_string(),
+    ));
+  }
+  if start > end {
+    return Err(CalcError::InvalidRange(format!(
+      "the start {} is greater than the end {}",
+      start, end
+    )));
+  }
+  Ok(())
+}
+
+/// Split the arguments of one of the [`RANGE_EXTREMA`] into the body, the variable, and the
+/// start, end and step.
+pub(crate) fn range_arguments<'a>(
+  name: &str,
+  arguments: &'a [Expr],
+) -> Result<(&'a Expr, &'a str, [&'a Expr; 3]), CalcError> {
+  let [body, variable, start, end, step] = arguments else {
+    return Err(CalcError::ArityMismatch {
+      function: name.to_string(),
+      expected: RANGE_EXTREMUM_ARITY,
+      found: arguments.len(),
+    });
+  };
+  let Expr::Variable(variable) = variable else {
+    return Err(CalcError::Unsupported(format!(
+      "the second argument of '{}' must be a variable",
+      name
+    )));
+  };
+  Ok((body, variable, [start, end, step]))
+}
+
+/// How the results of [`Evaluator::histogram`] are spread between their smallest and largest.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Histogram {
+  pub min: f64,
+  pub max: f64,
+  /// The number of results in each bucket, lowest first.
+  pub counts: Vec<usize>,
+}
+
+impl Histogram {
+  /// The lower and upper bound of bucket `i`.
+  pub fn bounds(&self, i: usize) -> (f64, f64) {
+    let width = (self.max - self.min) / self.counts.len() as f64;
+    (
+      self.min + i as f64 * width,
+      self.min + (i + 1) as f64 * width,
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{CalcError, Evaluator};
+
+  #[test]
+  fn test_table() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+      evaluator.table("x^2", "x", 0.0, 2.0, 0.5),
+      Ok(vec![
+        (0.0, 0.0),
+        (0.5, 0.25),
+        (1.0, 1.0),
+        (1.5, 2.25),
+        (2.0, 4.0)
+      ])
+    );
+    assert_eq!(
+      evaluator.table("sin(x)", "x", 0.0, 6.5, 0.5).unwrap().len(),
+      14
+    );
+    assert_eq!(evaluator.table("t", "t", 0.0, 0.3, 0.1).unwrap().len(), 4);
+    assert_eq!(
+      evaluator.table("1", "x", 1.0, 1.0, 1.0),
+      Ok(vec![(1.0, 1.0)])
+    );
+  }
+
+  #[test]
+  fn test_range_extrema() {
+    let mut evaluator = Evaluator::new();
+    let max = evaluator
+      .evaluate("rangemax(sin(x), x, 0, 6.28, 0.01)")
+      .unwrap();
+    assert!((max - 1.0).abs() < 1e-4);
+    assert_eq!(
+      evaluator.evaluate("rangemin(x^2 - 2x, x, -3, 3, 0.5)"),
+      Ok(-1.0)
+    );
+    assert_eq!(
+      evaluator.evaluate("rangemax(x^2 - 2x, x, -3, 3, 0.5)"),
+      Ok(15.0)
+    );
+    assert_eq!(evaluator.evaluate("rangemax(x, x, 2, 2, 1) + 1"), Ok(3.0));
+    assert_eq!(
+      evaluator.evaluate("x"),
+      Err(CalcError::UnknownVariable("x".to_string()))
+    );
+    for invalid in ["rangemax(x, x, 1, 0, 1)", "rangemin(x, x, 0, 1, 0)"] {
+      assert!(matches!(
+        evaluator.evaluate(invalid),
+        Err(CalcError::InvalidRange(_))
+      ));
+    }
+    assert!(matches!(
+      evaluator.evaluate("rangemax(x, 2, 0, 1, 1)"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert_eq!(
+      evaluator.validate("rangemin(x * y, x, 0, 1, 0.5)"),
+      Err(CalcError::UnknownVariable("y".to_string()))
+    );
+    assert_eq!(evaluator.validate("rangemin(x, x, 0, 1, 0.5)"), Ok(()));
+  }
+
+  #[test]
+  fn test_histogram() {
+    let mut evaluator = Evaluator::new();
+    let histogram = evaluator
+      .histogram("2x + 1", "x", 0.0, 9.0, 1.0, 5)
+      .unwrap();
+    assert_eq!((histogram.min, histogram.max), (1.0, 19.0));
+    assert_eq!(histogram.counts, [2, 2, 2, 2, 2]);
+    assert_eq!(histogram.bounds(1), (4.6, 8.2));
+    assert_eq!(
+      evaluator
+        .histogram("x", "x", 0.0, 3.0, 1.0, 2)
+        .unwrap()
+        .counts,
+      [2, 2]
+    );
+    assert_eq!(
+      evaluator
+        .histogram("1 / x", "x", 0.0, 2.0, 1.0, 3)
+        .unwrap()
+        .counts,
+      [1, 0, 1]
+    );
+    assert_eq!(
+      evaluator
+        .histogram("1", "x", 0.0, 2.0, 1.0, 2)
+        .unwrap()
+        .counts,
+      [3, 0]
+    );
+    assert!(matches!(
+      evaluator.histogram("x", "x", 0.0, 1.0, 1.0, 0),
+      Err(CalcError::InvalidRange(_))
+    ));
+  }
+
+  #[test]
+  fn test_table_keeps_variables() {
+    let mut evaluator = Evaluator::new();
+    evaluator.evaluate("a = 10").unwrap();
+    assert_eq!(
+      evaluator.table("a * x", "x", 1.0, 2.0, 1.0),
+      Ok(vec![(1.0, 10.0), (2.0, 20.0)])
+    );
+    assert_eq!(
+      evaluator.evaluate("x"),
+      Err(CalcError::UnknownVariable("x".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_invalid_ranges() {
+    let mut evaluator = Evaluator::new();
+    for (start, end, step) in [(0.0, 1.0, 0.0), (0.0, 1.0, -0.5), (2.0, 1.0, 0.5)] {
+      assert!(matches!(
+        evaluator.table("x", "x", start, end, step),
+        Err(CalcError::InvalidRange(_))
+      ));
+    }
+  }
+}
@@ -0,0 +1,152 @@
+use std::iter;
+
+use crate::ast::Expr;
+use crate::error::CalcError;
+use crate::evaluator::Evaluator;
+use crate::value::Value;
+
+/// `apply("%1 * %2 + %3", 2, 3, 4)` fills the placeholders `%1`, `%2`, ... of an expression in
+/// text with the numbers after it, then evaluates the result, here `(2) * (3) + (4)`.
+pub(crate) const TEMPLATE: &str = "apply";
+
+/// The template, which may have no placeholders at all.
+pub(crate) const TEMPLATE_MINIMUM_ARITY: usize = 1;
+
+impl Evaluator {
+  /// Evaluate a call to [`TEMPLATE`].
+  pub(crate) fn template(&mut self, arguments: &[Expr]) -> Result<Value, CalcError> {
+    let (template, values) = template_arguments(arguments)?;
+    let mut numbers = Vec::with_capacity(values.len());
+    for value in values {
+      match self.eval(value)?.classify() {
+        Value::List(_) | Value::Text(_) | Value::Quantity(..) => {
+          return Err(CalcError::Unsupported(
+            "only numbers can fill the placeholders of 'apply'".to_string(),
+          ))
+        }
+        Value::Boolean(boolean) => numbers.push((boolean as u8).to_string()),
+        number => numbers.push(number.to_string()),
+      }
+    }
+    let filled = fill(template, &numbers, arguments.len())?;
+    let expr = self.parse(&filled)?;
+    self.eval(&expr)
+  }
+}
+
+/// Split the arguments of [`TEMPLATE`] into the template and the values for its placeholders.
+pub(crate) fn template_arguments(arguments: &[Expr]) -> Result<(&str, &[Expr]), CalcError> {
+  match arguments {
+    [Expr::Text(template), values @ ..] => Ok((template, values)),
+    [] => Err(CalcError::NotEnoughArguments {
+      function: TEMPLATE.to_string(),
+      minimum: TEMPLATE_MINIMUM_ARITY,
+      found: 0,
+    }),
+    _ => Err(CalcError::Unsupported(format!(
+      "the first argument of '{}' must be text in quotes",
+      TEMPLATE
+    ))),
+  }
+}
+
+/// Replace each `%N` in the template with the `N`th number, in parentheses so it stays one
+/// operand. The highest placeholder has to match the number of values, so that none are missing
+/// or left over.
+fn fill(template: &str, numbers: &[String], found: usize) -> Result<String, CalcError> {
+  let mut filled = String::with_capacity(template.len());
+  let mut highest = 0;
+  let mut chars = template.chars().peekable();
+  while let Some(c) = chars.next() {
+    let digits: String = match c {
+      '%' => iter::from_fn(|| chars.next_if(char::is_ascii_digit)).collect(),
+      _ => String::new(),
+    };
+    if digits.is_empty() {
+      filled.push(c);
+      continue;
+    }
+    let placeholder: usize = digits.parse().unwrap_or(usize::MAX);
+    if placeholder == 0 {
+      return Err(CalcError::Unsupported(
+        "the placeholders of 'apply' count from %1".to_string(),
+      ));
+    }
+    highest = highest.max(placeholder);
+    if let Some(number) = numbers.get(placeholder - 1) {
+      filled.push_str(&format!("({})", number));
+    }
+  }
+  if highest != numbers.len() {
+    return Err(CalcError::ArityMismatch {
+      function: TEMPLATE.to_string(),
+      expected: highest.saturating_add(TEMPLATE_MINIMUM_ARITY),
+      found,
+    });
+  }
+  Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{evaluate, CalcError, Evaluator};
+
+  #[test]
+  fn test_template() {
+    assert_eq!(evaluate("apply(\"%1 * %2 + %3\", 2, 3, 4)"), Ok(10.0));
+    assert_eq!(evaluate("apply(\"%2 - %1\", 1, 10)"), Ok(9.0));
+    assert_eq!(evaluate("apply(\"%1^2 + %1\", -3)"), Ok(6.0));
+    assert_eq!(evaluate("apply(\"%1 / 2\", 1/4)"), Ok(0.125));
+    assert_eq!(evaluate("apply(\"7\")"), Ok(7.0));
+    assert_eq!(evaluate("1 + apply(\"%1%1\", 2 > 1)"), Ok(2.0));
+
+    let mut evaluator = Evaluator::new();
+    evaluator.evaluate("x = 5").unwrap();
+    assert_eq!(evaluator.evaluate("apply(\"x * %1\", x + 1)"), Ok(30.0));
+    assert_eq!(
+      evaluator.validate("apply(\"%1\", y)"),
+      Err(CalcError::UnknownVariable("y".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_template_errors() {
+    let mismatch = |expected, found| {
+      Err(CalcError::ArityMismatch {
+        function: "apply".to_string(),
+        expected,
+        found,
+      })
+    };
+    assert_eq!(evaluate("apply(\"%1 + %2\", 1)"), mismatch(3, 2));
+    assert_eq!(evaluate("apply(\"%1\", 1, 2)"), mismatch(2, 3));
+    assert_eq!(evaluate("apply(\"%1 + %3\", 1, 2)"), mismatch(4, 3));
+    assert_eq!(
+      evaluate("apply()"),
+      Err(CalcError::NotEnoughArguments {
+        function: "apply".to_string(),
+        minimum: 1,
+        found: 0
+      })
+    );
+    assert!(matches!(
+      evaluate("apply(\"%0\", 1)"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert!(matches!(
+      evaluate("apply(1, 2)"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert!(matches!(
+      evaluate("apply(\"%1\", [1, 2])"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert_eq!(
+      evaluate("apply(\"%1 +\", 1)"),
+      Err(CalcError::MissingOperand {
+        operator: "+".to_string(),
+        side: crate::OperandSide::Right
+      })
+    );
+  }
+}
@@ -0,0 +1,28 @@
+use crate::evaluate;
+
+/// How far from the expected value [`assert_close`] lets a result be.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum Tolerance {
+  /// Less than this far either way.
+  Absolute(f64),
+  /// Less than this fraction of the expected value either way.
+  Relative(f64),
+}
+
+/// Assert that an expression evaluates with the default configuration to within `tolerance` of
+/// `expected`, for results rounding errors keep from being exact.
+#[track_caller]
+pub(crate) fn assert_close(expression: &str, expected: f64, tolerance: Tolerance) {
+  let result = evaluate(expression).unwrap();
+  let limit = match tolerance {
+    Tolerance::Absolute(limit) => limit,
+    Tolerance::Relative(fraction) => fraction * expected.abs(),
+  };
+  assert!(
+    (result - expected).abs() < limit,
+    "{} is {}, not {}",
+    expression,
+    result,
+    expected
+  );
+}
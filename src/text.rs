@@ -0,0 +1,162 @@
+use crate::ast::Expr;
+use crate::error::CalcError;
+use crate::evaluator::Evaluator;
+use crate::value::Value;
+
+/// Functions of two arguments, at least one of which is text in quotes rather than a number:
+/// - `convert(amount, "USD_EUR")` multiplies the amount by a rate set with
+///   [`Evaluator::set_rate`].
+/// - `parse(base, "ff")` reads the digits as an integer in `base`, from 2 to 36.
+/// - `equivalent("a", "b")` takes text for both and reports [`Evaluator::equivalent`].
+/// - `debug("label", x)` takes the text first and returns `x`, noting `label = x` for
+///   [`Evaluator::take_annotations`].
+pub(crate) const TEXT_FUNCTIONS: &[&str] = &["convert", "parse", "equivalent", "debug"];
+
+/// Every text function takes the text and one other argument.
+pub(crate) const TEXT_FUNCTION_ARITY: usize = 2;
+
+/// The letters go up to `z`, for base 36.
+const MAX_BASE: i64 = 36;
+
+impl Evaluator {
+  /// Evaluate a call to one of the [`TEXT_FUNCTIONS`].
+  pub(crate) fn text_function(
+    &mut self,
+    name: &str,
+    arguments: &[Expr],
+  ) -> Result<Value, CalcError> {
+    let (argument, text) = text_arguments(name, arguments)?;
+    match name {
+      "convert" => self.convert(argument, text),
+      "parse" => self.parse_digits(argument, text),
+      "debug" => self.debug(text, argument),
+      "equivalent" => match argument {
+        Expr::Text(left) => Ok(Value::Boolean(self.equivalent(left, text)?)),
+        _ => Err(CalcError::Unsupported(
+          "both arguments of 'equivalent' must be text in quotes".to_string(),
+        )),
+      },
+      _ => Err(CalcError::UnknownFunction(name.to_string())),
+    }
+  }
+
+  /// The `label = value` notes of `debug` calls since the last time they were taken, oldest
+  /// first.
+  pub fn take_annotations(&mut self) -> Vec<String> {
+    std::mem::take(&mut self.annotations)
+  }
+
+  /// Evaluate `debug("label", x)`.
+  fn debug(&mut self, label: &str, argument: &Expr) -> Result<Value, CalcError> {
+    let value = self.eval(argument)?;
+    self
+      .annotations
+      .push(format!("{} = {}", label, value.clone().classify()));
+    Ok(value)
+  }
+
+  /// Evaluate `parse(base, "digits")`.
+  fn parse_digits(&mut self, base: &Expr, digits: &str) -> Result<Value, CalcError> {
+    let base = match self.eval(base)?.classify() {
+      Value::Integer(base) if (2..=MAX_BASE).contains(&base) => base as u32,
+      other => {
+        return Err(CalcError::DomainError {
+          function: "parse".to_string(),
+          argument: other.to_f64(),
+        })
+      }
+    };
+    let number = i64::from_str_radix(digits, base).map_err(|_| CalcError::InvalidDigits {
+      digits: digits.to_string(),
+      base,
+    })?;
+    Ok(self.number(number as f64))
+  }
+}
+
+/// Split the arguments of a text function into the number and the text.
+pub(crate) fn text_arguments<'a>(
+  name: &str,
+  arguments: &'a [Expr],
+) -> Result<(&'a Expr, &'a str), CalcError> {
+  let text_first = name == "debug";
+  let ((true, [text, argument]) | (false, [argument, text])) = (text_first, arguments) else {
+    return Err(CalcError::ArityMismatch {
+      function: name.to_string(),
+      expected: TEXT_FUNCTION_ARITY,
+      found: arguments.len(),
+    });
+  };
+  let Expr::Text(text) = text else {
+    return Err(CalcError::Unsupported(format!(
+      "the {} argument of '{}' must be text in quotes",
+      if text_first { "first" } else { "second" },
+      name
+    )));
+  };
+  Ok((argument, text))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{evaluate, CalcError, Evaluator};
+
+  #[test]
+  fn test_parse() {
+    assert_eq!(evaluate("parse(16, \"ff\")"), Ok(255.0));
+    assert_eq!(evaluate("parse(16, \"FF\")"), Ok(255.0));
+    assert_eq!(evaluate("parse(2, \"1010\")"), Ok(10.0));
+    assert_eq!(evaluate("parse(8, \"-17\")"), Ok(-15.0));
+    assert_eq!(evaluate("parse(36, \"zz\")"), Ok(1295.0));
+    assert_eq!(evaluate("parse(10, \"42\") + 1"), Ok(43.0));
+  }
+
+  #[test]
+  fn test_debug() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+      evaluator.evaluate("2 * debug(\"sum\", 1 + 2) + debug(\"half\", 1 / 2)"),
+      Ok(6.5)
+    );
+    assert_eq!(
+      evaluator.take_annotations(),
+      ["sum = 3".to_string(), "half = 0.5".to_string()]
+    );
+    assert!(evaluator.take_annotations().is_empty());
+    assert_eq!(evaluator.evaluate("debug(\"\", 2 > 1) + 1"), Ok(2.0));
+    assert_eq!(evaluator.take_annotations(), [" = true".to_string()]);
+    assert!(matches!(
+      evaluator.evaluate("debug(1, \"label\")"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert_eq!(
+      evaluator.validate("debug(\"x\", y)"),
+      Err(CalcError::UnknownVariable("y".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    for base in ["1", "37", "2.5"] {
+      assert!(matches!(
+        evaluate(&format!("parse({}, \"1\")", base)),
+        Err(CalcError::DomainError { .. })
+      ));
+    }
+    assert_eq!(
+      evaluate("parse(2, \"102\")"),
+      Err(CalcError::InvalidDigits {
+        digits: "102".to_string(),
+        base: 2
+      })
+    );
+    assert!(matches!(
+      evaluate("parse(2, \"\")"),
+      Err(CalcError::InvalidDigits { .. })
+    ));
+    assert!(matches!(
+      evaluate("parse(2, 101)"),
+      Err(CalcError::Unsupported(_))
+    ));
+  }
+}
@@ -0,0 +1,1169 @@
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::error::CalcError;
+use crate::units::BASE_UNITS;
+
+/// A piece of an expression, as produced by [`tokenize`](crate::tokenize) and reordered into
+/// reverse polish notation by [`to_rpn`](crate::to_rpn).
+#[derive(Clone, PartialEq, Debug)]
+pub enum Token {
+  Number(f64),
+  Operator(Operator),
+  Parenthesis(Parenthesis),
+  /// One side of a `⌊x⌋` or `⌈x⌉` pair, which groups like parentheses and rounds its contents, of
+  /// a `[1, 2]` list, or of the `[1]` indexing into a list.
+  Bracket(Bracket, Parenthesis),
+  Constant(String),
+  Function(String),
+  Variable(String),
+  /// One of the base units `m`, `s` or `kg`, when units are enabled.
+  Unit(String),
+  /// Text in double quotes, like the `"USD_EUR"` naming a rate for `convert`. Inside the quotes
+  /// `\"` stands for a quote and `\\` for a backslash.
+  Text(String),
+  /// The `,` between function arguments.
+  Separator,
+  /// A function call in reverse polish notation, taking its arguments from the stack.
+  Call(String, usize),
+  /// A list in reverse polish notation, made of the given number of items from the stack.
+  List(usize),
+  /// Indexing in reverse polish notation, taking the list and then the index from the stack.
+  Index,
+  /// `=`, or a compound assignment like `+=` carrying the operator it combines with.
+  Assign(Option<Operator>),
+}
+
+impl Token {
+  /// A JSON object with the token's type and value, like `{"type":"number","value":2}`.
+  pub(crate) fn to_json(&self) -> String {
+    // Identifiers only ever contain ASCII letters, digits and underscores, so only text needs
+    // escaping.
+    let (kind, value) = match self {
+      Token::Number(number) => ("number", number.to_string()),
+      Token::Operator(operator) => ("operator", format!("\"{}\"", operator.symbol())),
+      Token::Parenthesis(Parenthesis::Left) => ("parenthesis", "\"(\"".to_string()),
+      Token::Parenthesis(Parenthesis::Right) => ("parenthesis", "\")\"".to_string()),
+      Token::Bracket(bracket, side) => ("parenthesis", format!("\"{}\"", bracket.symbol(*side))),
+      Token::Constant(name) => ("constant", format!("\"{}\"", name)),
+      Token::Function(name) | Token::Call(name, _) => ("function", format!("\"{}\"", name)),
+      Token::List(count) => ("list", count.to_string()),
+      Token::Index => ("index", "\"[]\"".to_string()),
+      Token::Variable(name) => ("variable", format!("\"{}\"", name)),
+      Token::Text(text) => ("text", format!("{:?}", text)),
+      Token::Unit(unit) => ("unit", format!("\"{}\"", unit)),
+      Token::Separator => ("separator", "\",\"".to_string()),
+      Token::Assign(operator) => (
+        "assignment",
+        format!("\"{}=\"", operator.map_or("", |operator| operator.symbol())),
+      ),
+    };
+    format!("{{\"type\":\"{}\",\"value\":{}}}", kind, value)
+  }
+}
+
+/// A JSON array of the tokens, in order.
+pub(crate) fn tokens_to_json(tokens: &[Token]) -> String {
+  let tokens: Vec<String> = tokens.iter().map(Token::to_json).collect();
+  format!("[{}]", tokens.join(","))
+}
+
+/// How deeply parentheses and brackets nest at most, failing if they don't pair up.
+pub(crate) fn nesting_depth(tokens: &[Token]) -> Result<usize, CalcError> {
+  let mut open = Vec::new();
+  let mut depth = 0;
+  for token in tokens {
+    let (kind, side) = match token {
+      Token::Parenthesis(side) => (None, *side),
+      Token::Bracket(bracket, side) => (Some(*bracket), *side),
+      _ => continue,
+    };
+    match side {
+      Parenthesis::Left => open.push(kind),
+      Parenthesis::Right if open.pop() != Some(kind) => {
+        return Err(CalcError::MismatchedParentheses)
+      }
+      Parenthesis::Right => {}
+    }
+    depth = depth.max(open.len());
+  }
+  if open.is_empty() {
+    Ok(depth)
+  } else {
+    Err(CalcError::MismatchedParentheses)
+  }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Operator {
+  Add,
+  Subtract,
+  Multiply,
+  Divide,
+  /// `//`, the floor of the quotient.
+  FloorDivide,
+  Power,
+  /// Unary minus, binding tighter than everything but `^` so that `-2^2` is `-4`.
+  Negate,
+  /// `~`, bitwise NOT of an integer in two's complement, binding like unary minus.
+  BitNot,
+  /// Comparisons like `==` and `<`, binding loosest of all operators. They give `true` or
+  /// `false`, which count as `1` and `0`.
+  Equal,
+  NotEqual,
+  Less,
+  LessEqual,
+  Greater,
+  GreaterEqual,
+}
+
+/// What there is to know about an operator for documenting it, from [`Operator::all`].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct OperatorInfo {
+  pub operator: Operator,
+  pub symbol: &'static str,
+  /// Higher binds tighter: `0` for comparisons up to `3` for `^` and the unary operators.
+  pub precedence: i32,
+  pub associativity: Associativity,
+  /// `1` for the unary operators, which are written before their operand, and `2` for the rest.
+  pub arity: usize,
+}
+
+impl OperatorInfo {
+  const fn of(operator: Operator) -> OperatorInfo {
+    OperatorInfo {
+      operator,
+      symbol: operator.symbol(),
+      precedence: operator.precedence(),
+      associativity: operator.associativity(),
+      arity: operator.arity(),
+    }
+  }
+}
+
+/// Every operator, from the loosest binding to the tightest.
+const OPERATORS: [OperatorInfo; 14] = [
+  OperatorInfo::of(Operator::Equal),
+  OperatorInfo::of(Operator::NotEqual),
+  OperatorInfo::of(Operator::Less),
+  OperatorInfo::of(Operator::LessEqual),
+  OperatorInfo::of(Operator::Greater),
+  OperatorInfo::of(Operator::GreaterEqual),
+  OperatorInfo::of(Operator::Add),
+  OperatorInfo::of(Operator::Subtract),
+  OperatorInfo::of(Operator::Multiply),
+  OperatorInfo::of(Operator::Divide),
+  OperatorInfo::of(Operator::FloorDivide),
+  OperatorInfo::of(Operator::Power),
+  OperatorInfo::of(Operator::Negate),
+  OperatorInfo::of(Operator::BitNot),
+];
+
+impl Operator {
+  /// The symbol, precedence, associativity and arity of every operator, from the loosest binding
+  /// to the tightest.
+  pub fn all() -> &'static [OperatorInfo] {
+    &OPERATORS
+  }
+
+  /// [Order of operations](https://en.wikipedia.org/wiki/Order_of_operations)
+  pub(crate) const fn precedence(&self) -> i32 {
+    match self {
+      Operator::Equal
+      | Operator::NotEqual
+      | Operator::Less
+      | Operator::LessEqual
+      | Operator::Greater
+      | Operator::GreaterEqual => 0,
+      Operator::Add | Operator::Subtract => 1,
+      Operator::Multiply | Operator::Divide | Operator::FloorDivide => 2,
+      Operator::Power | Operator::Negate | Operator::BitNot => 3,
+    }
+  }
+
+  /// [Operator associativity](https://en.wikipedia.org/wiki/Operator_associativity)
+  pub(crate) const fn associativity(&self) -> Associativity {
+    match self {
+      Operator::Add
+      | Operator::Subtract
+      | Operator::Multiply
+      | Operator::Divide
+      | Operator::FloorDivide
+      | Operator::Equal
+      | Operator::NotEqual
+      | Operator::Less
+      | Operator::LessEqual
+      | Operator::Greater
+      | Operator::GreaterEqual => Associativity::Left,
+      Operator::Power | Operator::Negate | Operator::BitNot => Associativity::Right,
+    }
+  }
+
+  pub(crate) const fn symbol(&self) -> &'static str {
+    match self {
+      Operator::Add => "+",
+      Operator::Subtract => "-",
+      Operator::Multiply => "*",
+      Operator::Divide => "/",
+      Operator::FloorDivide => "//",
+      Operator::Power => "^",
+      Operator::Negate => "-",
+      Operator::BitNot => "~",
+      Operator::Equal => "==",
+      Operator::NotEqual => "!=",
+      Operator::Less => "<",
+      Operator::LessEqual => "<=",
+      Operator::Greater => ">",
+      Operator::GreaterEqual => ">=",
+    }
+  }
+
+  pub(crate) fn is_comparison(&self) -> bool {
+    self.precedence() == 0
+  }
+
+  /// The number of operands the operator takes. Unary operators are always prefix operators.
+  pub(crate) const fn arity(&self) -> usize {
+    match self {
+      Operator::Negate | Operator::BitNot => 1,
+      _ => 2,
+    }
+  }
+}
+
+/// Which side of a pair of parentheses or brackets a token is.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Parenthesis {
+  Left,
+  Right,
+}
+
+/// Brackets other than parentheses: ones that apply a rounding function to what they enclose,
+/// the square brackets of a list, and the square brackets picking an item out of the list in
+/// front of them, which directly follow a variable or a closing parenthesis or bracket.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Bracket {
+  Floor,
+  Ceiling,
+  List,
+  Index,
+}
+
+impl Bracket {
+  /// The builtin applied when the bracket closes, if any.
+  pub(crate) fn function(&self) -> Option<&'static str> {
+    match self {
+      Bracket::Floor => Some("floor"),
+      Bracket::Ceiling => Some("ceil"),
+      Bracket::List | Bracket::Index => None,
+    }
+  }
+
+  pub(crate) fn symbol(&self, side: Parenthesis) -> char {
+    match (self, side) {
+      (Bracket::Floor, Parenthesis::Left) => '⌊',
+      (Bracket::Floor, Parenthesis::Right) => '⌋',
+      (Bracket::Ceiling, Parenthesis::Left) => '⌈',
+      (Bracket::Ceiling, Parenthesis::Right) => '⌉',
+      (Bracket::List | Bracket::Index, Parenthesis::Left) => '[',
+      (Bracket::List | Bracket::Index, Parenthesis::Right) => ']',
+    }
+  }
+}
+
+/// Which way a chain of operators of the same precedence groups: `1 - 2 - 3` is `(1 - 2) - 3`, but
+/// `2 ^ 3 ^ 2` is `2 ^ (3 ^ 2)`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Associativity {
+  Left,
+  Right,
+}
+
+impl Associativity {
+  pub(crate) fn is_left(&self) -> bool {
+    *self == Associativity::Left
+  }
+
+  pub(crate) fn is_right(&self) -> bool {
+    *self == Associativity::Right
+  }
+}
+
+/// The blank in a fill-in-the-blank equation like `2 * ? = 10`, read as a variable that only
+/// [`Evaluator::solve`](crate::Evaluator::solve) can give a value.
+pub(crate) const PLACEHOLDER: &str = "?";
+
+/// What the tokeniser needs to know beyond the expression itself.
+pub(crate) struct Syntax<'a> {
+  /// Whether an identifier names a constant rather than a variable.
+  pub(crate) is_constant: &'a dyn Fn(&str) -> bool,
+  /// Whether an identifier names a function, which then has to be followed by parentheses.
+  pub(crate) is_function: &'a dyn Fn(&str) -> bool,
+  /// Read `,` directly between digits as a thousands separator, so `1,234` is `1234`.
+  pub(crate) digit_grouping: bool,
+  /// Close parentheses and brackets still open at the end, so `2*(3+4` is `2*(3+4)`.
+  pub(crate) auto_close: bool,
+  /// Read the names of the base units as units rather than variables.
+  pub(crate) units: bool,
+}
+
+/// Blank out `/* ... */` comments, which may span several lines, with spaces. Everything else,
+/// including the line breaks inside comments, stays where it was, so columns in errors still match
+/// the original text.
+pub fn strip_comments(str: &str) -> Result<String, CalcError> {
+  let mut result = String::with_capacity(str.len());
+  let mut rest = str;
+  while let Some(start) = rest.find("/*") {
+    result.push_str(&rest[..start]);
+    let comment = &rest[start..];
+    let end =
+      comment[2..]
+        .find("*/")
+        .map(|end| end + 4)
+        .ok_or_else(|| CalcError::UnterminatedComment {
+          column: column(str, str.len() - comment.len()),
+          span: str.len() - comment.len()..str.len(),
+        })?;
+    result.extend(
+      comment[..end]
+        .chars()
+        .map(|c| if c == '\n' { c } else { ' ' }),
+    );
+    rest = &comment[end..];
+  }
+  result.push_str(rest);
+  Ok(result)
+}
+
+/// Split input at every `;` that separates statements, leaving the ones inside text in quotes,
+/// like `"a;b"`, and inside `/* ... */` comments alone. Quotes are read the way [`tokenise`]
+/// reads them, so `\"` doesn't end the text.
+pub(crate) fn split_statements(str: &str) -> Vec<&str> {
+  let mut statements = Vec::new();
+  let mut start = 0;
+  let mut in_text = false;
+  let mut chars = str.char_indices().peekable();
+  while let Some((i, c)) = chars.next() {
+    match c {
+      '/' if !in_text && chars.next_if(|&(_, next)| next == '*').is_some() => {
+        // The rest is one unterminated comment if it doesn't end, which tokenising reports.
+        while let Some((_, c)) = chars.next() {
+          if c == '*' && chars.next_if(|&(_, next)| next == '/').is_some() {
+            break;
+          }
+        }
+      }
+      '"' => in_text = !in_text,
+      '\\' if in_text => {
+        chars.next_if(|&(_, next)| matches!(next, '"' | '\\'));
+      }
+      ';' if !in_text => {
+        statements.push(&str[start..i]);
+        start = i + 1;
+      }
+      _ => {}
+    }
+  }
+  statements.push(&str[start..]);
+  statements
+}
+
+/// Replace every identifier that is a key of `aliases` by its expansion. Only whole identifiers
+/// as the tokeniser reads them match, so an alias `k` applies to `5k` but not to `kg` or `x1k`.
+/// Expansions aren't expanded again.
+pub(crate) fn expand_aliases(str: &str, aliases: &HashMap<String, String>) -> String {
+  let mut expanded = String::with_capacity(str.len());
+  let mut chars = str.chars().peekable();
+  while let Some(c) = chars.next() {
+    if !(c.is_ascii_alphabetic() || c == '_') {
+      expanded.push(c);
+      continue;
+    }
+    let mut identifier = c.to_string();
+    while let Some(next) = chars.next_if(|next| next.is_ascii_alphanumeric() || *next == '_') {
+      identifier.push(next);
+    }
+    expanded.push_str(aliases.get(&identifier).unwrap_or(&identifier));
+  }
+  expanded
+}
+
+/// Tokenise a string into a vector of tokens.
+pub(crate) fn tokenise(str: &str, syntax: &Syntax) -> Result<Vec<Token>, CalcError> {
+  let mut tokens = Vec::new();
+  let mut number_buffer = NumberBuffer::new(str);
+  // The kind of each open square bracket, to close it with the same kind.
+  let mut squares = Vec::new();
+
+  let mut chars = str.char_indices().peekable();
+  while let Some((offset, c)) = chars.next() {
+    match c {
+      '.' if number_buffer.digits.contains('.') => {
+        let rest = chars.clone().map(|(_, c)| c);
+        let number: String = number_buffer
+          .digits
+          .chars()
+          .chain(Some(c))
+          .chain(rest.take_while(|&c| c.is_ascii_digit() || c == '.'))
+          .collect();
+        return Err(CalcError::InvalidNumber {
+          column: column(str, offset),
+          span: number_buffer.start..number_buffer.start + number.len(),
+          number,
+        });
+      }
+      '0'..='9' | '.' => number_buffer.push(offset, c),
+      ','
+        if syntax.digit_grouping
+          && !number_buffer.digits.is_empty()
+          && chars.peek().is_some_and(|(_, next)| next.is_ascii_digit()) =>
+      {
+        number_buffer.push(offset, c)
+      }
+      'a'..='z' | 'A'..='Z' | '_' => {
+        let mut identifier = c.to_string();
+        while let Some((_, next)) =
+          chars.next_if(|(_, next)| next.is_ascii_alphanumeric() || *next == '_')
+        {
+          identifier.push(next);
+        }
+        let called = chars.clone().map(|(_, c)| c).find(|c| !c.is_whitespace()) == Some('(');
+        push_non_number(
+          &mut tokens,
+          &mut number_buffer,
+          classify_identifier(identifier, called, syntax),
+        )?
+      }
+      '+' => push_non_number(
+        &mut tokens,
+        &mut number_buffer,
+        operator_or_assign(&mut chars, Operator::Add),
+      )?,
+      '-' => {
+        empty_number_buffer(&mut tokens, &mut number_buffer)?;
+        if expects_operand(&tokens) {
+          tokens.push(Token::Operator(Operator::Negate));
+        } else {
+          tokens.push(operator_or_assign(&mut chars, Operator::Subtract));
+        }
+      }
+      '~' => push_non_number(
+        &mut tokens,
+        &mut number_buffer,
+        Token::Operator(Operator::BitNot),
+      )?,
+      '*' => push_non_number(
+        &mut tokens,
+        &mut number_buffer,
+        operator_or_assign(&mut chars, Operator::Multiply),
+      )?,
+      '/' if chars.next_if(|(_, next)| *next == '/').is_some() => push_non_number(
+        &mut tokens,
+        &mut number_buffer,
+        operator_or_assign(&mut chars, Operator::FloorDivide),
+      )?,
+      '/' | ':' => push_non_number(
+        &mut tokens,
+        &mut number_buffer,
+        operator_or_assign(&mut chars, Operator::Divide),
+      )?,
+      ',' => push_non_number(&mut tokens, &mut number_buffer, Token::Separator)?,
+      '?' => push_non_number(
+        &mut tokens,
+        &mut number_buffer,
+        Token::Variable(PLACEHOLDER.to_string()),
+      )?,
+      '"' => {
+        let mut text = String::new();
+        loop {
+          match chars.next() {
+            Some((_, '"')) => break,
+            Some((_, '\\'))
+              if chars
+                .peek()
+                .is_some_and(|(_, next)| matches!(next, '"' | '\\')) =>
+            {
+              text.extend(chars.next().map(|(_, c)| c))
+            }
+            Some((_, c)) => text.push(c),
+            None => {
+              return Err(CalcError::UnterminatedText {
+                column: column(str, offset),
+                span: offset..str.len(),
+              })
+            }
+          }
+        }
+        push_non_number(&mut tokens, &mut number_buffer, Token::Text(text))?
+      }
+      '=' if chars.next_if(|(_, next)| *next == '=').is_some() => push_non_number(
+        &mut tokens,
+        &mut number_buffer,
+        Token::Operator(Operator::Equal),
+      )?,
+      '=' => push_non_number(&mut tokens, &mut number_buffer, Token::Assign(None))?,
+      '!' if chars.next_if(|(_, next)| *next == '=').is_some() => push_non_number(
+        &mut tokens,
+        &mut number_buffer,
+        Token::Operator(Operator::NotEqual),
+      )?,
+      '<' | '>' => {
+        let or_equal = chars.next_if(|(_, next)| *next == '=').is_some();
+        let operator = match (c, or_equal) {
+          ('<', false) => Operator::Less,
+          ('<', true) => Operator::LessEqual,
+          (_, false) => Operator::Greater,
+          (_, true) => Operator::GreaterEqual,
+        };
+        push_non_number(&mut tokens, &mut number_buffer, Token::Operator(operator))?
+      }
+      '⁻' | '⁰' | '¹' | '²' | '³' | '⁴'..='⁹' => {
+        // A run of superscript digits like `2¹⁰` is an exponent on whatever precedes it.
+        push_non_number(
+          &mut tokens,
+          &mut number_buffer,
+          Token::Operator(Operator::Power),
+        )?;
+        let mut superscript = c.to_string();
+        while let Some((_, next)) = chars.next_if(|(_, next)| superscript_digit(*next).is_some()) {
+          superscript.push(next);
+        }
+        let exponent = superscript.strip_prefix('⁻').unwrap_or(&superscript);
+        if exponent.is_empty() {
+          return Err(CalcError::InvalidNumber {
+            column: column(str, offset),
+            span: offset..offset + superscript.len(),
+            number: superscript,
+          });
+        }
+        if superscript.starts_with('⁻') {
+          tokens.push(Token::Operator(Operator::Negate));
+        }
+        let exponent = exponent
+          .chars()
+          .filter_map(superscript_digit)
+          .fold(0.0, |exponent, digit| exponent * 10.0 + digit as f64);
+        tokens.push(Token::Number(exponent));
+      }
+      '^' => push_non_number(
+        &mut tokens,
+        &mut number_buffer,
+        Token::Operator(Operator::Power),
+      )?,
+      '(' => push_non_number(
+        &mut tokens,
+        &mut number_buffer,
+        Token::Parenthesis(Parenthesis::Left),
+      )?,
+      ')' => push_non_number(
+        &mut tokens,
+        &mut number_buffer,
+        Token::Parenthesis(Parenthesis::Right),
+      )?,
+      '⌊' | '⌋' | '⌈' | '⌉' | '[' | ']' => {
+        let bracket = match c {
+          '⌊' | '⌋' => Bracket::Floor,
+          '⌈' | '⌉' => Bracket::Ceiling,
+          '[' => {
+            let index = number_buffer.digits.is_empty()
+              && matches!(
+                tokens.last(),
+                Some(Token::Variable(_))
+                  | Some(Token::Parenthesis(Parenthesis::Right))
+                  | Some(Token::Bracket(_, Parenthesis::Right))
+              );
+            let bracket = if index { Bracket::Index } else { Bracket::List };
+            squares.push(bracket);
+            bracket
+          }
+          _ => squares.pop().unwrap_or(Bracket::List),
+        };
+        let side = match c {
+          '⌊' | '⌈' | '[' => Parenthesis::Left,
+          _ => Parenthesis::Right,
+        };
+        push_non_number(
+          &mut tokens,
+          &mut number_buffer,
+          Token::Bracket(bracket, side),
+        )?
+      }
+      _ => (),
+    }
+  }
+
+  empty_number_buffer(&mut tokens, &mut number_buffer)?;
+  if syntax.auto_close {
+    close_unmatched(&mut tokens);
+  }
+
+  if syntax.units {
+    tokens = group_quantities(tokens);
+  }
+  multiply_implicitly(tokens, syntax.is_function)
+}
+
+/// Put a number directly followed by a unit into parentheses along with the unit's power, so that
+/// `3 m / 2 s^2` divides by `2 s^2` as a whole: `(3*m) / (2*s^2)`.
+fn group_quantities(tokens: Vec<Token>) -> Vec<Token> {
+  let mut result = Vec::with_capacity(tokens.len());
+  let mut tokens = tokens.into_iter().peekable();
+  while let Some(token) = tokens.next() {
+    let Token::Number(_) = token else {
+      result.push(token);
+      continue;
+    };
+    let Some(unit) = tokens.next_if(|next| matches!(next, Token::Unit(_))) else {
+      result.push(token);
+      continue;
+    };
+    result.extend([
+      Token::Parenthesis(Parenthesis::Left),
+      token,
+      Token::Operator(Operator::Multiply),
+      unit,
+    ]);
+    if let Some(power) = tokens.next_if_eq(&Token::Operator(Operator::Power)) {
+      result.push(power);
+      result.extend(tokens.next_if_eq(&Token::Operator(Operator::Negate)));
+      result.extend(tokens.next_if(|next| matches!(next, Token::Number(_))));
+    }
+    result.push(Token::Parenthesis(Parenthesis::Right));
+  }
+  result
+}
+
+/// Append whatever closes the parentheses and brackets left open, innermost first. Mismatched
+/// closing ones are left for the parser to report.
+fn close_unmatched(tokens: &mut Vec<Token>) {
+  let mut open = Vec::new();
+  for token in tokens.iter() {
+    match token {
+      Token::Parenthesis(Parenthesis::Left) => open.push(Token::Parenthesis(Parenthesis::Right)),
+      Token::Bracket(bracket, Parenthesis::Left) => {
+        open.push(Token::Bracket(*bracket, Parenthesis::Right))
+      }
+      Token::Parenthesis(Parenthesis::Right) | Token::Bracket(_, Parenthesis::Right) => {
+        open.pop();
+      }
+      _ => {}
+    }
+  }
+  tokens.extend(open.into_iter().rev());
+}
+
+/// Insert the `*` left out between an operand and whatever starts the next one, as in `2(3+4)`,
+/// `2pi` or `(1+2)(3+4)`. A function name in front of an operand is a call missing its
+/// parentheses rather than a product.
+fn multiply_implicitly(
+  tokens: Vec<Token>,
+  is_function: &dyn Fn(&str) -> bool,
+) -> Result<Vec<Token>, CalcError> {
+  let mut result: Vec<Token> = Vec::with_capacity(tokens.len());
+  for token in tokens {
+    let ends_operand = matches!(
+      result.last(),
+      Some(Token::Number(_))
+        | Some(Token::Constant(_))
+        | Some(Token::Variable(_))
+        | Some(Token::Unit(_))
+        | Some(Token::Parenthesis(Parenthesis::Right))
+        | Some(Token::Bracket(_, Parenthesis::Right))
+    );
+    let starts_operand = matches!(
+      token,
+      Token::Number(_)
+        | Token::Constant(_)
+        | Token::Variable(_)
+        | Token::Unit(_)
+        | Token::Function(_)
+        | Token::Parenthesis(Parenthesis::Left)
+        | Token::Bracket(
+          Bracket::Floor | Bracket::Ceiling | Bracket::List,
+          Parenthesis::Left
+        )
+    );
+    if ends_operand && starts_operand {
+      if let Some(Token::Variable(name)) = result.last() {
+        if is_function(name) {
+          return Err(CalcError::MissingFunctionParens {
+            function: name.clone(),
+            argument: match token {
+              Token::Number(number) => number.to_string(),
+              Token::Constant(name) | Token::Variable(name) => name,
+              _ => "...".to_string(),
+            },
+          });
+        }
+      }
+      result.push(Token::Operator(Operator::Multiply));
+    }
+    result.push(token);
+  }
+  Ok(result)
+}
+
+/// The value of a superscript digit like `²`.
+fn superscript_digit(c: char) -> Option<u32> {
+  match c {
+    '⁰' => Some(0),
+    '¹' => Some(1),
+    '²' => Some(2),
+    '³' => Some(3),
+    '⁴'..='⁹' => Some(c as u32 - '⁴' as u32 + 4),
+    _ => None,
+  }
+}
+
+/// Turn an operator into its compound assignment if it is directly followed by `=`.
+fn operator_or_assign(chars: &mut Peekable<CharIndices>, operator: Operator) -> Token {
+  if chars.next_if(|(_, next)| *next == '=').is_some() {
+    Token::Assign(Some(operator))
+  } else {
+    Token::Operator(operator)
+  }
+}
+
+/// Whether the next token has to start an operand, which makes a `-` a unary minus.
+fn expects_operand(tokens: &[Token]) -> bool {
+  matches!(
+    tokens.last(),
+    None
+      | Some(Token::Operator(_))
+      | Some(Token::Assign(_))
+      | Some(Token::Separator)
+      | Some(Token::Parenthesis(Parenthesis::Left))
+      | Some(Token::Bracket(_, Parenthesis::Left))
+  )
+}
+
+/// Decide what an identifier refers to: a function if it is being called, then a constant, and a
+/// variable otherwise.
+fn classify_identifier(identifier: String, called: bool, syntax: &Syntax) -> Token {
+  if called {
+    Token::Function(identifier)
+  } else if syntax.units && BASE_UNITS.contains(&identifier.as_str()) {
+    Token::Unit(identifier)
+  } else if (syntax.is_constant)(&identifier) {
+    Token::Constant(identifier)
+  } else {
+    Token::Variable(identifier)
+  }
+}
+
+/// The 1-based column of the character at byte `offset`.
+fn column(str: &str, offset: usize) -> usize {
+  str[..offset].chars().count() + 1
+}
+
+/// The number literal being read, remembering where it started for error messages.
+struct NumberBuffer<'a> {
+  source: &'a str,
+  digits: String,
+  start: usize,
+}
+
+impl NumberBuffer<'_> {
+  fn new(source: &str) -> NumberBuffer<'_> {
+    NumberBuffer {
+      source,
+      digits: String::new(),
+      start: 0,
+    }
+  }
+
+  fn push(&mut self, offset: usize, c: char) {
+    if self.digits.is_empty() {
+      self.start = offset;
+    }
+    self.digits.push(c);
+  }
+
+  fn invalid(&self) -> CalcError {
+    CalcError::InvalidNumber {
+      number: self.digits.clone(),
+      column: column(self.source, self.start),
+      span: self.start..self.start + self.digits.len(),
+    }
+  }
+}
+
+/// Push a non-number token onto the token vector and clear the number buffer in one go.
+fn push_non_number(
+  tokens: &mut Vec<Token>,
+  number_buffer: &mut NumberBuffer,
+  token: Token,
+) -> Result<(), CalcError> {
+  assert!(!matches!(token, Token::Number(_)));
+  empty_number_buffer(tokens, number_buffer)?;
+  tokens.push(token);
+  Ok(())
+}
+
+fn empty_number_buffer(
+  tokens: &mut Vec<Token>,
+  number_buffer: &mut NumberBuffer,
+) -> Result<(), CalcError> {
+  if !number_buffer.digits.is_empty() {
+    let digits = if number_buffer.digits.contains(',') {
+      ungroup(&number_buffer.digits)?
+    } else {
+      number_buffer.digits.clone()
+    };
+    let number = digits.parse().map_err(|_| number_buffer.invalid())?;
+    tokens.push(Token::Number(number));
+    number_buffer.digits.clear();
+  }
+  Ok(())
+}
+
+/// Strip the thousands separators from a number, making sure they split its integer part into
+/// groups of three digits.
+fn ungroup(number: &str) -> Result<String, CalcError> {
+  let integer = number.split('.').next().unwrap_or_default();
+  let mut groups = integer.split(',');
+  let leading = groups.next().unwrap_or_default().len();
+  if !(1..=3).contains(&leading)
+    || !groups.all(|group| group.len() == 3)
+    || number[integer.len()..].contains(',')
+  {
+    return Err(CalcError::InvalidGrouping(number.to_string()));
+  }
+  Ok(number.replace(',', ""))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::constants::builtin_constant;
+
+  fn tokenise(str: &str) -> Result<Vec<Token>, CalcError> {
+    super::tokenise(
+      str,
+      &Syntax {
+        is_constant: &|name| builtin_constant(name).is_some(),
+        is_function: &|_| false,
+        digit_grouping: false,
+        auto_close: false,
+        units: false,
+      },
+    )
+  }
+
+  fn tokenise_grouped(str: &str) -> Result<Vec<Token>, CalcError> {
+    super::tokenise(
+      str,
+      &Syntax {
+        is_constant: &|name| builtin_constant(name).is_some(),
+        is_function: &|_| false,
+        digit_grouping: true,
+        auto_close: false,
+        units: false,
+      },
+    )
+  }
+
+  #[test]
+  fn test_operator_info() {
+    let info = |symbol: &str, arity| {
+      *Operator::all()
+        .iter()
+        .find(|info| info.symbol == symbol && info.arity == arity)
+        .unwrap()
+    };
+    assert_eq!(
+      info("^", 2),
+      OperatorInfo {
+        operator: Operator::Power,
+        symbol: "^",
+        precedence: 3,
+        associativity: Associativity::Right,
+        arity: 2
+      }
+    );
+    assert_eq!(info("-", 1).operator, Operator::Negate);
+    assert_eq!(info("-", 2).associativity, Associativity::Left);
+    assert_eq!(info("==", 2).precedence, 0);
+    assert_eq!(Operator::all().len(), 14);
+    assert!(Operator::all()
+      .windows(2)
+      .all(|pair| pair[0].precedence <= pair[1].precedence));
+  }
+
+  #[test]
+  fn test_block_comments() {
+    assert_eq!(
+      strip_comments("1 /* one */ + 2"),
+      Ok("1           + 2".to_string())
+    );
+    assert_eq!(
+      strip_comments("1 + /* first\nsecond */ 2 /**/"),
+      Ok("1 +         \n          2     ".to_string())
+    );
+    assert_eq!(
+      tokenise(&strip_comments("2 /* * / */ * 3").unwrap()),
+      tokenise("2 * 3")
+    );
+    assert_eq!(
+      strip_comments("1 /* open"),
+      Err(CalcError::UnterminatedComment {
+        column: 3,
+        span: 2..9
+      })
+    );
+    assert_eq!(
+      strip_comments("/* a */ 1 /* b"),
+      Err(CalcError::UnterminatedComment {
+        column: 11,
+        span: 10..14
+      })
+    );
+  }
+
+  #[test]
+  fn test_text() {
+    assert_eq!(
+      tokenise("convert(1, \"USD_EUR\")").unwrap()[4],
+      Token::Text("USD_EUR".to_string())
+    );
+    assert_eq!(
+      tokenise(r#""say \"hi\" \\ \n""#),
+      Ok(vec![Token::Text(r#"say "hi" \ \n"#.to_string())])
+    );
+    assert_eq!(tokenise("\"\""), Ok(vec![Token::Text(String::new())]));
+    assert_eq!(
+      tokenise("1 + \"open"),
+      Err(CalcError::UnterminatedText {
+        column: 5,
+        span: 4..9
+      })
+    );
+    assert_eq!(
+      tokenise(r#""\""#),
+      Err(CalcError::UnterminatedText {
+        column: 1,
+        span: 0..3
+      })
+    );
+  }
+
+  #[test]
+  fn test_identifiers() {
+    assert_eq!(
+      tokenise("x1+1"),
+      Ok(vec![
+        Token::Variable("x1".to_string()),
+        Token::Operator(Operator::Add),
+        Token::Number(1.0),
+      ])
+    );
+    assert_eq!(
+      tokenise("total_sum*_temp2"),
+      Ok(vec![
+        Token::Variable("total_sum".to_string()),
+        Token::Operator(Operator::Multiply),
+        Token::Variable("_temp2".to_string()),
+      ])
+    );
+  }
+
+  #[test]
+  fn test_unary_minus() {
+    assert_eq!(
+      tokenise("-2-(-3)"),
+      Ok(vec![
+        Token::Operator(Operator::Negate),
+        Token::Number(2.0),
+        Token::Operator(Operator::Subtract),
+        Token::Parenthesis(Parenthesis::Left),
+        Token::Operator(Operator::Negate),
+        Token::Number(3.0),
+        Token::Parenthesis(Parenthesis::Right),
+      ])
+    );
+  }
+
+  #[test]
+  fn test_assignment_operators() {
+    assert_eq!(
+      tokenise("a=b-=-1"),
+      Ok(vec![
+        Token::Variable("a".to_string()),
+        Token::Assign(None),
+        Token::Variable("b".to_string()),
+        Token::Assign(Some(Operator::Subtract)),
+        Token::Operator(Operator::Negate),
+        Token::Number(1.0),
+      ])
+    );
+  }
+
+  #[test]
+  fn test_floor_division() {
+    assert_eq!(
+      tokenise("7//2/1//=3"),
+      Ok(vec![
+        Token::Number(7.0),
+        Token::Operator(Operator::FloorDivide),
+        Token::Number(2.0),
+        Token::Operator(Operator::Divide),
+        Token::Number(1.0),
+        Token::Assign(Some(Operator::FloorDivide)),
+        Token::Number(3.0),
+      ])
+    );
+  }
+
+  #[test]
+  fn test_identifier_classification() {
+    assert_eq!(
+      tokenise("2*pi"),
+      Ok(vec![
+        Token::Number(2.0),
+        Token::Operator(Operator::Multiply),
+        Token::Constant("pi".to_string()),
+      ])
+    );
+    assert_eq!(
+      tokenise("f (e)"),
+      Ok(vec![
+        Token::Function("f".to_string()),
+        Token::Parenthesis(Parenthesis::Left),
+        Token::Constant("e".to_string()),
+        Token::Parenthesis(Parenthesis::Right),
+      ])
+    );
+  }
+
+  #[test]
+  fn test_json() {
+    assert_eq!(
+      tokens_to_json(&tokenise("2+0.5").unwrap()),
+      r#"[{"type":"number","value":2},{"type":"operator","value":"+"},{"type":"number","value":0.5}]"#
+    );
+    assert_eq!(
+      tokens_to_json(&tokenise("x*=max(pi,-1)").unwrap()),
+      concat!(
+        r#"[{"type":"variable","value":"x"},{"type":"assignment","value":"*="},"#,
+        r#"{"type":"function","value":"max"},{"type":"parenthesis","value":"("},"#,
+        r#"{"type":"constant","value":"pi"},{"type":"separator","value":","},"#,
+        r#"{"type":"operator","value":"-"},{"type":"number","value":1},"#,
+        r#"{"type":"parenthesis","value":")"}]"#,
+      )
+    );
+    assert_eq!(tokens_to_json(&[]), "[]");
+  }
+
+  #[test]
+  fn test_digit_grouping() {
+    assert_eq!(
+      tokenise_grouped("1,234,567"),
+      Ok(vec![Token::Number(1234567.0)])
+    );
+    assert_eq!(
+      tokenise_grouped("12,345.5"),
+      Ok(vec![Token::Number(12345.5)])
+    );
+    assert_eq!(
+      tokenise_grouped("max(1,000, 2)"),
+      Ok(vec![
+        Token::Function("max".to_string()),
+        Token::Parenthesis(Parenthesis::Left),
+        Token::Number(1000.0),
+        Token::Separator,
+        Token::Number(2.0),
+        Token::Parenthesis(Parenthesis::Right),
+      ])
+    );
+    for invalid in ["1,23", "1234,567", "1,2345", "1.234,567"] {
+      assert_eq!(
+        tokenise_grouped(invalid),
+        Err(CalcError::InvalidGrouping(invalid.to_string()))
+      );
+    }
+    assert_eq!(
+      tokenise("1,234"),
+      Ok(vec![
+        Token::Number(1.0),
+        Token::Separator,
+        Token::Number(234.0)
+      ])
+    );
+  }
+
+  #[test]
+  fn test_superscript_exponents() {
+    assert_eq!(
+      tokenise("2¹⁰"),
+      Ok(vec![
+        Token::Number(2.0),
+        Token::Operator(Operator::Power),
+        Token::Number(10.0),
+      ])
+    );
+    assert_eq!(
+      tokenise("x⁻²"),
+      Ok(vec![
+        Token::Variable("x".to_string()),
+        Token::Operator(Operator::Power),
+        Token::Operator(Operator::Negate),
+        Token::Number(2.0),
+      ])
+    );
+    assert_eq!(
+      tokenise("2⁻"),
+      Err(CalcError::InvalidNumber {
+        number: "⁻".to_string(),
+        column: 2,
+        span: 1..4
+      })
+    );
+  }
+
+  #[test]
+  fn test_multiple_decimal_points() {
+    assert_eq!(
+      tokenise("1.2.3+1"),
+      Err(CalcError::InvalidNumber {
+        number: "1.2.3".to_string(),
+        column: 4,
+        span: 0..5
+      })
+    );
+    assert_eq!(
+      tokenise("²+ 10..5"),
+      Err(CalcError::InvalidNumber {
+        number: "10..5".to_string(),
+        column: 7,
+        span: 4..9
+      })
+    );
+    assert_eq!(
+      tokenise("2*."),
+      Err(CalcError::InvalidNumber {
+        number: ".".to_string(),
+        column: 3,
+        span: 2..3
+      })
+    );
+    assert_eq!(
+      tokenise("sqrt(1 + 2.5.1) * 2").map_err(|error| error.span()),
+      Err(Some(9..14))
+    );
+  }
+
+  #[test]
+  fn test_alias_expansion() {
+    let aliases = HashMap::from([
+      ("k".to_string(), "*1000".to_string()),
+      ("tau".to_string(), "(2*pi)".to_string()),
+    ]);
+    assert_eq!(expand_aliases("5k", &aliases), "5*1000");
+    assert_eq!(expand_aliases("2.5k + 1k", &aliases), "2.5*1000 + 1*1000");
+    assert_eq!(expand_aliases("tau/2", &aliases), "(2*pi)/2");
+    assert_eq!(expand_aliases("kg + x1k + k_2", &aliases), "kg + x1k + k_2");
+    assert_eq!(expand_aliases("sqrt(tau)", &aliases), "sqrt((2*pi))");
+    assert_eq!(expand_aliases("", &aliases), "");
+  }
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+
+use crate::error::CalcError;
+use crate::evaluator::Evaluator;
+use crate::token::Operator;
+use crate::value::Value;
+
+/// The units [`Config::units`](crate::Config::units) recognises, in the order they are printed.
+pub(crate) const BASE_UNITS: [&str; 3] = ["kg", "m", "s"];
+
+/// The exponent of every base unit in a quantity, so metres per second are `m^1 s^-1`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Dimension([i32; BASE_UNITS.len()]);
+
+impl Dimension {
+  /// The dimension of one of the [`BASE_UNITS`].
+  pub(crate) fn of(unit: &str) -> Option<Dimension> {
+    let index = BASE_UNITS.iter().position(|&base| base == unit)?;
+    let mut exponents = [0; BASE_UNITS.len()];
+    exponents[index] = 1;
+    Some(Dimension(exponents))
+  }
+
+  /// Whether this is the dimension of a plain number.
+  pub fn is_none(&self) -> bool {
+    self.0.iter().all(|&exponent| exponent == 0)
+  }
+
+  /// The dimension of a product, or with `sign` `-1` of a quotient.
+  fn combine(self, other: Dimension, sign: i32) -> Dimension {
+    let mut exponents = self.0;
+    for (exponent, other) in exponents.iter_mut().zip(other.0) {
+      *exponent += sign * other;
+    }
+    Dimension(exponents)
+  }
+
+  fn power(self, power: i32) -> Dimension {
+    Dimension(self.0.map(|exponent| exponent * power))
+  }
+}
+
+/// Units with positive exponents first and the rest after a `/`, like `kg m/s^2`.
+impl fmt::Display for Dimension {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let units = |positive: bool| {
+      let units: Vec<String> = BASE_UNITS
+        .iter()
+        .zip(self.0)
+        .filter(|&(_, exponent)| exponent != 0 && (exponent > 0) == positive)
+        .map(|(unit, exponent)| match exponent.abs() {
+          1 => unit.to_string(),
+          exponent => format!("{}^{}", unit, exponent),
+        })
+        .collect();
+      units
+    };
+    let (numerator, denominator) = (units(true), units(false));
+    let numerator = match numerator.len() {
+      0 => "1".to_string(),
+      _ => numerator.join(" "),
+    };
+    match denominator.len() {
+      0 => write!(f, "{}", numerator),
+      1 => write!(f, "{}/{}", numerator, denominator[0]),
+      _ => write!(f, "{}/({})", numerator, denominator.join(" ")),
+    }
+  }
+}
+
+impl Evaluator {
+  /// Apply an operator to operands of which at least one is a quantity with units, by applying it
+  /// to the numbers and working out the units of the result separately.
+  pub(crate) fn apply_quantities(
+    &mut self,
+    operator: Operator,
+    left: Value,
+    right: Value,
+  ) -> Result<Value, CalcError> {
+    let (left, left_dimension) = split(left);
+    let (right, right_dimension) = split(right);
+    let mismatch = || CalcError::DimensionMismatch {
+      operator: operator.symbol().to_string(),
+      left: describe(left_dimension),
+      right: describe(right_dimension),
+    };
+    let dimension = match operator {
+      Operator::Multiply => left_dimension.combine(right_dimension, 1),
+      Operator::Divide | Operator::FloorDivide => left_dimension.combine(right_dimension, -1),
+      Operator::Power => {
+        let exponent = right.to_f64();
+        if !right_dimension.is_none() {
+          return Err(CalcError::Unsupported(
+            "an exponent can't have units".to_string(),
+          ));
+        }
+        if exponent.fract() != 0.0 || exponent.abs() > i32::MAX as f64 {
+          return Err(CalcError::Unsupported(
+            "a quantity with units can only be raised to an integer power".to_string(),
+          ));
+        }
+        left_dimension.power(exponent as i32)
+      }
+      _ if left_dimension != right_dimension => return Err(mismatch()),
+      _ if operator.is_comparison() => Dimension::default(),
+      _ => left_dimension,
+    };
+    let result = self.apply(operator, left, right)?;
+    Ok(result.with_dimension(dimension))
+  }
+}
+
+/// Separate a value into its number and its units.
+fn split(value: Value) -> (Value, Dimension) {
+  match value {
+    Value::Quantity(magnitude, dimension) => (Value::Float(magnitude), dimension),
+    number => (number, Dimension::default()),
+  }
+}
+
+fn describe(dimension: Dimension) -> String {
+  if dimension.is_none() {
+    "no unit".to_string()
+  } else {
+    dimension.to_string()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{CalcError, Config, Evaluator, Value};
+
+  fn evaluate(expression: &str) -> Result<String, CalcError> {
+    let mut evaluator = Evaluator::with_config(Config {
+      units: true,
+      ..Config::default()
+    });
+    evaluator
+      .evaluate_typed(expression)
+      .map(|value| value.to_string())
+  }
+
+  #[test]
+  fn test_compatible_addition() {
+    assert_eq!(evaluate("3 m + 2 m"), Ok("5 m".to_string()));
+    assert_eq!(evaluate("3 m - 5 m"), Ok("-2 m".to_string()));
+    assert_eq!(evaluate("-3 s + 1 s"), Ok("-2 s".to_string()));
+    assert_eq!(evaluate("1 m/s + 1 m/s"), Ok("2 m/s".to_string()));
+    assert_eq!(evaluate("2 m > 1 m"), Ok("true".to_string()));
+  }
+
+  #[test]
+  fn test_derived_units() {
+    assert_eq!(evaluate("3 m / 2 s"), Ok("1.5 m/s".to_string()));
+    assert_eq!(evaluate("3 m * 2 m"), Ok("6 m^2".to_string()));
+    assert_eq!(evaluate("3 m^2"), Ok("3 m^2".to_string()));
+    assert_eq!(evaluate("(3 m)^2"), Ok("9 m^2".to_string()));
+    assert_eq!(evaluate("2 kg * 3 m / 2 s^2"), Ok("3 kg m/s^2".to_string()));
+    assert_eq!(evaluate("1 / 4 s"), Ok("0.25 1/s".to_string()));
+    assert_eq!(evaluate("1 m / (2 kg s)"), Ok("0.5 m/(kg s)".to_string()));
+    assert_eq!(evaluate("(2 m)^3 / 4 m^3"), Ok("2".to_string()));
+    assert_eq!(evaluate("3 m / 2 m"), Ok("1.5".to_string()));
+    assert_eq!(evaluate("2 s * 3 / s"), Ok("6".to_string()));
+    assert_eq!(evaluate("6 m / 3"), Ok("2 m".to_string()));
+  }
+
+  #[test]
+  fn test_dimension_mismatch() {
+    assert_eq!(
+      evaluate("3 m + 2 s"),
+      Err(CalcError::DimensionMismatch {
+        operator: "+".to_string(),
+        left: "m".to_string(),
+        right: "s".to_string()
+      })
+    );
+    assert_eq!(
+      evaluate("1 m/s - 1"),
+      Err(CalcError::DimensionMismatch {
+        operator: "-".to_string(),
+        left: "m/s".to_string(),
+        right: "no unit".to_string()
+      })
+    );
+    assert!(evaluate("1 m < 1 kg").is_err());
+    assert!(matches!(
+      evaluate("2 ^ 1 m"),
+      Err(CalcError::Unsupported(_))
+    ));
+    assert!(matches!(
+      evaluate("sqrt(4 m)"),
+      Err(CalcError::Unsupported(_))
+    ));
+  }
+
+  #[test]
+  fn test_units_are_off_by_default() {
+    let mut evaluator = Evaluator::new();
+    evaluator.evaluate("m = 2").unwrap();
+    assert_eq!(evaluator.evaluate_typed("3 m"), Ok(Value::Integer(6)));
+  }
+}
@@ -0,0 +1,187 @@
+use crate::ast::Expr;
+use crate::calculus::calculus_arguments;
+use crate::error::CalcError;
+use crate::evaluator::Evaluator;
+use crate::series::{is_aggregate, series_arguments};
+use crate::table::range_arguments;
+use crate::template::template_arguments;
+use crate::text::text_arguments;
+
+impl Evaluator {
+  /// Check that an expression is well-formed and only refers to functions, constants and variables
+  /// that exist, without evaluating anything. Errors that depend on values, like division by zero,
+  /// can still occur when the expression is evaluated.
+  pub fn validate(&self, expression: &str) -> Result<(), CalcError> {
+    let expr = self.parse(expression)?;
+    self.check(&expr, &mut Vec::new())
+  }
+
+  /// Check a tree, treating the names in `bound` as defined on top of the evaluator's variables.
+  pub(crate) fn check<'a>(
+    &self,
+    expr: &'a Expr,
+    bound: &mut Vec<&'a str>,
+  ) -> Result<(), CalcError> {
+    match expr {
+      Expr::Number(_) | Expr::Constant(_) | Expr::Text(_) | Expr::Unit(_) => Ok(()),
+      Expr::Variable(name) => self.check_variable(name, bound),
+      Expr::Unary(_, operand) => self.check(operand, bound),
+      Expr::Binary(_, left, right) => {
+        self.check(left, bound)?;
+        self.check(right, bound)
+      }
+      Expr::Call(name, arguments) if self.is_series(name) && is_aggregate(name, arguments) => {
+        self.check(&arguments[0], bound)
+      }
+      Expr::Call(name, arguments) if self.is_series(name) => {
+        self.allow_loop(name)?;
+        let (index, start, end, body) = series_arguments(name, arguments)?;
+        self.check(start, bound)?;
+        self.check(end, bound)?;
+        bound.push(index);
+        let result = self.check(body, bound);
+        bound.pop();
+        result
+      }
+      Expr::Call(name, arguments) if self.is_range_extremum(name) => {
+        self.allow_loop(name)?;
+        let (body, variable, bounds) = range_arguments(name, arguments)?;
+        bounds
+          .iter()
+          .try_for_each(|limit| self.check(limit, bound))?;
+        bound.push(variable);
+        let result = self.check(body, bound);
+        bound.pop();
+        result
+      }
+      Expr::Call(name, arguments) if self.is_calculus(name) => {
+        if name == "integrate" {
+          self.allow_loop(name)?;
+        }
+        let (body, variable, points) = calculus_arguments(name, arguments)?;
+        points
+          .iter()
+          .try_for_each(|point| self.check(point, bound))?;
+        bound.push(variable);
+        let result = self.check(body, bound);
+        bound.pop();
+        result
+      }
+      Expr::Call(name, arguments) if self.is_template(name) => {
+        let (_, values) = template_arguments(arguments)?;
+        values.iter().try_for_each(|value| self.check(value, bound))
+      }
+      Expr::Call(name, arguments) if self.is_text_function(name) => {
+        let (argument, _) = text_arguments(name, arguments)?;
+        self.check(argument, bound)
+      }
+      Expr::Call(name, arguments) => {
+        let expected = self
+          .arity(name)
+          .ok_or_else(|| CalcError::UnknownFunction(name.clone()))?;
+        expected.check(name, arguments.len())?;
+        arguments
+          .iter()
+          .try_for_each(|argument| self.check(argument, bound))
+      }
+      Expr::List(items) => items.iter().try_for_each(|item| self.check(item, bound)),
+      Expr::Index(list, index) => {
+        self.check(list, bound)?;
+        self.check(index, bound)
+      }
+      Expr::Assign(name, operator, value) => {
+        self.check_assignable(name)?;
+        self.check(value, bound)?;
+        if operator.is_some() && self.constant(name).is_none() {
+          self.check_variable(name, bound)?;
+        }
+        bound.push(name);
+        Ok(())
+      }
+    }
+  }
+
+  fn check_variable(&self, name: &str, bound: &[&str]) -> Result<(), CalcError> {
+    if bound.contains(&name) || self.variable(name).is_some() || self.recall_name(name).is_some() {
+      Ok(())
+    } else {
+      Err(CalcError::UnknownVariable(name.to_string()))
+    }
+  }
+}
+
+/// Check an expression with the default configuration. See [`Evaluator::validate`].
+pub fn validate(expression: &str) -> Result<(), CalcError> {
+  Evaluator::new().validate(expression)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{validate, CalcError, Evaluator, OperandSide};
+
+  #[test]
+  fn test_valid_expressions() {
+    assert_eq!(validate("2 + 3 * (4 - 1)"), Ok(()));
+    assert_eq!(validate("1/0"), Ok(()));
+    assert_eq!(validate("sqrt(pi) + pct_of(1, 0)"), Ok(()));
+    assert_eq!(validate("sum(i, 1, 3, i^2)"), Ok(()));
+    assert_eq!(validate("x = 2"), Ok(()));
+    assert_eq!(validate("(x = 2) * x"), Ok(()));
+    assert_eq!(validate("mean([1, 2], 3) + sum([4])"), Ok(()));
+  }
+
+  #[test]
+  fn test_malformed_expressions() {
+    assert_eq!(validate(""), Err(CalcError::NoExpression));
+    assert_eq!(validate("(2+3"), Err(CalcError::MismatchedParentheses));
+    assert_eq!(
+      validate("2+"),
+      Err(CalcError::MissingOperand {
+        operator: "+".to_string(),
+        side: OperandSide::Right
+      })
+    );
+    assert_eq!(
+      validate("sqrt(1, 2)"),
+      Err(CalcError::ArityMismatch {
+        function: "sqrt".to_string(),
+        expected: 1,
+        found: 2
+      })
+    );
+  }
+
+  #[test]
+  fn test_unknown_names() {
+    assert_eq!(
+      validate("2 * y"),
+      Err(CalcError::UnknownVariable("y".to_string()))
+    );
+    assert_eq!(
+      validate("x += 1"),
+      Err(CalcError::UnknownVariable("x".to_string()))
+    );
+    assert_eq!(
+      validate("sum(i, 1, 3, j)"),
+      Err(CalcError::UnknownVariable("j".to_string()))
+    );
+    assert_eq!(
+      validate("nope(1)"),
+      Err(CalcError::UnknownFunction("nope".to_string()))
+    );
+    assert_eq!(
+      validate("pi = 3"),
+      Err(CalcError::ConstantAssignment("pi".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_validation_sees_evaluator_state() {
+    let mut evaluator = Evaluator::new();
+    assert!(evaluator.validate("y + ans").is_err());
+    evaluator.evaluate("y = 4").unwrap();
+    assert_eq!(evaluator.validate("y + ans"), Ok(()));
+    assert_eq!(evaluator.validate("y += 1"), Ok(()));
+    assert_eq!(evaluator.variable("y").map(|y| y.to_f64()), Some(4.0));
+  }
+}
@@ -0,0 +1,111 @@
+use std::fmt;
+
+use crate::rational::Rational;
+use crate::units::Dimension;
+
+/// The result of an evaluation, tagged with what kind of number it turned out to be.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+  /// A whole number, whether it was computed exactly or happens to be an integral float.
+  Integer(i64),
+  /// An exact fraction that isn't whole. Only produced in fraction mode.
+  Rational(Rational),
+  /// Anything else: results of inexact operations or numbers too large for an integer.
+  Float(f64),
+  /// The result of a comparison like `3 > 2`. In arithmetic it counts as `1` or `0`.
+  Boolean(bool),
+  /// A list like `[1, 2, 3]`, which aggregate functions like `mean` accept.
+  List(Vec<Value>),
+  /// A number of units, like `3 m/s`, measured in the base units. Only produced in units mode.
+  Quantity(f64, Dimension),
+  /// Text, like the numeral `roman(4)` gives. It can be a result, but no number.
+  Text(String),
+}
+
+impl Value {
+  /// Classify a float result, reporting it as an integer when it is one.
+  pub fn from_f64(value: f64) -> Value {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+      Value::Integer(value as i64)
+    } else {
+      Value::Float(value)
+    }
+  }
+
+  /// Integers are reported as such, everything else keeps its fraction.
+  pub fn from_rational(rational: Rational) -> Value {
+    if rational.is_integer() {
+      Value::Integer(rational.numerator())
+    } else {
+      Value::Rational(rational)
+    }
+  }
+
+  /// Report integral floats as integers, leaving every other value as it is.
+  pub fn classify(self) -> Value {
+    match self {
+      Value::Float(float) => Value::from_f64(float),
+      Value::List(items) => Value::List(items.into_iter().map(Value::classify).collect()),
+      exact => exact,
+    }
+  }
+
+  /// The value as a float. A list isn't a single number and gives NaN, and a quantity gives its
+  /// number without the units.
+  pub fn to_f64(&self) -> f64 {
+    match *self {
+      Value::Integer(integer) => integer as f64,
+      Value::Rational(rational) => rational.to_f64(),
+      Value::Float(float) => float,
+      Value::Boolean(boolean) => boolean as u8 as f64,
+      Value::List(_) | Value::Text(_) => f64::NAN,
+      Value::Quantity(magnitude, _) => magnitude,
+    }
+  }
+
+  /// The exact value, if there is one.
+  pub fn to_rational(&self) -> Option<Rational> {
+    match *self {
+      Value::Integer(integer) => Some(Rational::from_integer(integer)),
+      Value::Rational(rational) => Some(rational),
+      Value::Boolean(boolean) => Some(Rational::from_integer(boolean as i64)),
+      Value::Float(_) | Value::List(_) | Value::Quantity(..) | Value::Text(_) => None,
+    }
+  }
+
+  /// Attach units to a number, leaving it as it is if there are none.
+  pub(crate) fn with_dimension(self, dimension: Dimension) -> Value {
+    if dimension.is_none() {
+      self
+    } else {
+      Value::Quantity(self.to_f64(), dimension)
+    }
+  }
+
+  /// The numbers in a value: the value itself, or every number in a list and its nested lists.
+  pub(crate) fn flatten(self) -> Vec<Value> {
+    match self {
+      Value::List(items) => items.into_iter().flat_map(Value::flatten).collect(),
+      number => vec![number],
+    }
+  }
+}
+
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Value::Integer(integer) => write!(f, "{}", integer),
+      Value::Rational(rational) => write!(f, "{}", rational),
+      Value::Float(float) => write!(f, "{:?}", float),
+      Value::Boolean(boolean) => write!(f, "{}", boolean),
+      Value::List(items) => {
+        let items: Vec<String> = items.iter().map(Value::to_string).collect();
+        write!(f, "[{}]", items.join(", "))
+      }
+      Value::Quantity(magnitude, dimension) => {
+        write!(f, "{} {}", Value::from_f64(*magnitude), dimension)
+      }
+      Value::Text(text) => write!(f, "{}", text),
+    }
+  }
+}
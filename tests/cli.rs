@@ -0,0 +1,78 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn calculator(arguments: &[&str]) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_calculator"))
+    .args(arguments)
+    .env_remove("CALC_EXPR")
+    .output()
+    .expect("the calculator should run")
+}
+
+fn text(bytes: &[u8]) -> &str {
+  std::str::from_utf8(bytes).unwrap()
+}
+
+#[test]
+fn test_success() {
+  let output = calculator(&["1 + 2", "x = 4; x * 2"]);
+  assert_eq!(text(&output.stdout), "3\n8\n");
+  assert_eq!(text(&output.stderr), "");
+  assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_errors() {
+  let output = calculator(&["1 + 2", "y", "5"]);
+  assert_eq!(text(&output.stdout), "3\n5\n");
+  assert_eq!(text(&output.stderr), "Error: Unknown variable 'y'\n");
+  assert_eq!(output.status.code(), Some(1));
+
+  let output = calculator(&["--precision"]);
+  assert_eq!(text(&output.stdout), "");
+  assert!(text(&output.stderr).starts_with("Error: "));
+  assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_check_syntax() {
+  let output = calculator(&["--check-syntax", "2 + 3 * (4 - 1)", "1/0"]);
+  assert_eq!(text(&output.stdout), "");
+  assert_eq!(text(&output.stderr), "");
+  assert_eq!(output.status.code(), Some(0));
+
+  let output = calculator(&["--check-syntax", "2+*3"]);
+  assert_eq!(text(&output.stdout), "");
+  assert_eq!(
+    text(&output.stderr),
+    "Error: Operator '+' is missing its right operand\n"
+  );
+  assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_thousands() {
+  let output = calculator(&["--thousands", "1234567.5"]);
+  assert_eq!(text(&output.stdout), "1,234,567.5\n");
+
+  let output = calculator(&["--thousands", "--decimal-point", ",", "1234567.5"]);
+  assert_eq!(text(&output.stdout), "1.234.567,5\n");
+}
+
+#[test]
+fn test_repl_closed_stdout() {
+  let mut child = Command::new(env!("CARGO_BIN_EXE_calculator"))
+    .env_remove("CALC_EXPR")
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .expect("the calculator should run");
+  drop(child.stdout.take());
+  let mut stdin = child.stdin.take().unwrap();
+  stdin.write_all(b"config\nfunctions\n").unwrap();
+  drop(stdin);
+  let output = child.wait_with_output().unwrap();
+  assert!(text(&output.stderr).starts_with("Error: Can't write to standard output: "));
+  assert_eq!(output.status.code(), Some(1));
+}